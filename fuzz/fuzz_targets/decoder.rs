@@ -0,0 +1,52 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+//! Feeds arbitrary bytes through packet framing, split into reads of arbitrary size.
+
+#![no_main]
+
+use bytes::BytesMut;
+use crawlspace::protocol::Decoder;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&flags, data)) = data.split_first() else {
+        return;
+    };
+
+    let mut decoder = Decoder::new();
+    if flags & 1 == 1 {
+        decoder.set_compression(Some(256));
+    }
+
+    // the rest of the flags byte decides how much each read gets, like a socket would
+    let read_size = (flags >> 1).max(1) as usize;
+    for read in data.chunks(read_size) {
+        decoder.add_bytes(BytesMut::from(read));
+
+        loop {
+            match decoder.try_read_next() {
+                Ok(Some(_)) => continue,
+                Ok(None) => break,
+                // the connection would be dropped here
+                Err(_) => return,
+            }
+        }
+    }
+});
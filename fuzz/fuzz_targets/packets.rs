@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+//! Decodes arbitrary bytes as each serverbound packet. The first byte picks which one.
+
+#![no_main]
+
+use crawlspace::protocol::{
+    packets::{login::*, play::*},
+    Decode,
+};
+use libfuzzer_sys::fuzz_target;
+
+macro_rules! decoders {
+    ($($packet:ident),* $(,)?) => {
+        &[$(|mut r: &[u8]| {
+            let _ = $packet::decode(&mut r);
+        }),*]
+    };
+}
+
+const DECODERS: &[fn(&[u8])] = decoders![
+    HandshakeS,
+    StatusRequestS,
+    Ping,
+    LoginStartS,
+    EncryptionResponseS,
+    PluginResponseS,
+    LoginAckS,
+    ClientInformationConfigS,
+    KnownPacksS,
+    FinishConfigurationAckS,
+    ConfirmTeleportS,
+    ChatCommandS,
+    ChatMessageS,
+    ClientStatusS,
+    ClientInformationS,
+    ClickContainerS,
+    CloseContainerS,
+    ConfigurationAckS,
+    InteractS,
+    KeepAliveS,
+    SetPlayerPositionS,
+    SetPlayerPositionAndRotationS,
+    SetPlayerRotationS,
+    PickItemS,
+    PongS,
+    ResourcePackResponseS,
+    UseItemOnS,
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&which, body)) = data.split_first() else {
+        return;
+    };
+
+    DECODERS[which as usize % DECODERS.len()](body);
+});
@@ -21,10 +21,11 @@ use clap::Parser;
 
 #[derive(Debug, Parser)]
 pub struct Args {
-    /// The directory to load the map from. Should be DIM1, or the equivalent renamed folder.
-    #[arg(env = "LIMBO_WORLD")]
-    pub map_dir: String,
-    /// The address to serve crawlspace on.
+    /// The directories to load maps from. Should be DIM1, or the equivalent renamed folder. Each
+    /// can be named with `name=dir`, otherwise the folder name is used. Players spawn in the first.
+    #[arg(env = "LIMBO_WORLD", required = true, value_delimiter = ',')]
+    pub map_dirs: Vec<String>,
+    /// The IP address to serve crawlspace on, e.g. `0.0.0.0` for IPv4 only.
     #[arg(short, long, default_value = "[::]", env = "LIMBO_ADDRESS")]
     pub addr: String,
     /// The port to serve crawlspace on. Defaults to 25565 if not set.
@@ -42,12 +43,109 @@ pub struct Args {
     /// The z coordinate of the spawnpoint.
     #[arg(short = 'z', long, default_value = "0", env = "LIMBO_SPAWN_Z")]
     pub spawn_z: f64,
+    /// Move the spawnpoint up or down onto the nearest solid ground, so a bad spawn y doesn't
+    /// drop players into the void or inside blocks.
+    #[arg(long, env = "LIMBO_SAFE_SPAWN")]
+    pub safe_spawn: bool,
+    /// How many chunks to load from the map in each direction around chunk (0, 0). Anything
+    /// outside is dropped.
+    #[arg(long, default_value = "10", env = "LIMBO_CHUNK_RADIUS")]
+    pub chunk_radius: i32,
+    /// Moves the map by this many chunks on x and z, e.g. `--origin-offset 4 -2`. The
+    /// spawnpoint and border are where they'd be in the moved map.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["X", "Z"],
+        value_delimiter = ',',
+        allow_negative_numbers = true,
+        env = "LIMBO_ORIGIN_OFFSET"
+    )]
+    pub origin_offset: Vec<i32>,
     /// The border radius, centered around the spawnpoint. Defaults to 10 chunks. One
     /// chunk past the border will be loaded.
     #[arg(short = 'b', long, default_value = "160", env = "LIMBO_BORDER_RADIUS")]
     pub border_radius: i32,
+    /// How far away, in blocks, players will see each other move. Anything broadcast about a
+    /// player only goes to players this close. Defaults to 64 blocks, or 4 chunks.
+    #[arg(
+        long,
+        alias = "entity-broadcast-range",
+        default_value = "64",
+        env = "LIMBO_ENTITY_VIEW_RANGE"
+    )]
+    pub entity_view_range: f64,
     #[arg(short, long, default_value = "Limbo")]
     pub motd: String,
+    /// Text shown above the player list in the tab list.
+    #[arg(long, default_value = "", env = "LIMBO_TAB_HEADER")]
+    pub tab_header: String,
+    /// Text shown below the player list in the tab list.
+    #[arg(long, default_value = "", env = "LIMBO_TAB_FOOTER")]
+    pub tab_footer: String,
+    /// A different MOTD to show clients connecting with a specific hostname, as `host=motd`. Can
+    /// be given more than once, or separated with semicolons.
+    #[arg(long, env = "LIMBO_VIRTUAL_HOSTS", value_delimiter = ';')]
+    pub virtual_host: Vec<String>,
+    /// Feature flags to enable on clients, separated by commas. Add experimental flags (like
+    /// `minecraft:trade_rebalance`) for maps that use them.
+    #[arg(
+        long,
+        default_value = "minecraft:vanilla",
+        env = "LIMBO_FEATURE_FLAGS",
+        value_delimiter = ','
+    )]
+    pub feature_flags: Vec<String>,
     #[arg(long, default_value = "500", env = "LIMBO_MAX_PLAYERS")]
     pub max_players: usize,
+    /// A file to append a JSON line to for every connection once it closes.
+    #[arg(long, env = "LIMBO_CONNECTION_LOG")]
+    pub connection_log: Option<String>,
+    /// Usernames allowed to run operator-only commands, separated by commas.
+    #[arg(long, env = "LIMBO_OPS", value_delimiter = ',')]
+    pub ops: Vec<String>,
+    /// Kick players who haven't moved or interacted with anything for this many minutes. Ops are
+    /// never kicked. Disabled if unset.
+    #[arg(long, env = "LIMBO_IDLE_KICK_MINUTES")]
+    pub idle_kick_minutes: Option<u64>,
+    /// Usernames that are never kicked for being idle, separated by commas.
+    #[arg(long, env = "LIMBO_IDLE_KICK_EXEMPT", value_delimiter = ',')]
+    pub idle_kick_exempt: Vec<String>,
+    /// A directory to cache encoded chunks in, so unchanged maps start faster. Disabled if unset.
+    #[arg(long, env = "LIMBO_CACHE_DIR")]
+    pub cache_dir: Option<String>,
+    /// Gzip the world cache. Smaller on disk, but slower to load.
+    #[arg(long, env = "LIMBO_CACHE_COMPRESSION")]
+    pub cache_compression: bool,
+    /// Compress packets at least this many bytes big. Negative turns compression off, which is
+    /// best behind a proxy that compresses anyway.
+    #[cfg(feature = "compression")]
+    #[arg(long, default_value = "256", env = "LIMBO_COMPRESSION_THRESHOLD")]
+    pub compression_threshold: i32,
+    /// Check players own their accounts with Mojang's session server, and encrypt the connection.
+    /// Leave this off behind a proxy, which does it instead.
+    #[cfg(feature = "encryption")]
+    #[arg(long, env = "LIMBO_ONLINE_MODE")]
+    pub online_mode: bool,
+    /// Close and reopen containers when their contents change, for clients that don't redraw an
+    /// open window on a plain content update.
+    #[arg(long, env = "LIMBO_REOPEN_CONTAINERS")]
+    pub reopen_containers: bool,
+    /// A resource pack URL to send players when they join.
+    #[arg(long, env = "LIMBO_RESOURCE_PACK")]
+    pub resource_pack: Option<String>,
+    /// The resource pack's SHA-1 hash, in hex. Clients skip checking it if unset.
+    #[arg(long, env = "LIMBO_RESOURCE_PACK_HASH")]
+    pub resource_pack_hash: Option<String>,
+    /// Don't spawn players until they've loaded the resource pack, and disconnect them if they
+    /// decline it or it fails to load.
+    #[arg(long, env = "LIMBO_RESOURCE_PACK_FORCED")]
+    pub resource_pack_forced: bool,
+    /// Serve the world read-only: players spectate, time is frozen, and containers can be looked
+    /// in but not taken from.
+    #[arg(long, env = "LIMBO_SHOWCASE")]
+    pub showcase: bool,
+    /// Load the world, report any problems with it, then exit without serving.
+    #[arg(long)]
+    pub validate: bool,
 }
@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+//! Crawlspace as a library, for starting a server in-process (e.g. from a load tester or map
+//! tooling) instead of through the binary.
+
+use std::{collections::BTreeSet, net::SocketAddr, sync::Arc};
+
+use args::Args;
+#[cfg(feature = "library")]
+use clap::Parser;
+use color_eyre::eyre::Result;
+use net::{cache::WorldCache, disk_cache};
+use server::Server;
+use world::{read_world, LoadReport, World};
+
+#[macro_use]
+extern crate tracing;
+
+pub mod args;
+pub mod net;
+pub mod protocol;
+pub mod server;
+pub mod state;
+pub mod world;
+
+pub const VERSION: &str = "1.21.1";
+pub const VERSION_NUM: i32 = 767;
+pub const TICK_RATE: u8 = 20;
+
+pub type CrawlState = Arc<state::State>;
+
+/// A running server. Dropping this leaves it running - call [`Crawlspace::shutdown`] to stop it.
+#[derive(Debug)]
+pub struct Crawlspace {
+    state: CrawlState,
+    server: Arc<Server>,
+    local_addr: SocketAddr,
+}
+
+/// Configures a [`Crawlspace`] before starting it.
+#[derive(Debug)]
+pub struct CrawlspaceBuilder {
+    args: Args,
+}
+
+impl Crawlspace {
+    /// Starts configuring a server for the world in `map_dir`. Anything not set on the builder
+    /// uses the same defaults (and `LIMBO_` environment variables) as the binary, so this fails
+    /// if one of those is invalid.
+    #[cfg(feature = "library")]
+    pub fn builder(map_dir: &str) -> Result<CrawlspaceBuilder> {
+        let args = Args::try_parse_from(["crawlspace", map_dir])?;
+        Ok(CrawlspaceBuilder::from_args(args))
+    }
+
+    /// The address the server is listening on. Useful when it was started on port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn state(&self) -> &CrawlState {
+        &self.state
+    }
+
+    pub fn server(&self) -> &Arc<Server> {
+        &self.server
+    }
+
+    /// Stops accepting connections and stops ticking.
+    pub fn shutdown(&self) {
+        self.state.shutdown_token.cancel();
+    }
+}
+
+impl CrawlspaceBuilder {
+    pub fn from_args(args: Args) -> Self {
+        Self { args }
+    }
+
+    #[cfg(feature = "library")]
+    pub fn addr(mut self, addr: impl Into<String>) -> Self {
+        self.args.addr = addr.into();
+        self
+    }
+
+    /// The port to listen on. 0 picks any free one, see [`Crawlspace::local_addr`].
+    #[cfg(feature = "library")]
+    pub fn port(mut self, port: u16) -> Self {
+        self.args.port = port;
+        self
+    }
+
+    #[cfg(feature = "library")]
+    pub fn motd(mut self, motd: impl Into<String>) -> Self {
+        self.args.motd = motd.into();
+        self
+    }
+
+    #[cfg(feature = "library")]
+    pub fn max_players(mut self, max_players: usize) -> Self {
+        self.args.max_players = max_players;
+        self
+    }
+
+    #[cfg(feature = "library")]
+    pub fn velocity_forwarding(mut self, velocity_forwarding: bool) -> Self {
+        self.args.velocity_forwarding = velocity_forwarding;
+        self
+    }
+
+    /// Loads the worlds, starts listening, and starts ticking.
+    pub async fn start(self) -> Result<Crawlspace> {
+        check_assets()?;
+
+        let state = Arc::new(state::State::new(VERSION, VERSION_NUM, self.args)?);
+        let worlds = load_worlds(&state, false)?;
+
+        #[cfg(feature = "lan")]
+        net::spawn_lan_broadcast(state.clone()).await?;
+
+        let local_addr = net::spawn_net_handler(state.clone()).await?;
+
+        let server = Server::new(state.clone(), worlds, TICK_RATE);
+
+        {
+            let mut ticker = server.ticker;
+            let server = server.clone();
+            let token = state.shutdown_token.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = ticker.run(server) => (),
+                    _ = token.cancelled() => debug!("Stopped ticking."),
+                }
+            });
+        }
+
+        Ok(Crawlspace {
+            state,
+            server,
+            local_addr,
+        })
+    }
+}
+
+/// Loads the worlds in `args` and reports any problems with them, without serving anything.
+pub fn validate(args: Args) -> Result<()> {
+    check_assets()?;
+
+    let state = Arc::new(state::State::new(VERSION, VERSION_NUM, args)?);
+    load_worlds(&state, true)?;
+
+    Ok(())
+}
+
+/// Parses bundled data up front so a broken file fails here, not in the middle of a login.
+fn check_assets() -> Result<()> {
+    protocol::packets::login::registry::all_registries()?;
+    server::registries::try_registries()?;
+    server::tags::try_tags()?;
+
+    Ok(())
+}
+
+fn load_worlds(state: &CrawlState, validate: bool) -> Result<Vec<(String, WorldCache)>> {
+    let mut worlds = Vec::new();
+    for spec in &state.worlds {
+        // validating needs the world itself, so skip the cache entirely
+        let cache_path = match (&state.cache_dir, validate) {
+            (Some(dir), false) => Some(disk_cache::cache_path(state, spec, dir)?),
+            _ => None,
+        };
+
+        if let Some(path) = cache_path.as_ref().filter(|p| p.exists()) {
+            match disk_cache::load(path) {
+                Ok(world_cache) => {
+                    info!("Loaded world {} from {}", spec.name, path.display());
+                    worlds.push((spec.name.clone(), world_cache));
+                    continue;
+                }
+                Err(why) => warn!("Failed to load cache {}: {why}", path.display()),
+            }
+        }
+
+        info!("Loading world {} from {}", spec.name, spec.dir);
+        let (world, report) = read_world(&spec.dir, state.chunk_bounds, state.origin_offset)?;
+        info!("Done.");
+
+        info!("Generating world chunk packets");
+        let world_cache = WorldCache::from_anvil(state.clone(), &world);
+        info!("Done.");
+
+        if validate {
+            info!("World {}:", spec.name);
+            report_validation(&world, &report, &world_cache);
+        }
+
+        if let Some(path) = cache_path {
+            match disk_cache::save(&world_cache, spec, &path, state.cache_compression) {
+                Ok(()) => info!("Cached world {} to {}", spec.name, path.display()),
+                Err(why) => warn!("Failed to write cache {}: {why}", path.display()),
+            }
+        }
+
+        worlds.push((spec.name.clone(), world_cache));
+    }
+
+    Ok(worlds)
+}
+
+fn report_validation(world: &World, report: &LoadReport, world_cache: &WorldCache) {
+    let unknown_block_entities = world
+        .0
+        .values()
+        .flat_map(|c| c.block_entities.iter())
+        .filter_map(|e| world::BlockEntity::try_parse(e.clone()).ok())
+        .filter(|e| protocol::packets::play::block_entity_kind(&e.id).is_none())
+        .map(|e| e.id)
+        .collect::<BTreeSet<_>>();
+
+    info!("Chunks loaded: {}", report.chunks_loaded);
+    info!("Containers found: {}", world_cache.containers.len());
+
+    for failure in &report.failed_regions {
+        warn!("Failed to open {failure}");
+    }
+
+    for failure in &report.failed_chunks {
+        warn!("Failed to load {failure}");
+    }
+
+    for id in &unknown_block_entities {
+        warn!("Unknown block entity type {id} will not be sent to clients");
+    }
+
+    match report.failed_regions.is_empty()
+        && report.failed_chunks.is_empty()
+        && unknown_block_entities.is_empty()
+    {
+        true => info!("World looks good!"),
+        false => warn!(
+            "Found {} failed regions, {} failed chunks and {} unknown block entity types",
+            report.failed_regions.len(),
+            report.skipped_chunks(),
+            unknown_block_entities.len()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::world::{fixtures, ChunkBounds};
+
+    #[test]
+    fn validate_reports_corrupt_chunks() {
+        let map = fixtures::MapDir::new("validate");
+        let good = fixtures::chunk(0, 0, vec![fixtures::air_section(0)]);
+        let region = fixtures::region(&[
+            ((0, 0), fixtures::compress(&good)),
+            ((1, 0), b"not a chunk".to_vec()),
+        ]);
+        map.write_region("r.0.0.mca", &region);
+
+        let (world, report) = read_world(map.path(), ChunkBounds::default(), (0, 0)).unwrap();
+        assert!(world.0.contains_key(&(0, 0)));
+        assert_eq!(report.chunks_loaded, 1);
+        assert_eq!(report.skipped_chunks(), 1);
+        assert!(report.failed_regions.is_empty());
+
+        let args = Args::try_parse_from(["crawlspace", map.path(), "--validate"]).unwrap();
+        validate(args).unwrap();
+    }
+}
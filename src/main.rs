@@ -17,31 +17,12 @@
  * <https://www.gnu.org/licenses/>.
  */
 
-use std::{fs::OpenOptions, sync::Arc};
+use std::fs::OpenOptions;
 
-use args::Args;
 use clap::Parser;
 use color_eyre::eyre::Result;
-use net::cache::{RegistryCache, WorldCache};
-use server::Server;
+use crawlspace::{args::Args, CrawlspaceBuilder};
 use tracing_subscriber::{layer::SubscriberExt, prelude::*, EnvFilter};
-use world::read_world;
-
-#[macro_use]
-extern crate tracing;
-
-mod args;
-mod net;
-mod protocol;
-mod server;
-mod state;
-mod world;
-
-const VERSION: &str = "1.21.1";
-const VERSION_NUM: i32 = 767;
-const TICK_RATE: u8 = 20;
-
-type CrawlState = Arc<state::State>;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -70,31 +51,15 @@ async fn main() -> Result<()> {
 
     let args = Args::parse();
 
-    info!("Loading world");
-    let world = read_world(&args.map_dir)?;
-    info!("Done.");
-
-    let state = Arc::new(state::State::new(VERSION, VERSION_NUM, args));
-
-    info!("Generating world chunk packets");
-    let world_cache = WorldCache::from_anvil(state.clone(), &world);
-    info!("Done.");
-
-    #[cfg(feature = "lan")]
-    net::spawn_lan_broadcast(state.clone()).await?;
-
-    net::spawn_net_handler(state.clone()).await?;
-
-    let server = Server::new(state.clone(), world_cache, TICK_RATE);
-
-    {
-        let mut ticker = server.ticker;
-        tokio::spawn(async move { ticker.run(server).await });
+    if args.validate {
+        return crawlspace::validate(args);
     }
 
+    let crawlspace = CrawlspaceBuilder::from_args(args).start().await?;
+
     // TODO: more graceful shutdown?
     tokio::signal::ctrl_c().await?;
-    state.shutdown_token.cancel();
+    crawlspace.shutdown();
 
     Ok(())
 }
@@ -0,0 +1,141 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use serde::Deserialize;
+
+use crate::protocol::{
+    datatypes::{Bounded, OptionalPrefixed},
+    Property,
+};
+
+#[cfg(feature = "encryption")]
+use {
+    color_eyre::eyre::{bail, Context, Result},
+    num_bigint::BigInt,
+    reqwest::StatusCode,
+    rsa::{pkcs8::EncodePublicKey, Pkcs1v15Encrypt, RsaPrivateKey},
+    sha1::{Digest, Sha1},
+    uuid::Uuid,
+};
+
+#[cfg(feature = "encryption")]
+const HAS_JOINED_URL: &str = "https://sessionserver.mojang.com/session/minecraft/hasJoined";
+
+/// A signed property on a player's profile, like their skin.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProfileProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+impl ProfileProperty {
+    pub fn as_property(&self) -> Property<'_> {
+        Property {
+            name: Bounded(&self.name),
+            value: Bounded(&self.value),
+            signature: OptionalPrefixed(self.signature.as_deref().map(Bounded)),
+        }
+    }
+}
+
+/// A player's profile, as the session server has it.
+#[cfg(feature = "encryption")]
+#[derive(Debug, Deserialize)]
+pub struct GameProfile {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub properties: Vec<ProfileProperty>,
+}
+
+/// What online mode needs: the keypair clients encrypt the shared secret with, and a client for
+/// asking the session server about them.
+#[cfg(feature = "encryption")]
+#[derive(Debug)]
+pub struct Authenticator {
+    private_key: RsaPrivateKey,
+    /// The public key, DER encoded, as it's sent to clients.
+    pub public_key: Vec<u8>,
+    http: reqwest::Client,
+}
+
+#[cfg(feature = "encryption")]
+impl Authenticator {
+    /// Generates a new keypair. Like vanilla, it's 1024 bits and only lives as long as the server.
+    pub fn new() -> Result<Self> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)
+            .context("Failed to generate server keypair")?;
+        let public_key = private_key
+            .to_public_key()
+            .to_public_key_der()
+            .context("Failed to encode server public key")?
+            .as_bytes()
+            .to_vec();
+
+        Ok(Self {
+            private_key,
+            public_key,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.private_key
+            .decrypt(Pkcs1v15Encrypt, data)
+            .context("Failed to decrypt with server key")
+    }
+
+    /// The server id the client and session server both hash the login with. It's a SHA-1, printed
+    /// as a signed hex number the way Java's `BigInteger` does it.
+    pub fn server_hash(&self, shared_secret: &[u8]) -> String {
+        let digest = Sha1::new()
+            .chain_update(shared_secret)
+            .chain_update(&self.public_key)
+            .finalize();
+
+        BigInt::from_signed_bytes_be(&digest).to_str_radix(16)
+    }
+
+    /// Asks the session server whether `username` joined with `server_hash`, and gets their
+    /// profile if they did. `None` means they didn't, so they don't own the account.
+    pub async fn has_joined(
+        &self,
+        username: &str,
+        server_hash: &str,
+    ) -> Result<Option<GameProfile>> {
+        let res = self
+            .http
+            .get(HAS_JOINED_URL)
+            .query(&[("username", username), ("serverId", server_hash)])
+            .send()
+            .await
+            .context("Failed to reach the session server")?;
+
+        match res.status() {
+            StatusCode::OK => {
+                Ok(Some(res.json().await.context(
+                    "Failed to parse profile from the session server",
+                )?))
+            }
+            StatusCode::NO_CONTENT => Ok(None),
+            status => bail!("Session server responded with {status}"),
+        }
+    }
+}
@@ -19,25 +19,37 @@
 
 use std::{cmp::Ordering, collections::HashMap};
 
+use color_eyre::eyre::{eyre, Result, WrapErr};
 use rayon::prelude::*;
 
 use crate::{
     protocol::{
         datatypes::VarInt,
         packets::{
-            login::registry::{AllRegistries, Registry},
-            play::ChunkDataUpdateLightC,
+            login::{
+                registry::{AllRegistries, Registry, RegistryItem},
+                UpdateTagsConfigC,
+            },
+            play::{ChunkDataUpdateLightC, RegistryTags, UpdateTagsC},
         },
         Encoder,
     },
+    server::{registries::try_registries, tags::Tags},
     world::{blocks::Blocks, BlockEntity, Container, World},
     CrawlState,
 };
 
+/// How far up or down `--safe-spawn` looks for solid ground.
+const SAFE_SPAWN_RANGE: i32 = 64;
+
 #[derive(Debug)]
 pub struct WorldCache {
-    pub encoded: Vec<Vec<u8>>,
+    /// Encoded chunk packets, keyed by chunk x/z.
+    pub encoded: Vec<((i32, i32), Vec<u8>)>,
     pub containers: HashMap<(i32, i32, i32), Container>,
+    /// Where players spawn in this world. This is the configured spawnpoint unless
+    /// `--safe-spawn` moved it onto solid ground.
+    pub spawnpoint: (f64, f64, f64),
 }
 
 impl WorldCache {
@@ -88,11 +100,15 @@ impl WorldCache {
                             _ => None,
                         }
                     })
-                    .map(|container| {
-                        (
-                            (container.x, container.y, container.z),
-                            Container::try_from(container).expect("Failed to convert container from block entity NBT"),
-                        )
+                    .filter_map(|container| {
+                        let pos = (container.x, container.y, container.z);
+                        match Container::try_from(container) {
+                            Ok(container) => Some((pos, container)),
+                            Err(why) => {
+                                warn!("Failed to read container at {pos:?}, ignoring: {why}");
+                                None
+                            }
+                        }
                     })
                     .collect::<Vec<((i32, i32, i32), Container)>>()
             })
@@ -103,22 +119,36 @@ impl WorldCache {
 
         let encoded = chunks
             .par_iter()
-            .map(|(_, chunk)| {
+            .filter_map(|(pos, chunk)| {
                 let mut encoder = Encoder::new();
-                encoder
-                    .append_packet(&ChunkDataUpdateLightC::new(
-                        crawlstate.clone(),
-                        chunk,
-                        &block_states,
-                    ))
-                    .expect("Failed to append packet to encoder");
-                encoder.take().to_vec()
+                let packet = ChunkDataUpdateLightC::new(crawlstate.clone(), chunk, &block_states);
+                match encoder.append_packet(&packet) {
+                    Ok(()) => Some((**pos, encoder.take().to_vec())),
+                    Err(why) => {
+                        warn!("Failed to encode chunk {pos:?}, skipping: {why}");
+                        None
+                    }
+                }
             })
             .collect();
 
+        let spawnpoint = match crawlstate.safe_spawn {
+            false => crawlstate.spawnpoint,
+            true => world
+                .find_safe_spawn(crawlstate.spawnpoint, SAFE_SPAWN_RANGE)
+                .unwrap_or_else(|| {
+                    warn!(
+                        "Couldn't find solid ground within {SAFE_SPAWN_RANGE} blocks of the spawnpoint {:?}, using it as is",
+                        crawlstate.spawnpoint
+                    );
+                    crawlstate.spawnpoint
+                }),
+        };
+
         Self {
             encoded,
             containers,
+            spawnpoint,
         }
     }
 }
@@ -128,46 +158,198 @@ pub struct RegistryCache {
     pub encoded: Vec<u8>,
     pub the_end_id: VarInt,
     pub the_end_biome_id: u16,
+    /// The lowest block y in the end, for heightmaps.
+    pub the_end_min_y: i32,
+    /// How tall the end is, for heightmaps.
+    pub the_end_height: i32,
 }
 
-impl From<&AllRegistries> for RegistryCache {
-    fn from(registry: &AllRegistries) -> Self {
+impl TryFrom<&AllRegistries> for RegistryCache {
+    type Error = color_eyre::eyre::Report;
+
+    fn try_from(registry: &AllRegistries) -> Result<Self> {
         let mut encoder = Encoder::new();
 
         let dimensions = Registry::from(registry.dimension_type.clone());
         let biomes = Registry::from(registry.biome.clone());
         encoder
             .append_packet(&Registry::from(registry.trim_material.clone()))
-            .expect("Failed to encode trim material");
+            .wrap_err("Failed to encode trim material")?;
         encoder
             .append_packet(&Registry::from(registry.trim_pattern.clone()))
-            .expect("Failed to encode trim pattern");
+            .wrap_err("Failed to encode trim pattern")?;
         encoder
             .append_packet(&Registry::from(registry.banner_pattern.clone()))
-            .expect("Failed to encode banner pattern");
+            .wrap_err("Failed to encode banner pattern")?;
         encoder
             .append_packet(&Registry::from(registry.chat_type.clone()))
-            .expect("Failed to encode chat type");
+            .wrap_err("Failed to encode chat type")?;
         encoder
             .append_packet(&Registry::from(registry.damage_type.clone()))
-            .expect("Failed to encode damage type");
+            .wrap_err("Failed to encode damage type")?;
         encoder
             .append_packet(&dimensions)
-            .expect("Failed to encode dimensions");
+            .wrap_err("Failed to encode dimensions")?;
         encoder
             .append_packet(&biomes)
-            .expect("Failed to encode biomes");
+            .wrap_err("Failed to encode biomes")?;
         encoder
             .append_packet(&Registry::from(registry.wolf_variant.clone()))
-            .expect("Failed to encode wolf variants");
+            .wrap_err("Failed to encode wolf variants")?;
         encoder
             .append_packet(&Registry::from(registry.painting_variant.clone()))
-            .expect("Failed to encode painting variants");
+            .wrap_err("Failed to encode painting variants")?;
 
-        Self {
+        let the_end_id = required_index(&dimensions, "minecraft:the_end")?;
+        let the_end_biome_id = required_index(&biomes, "minecraft:the_end")?;
+        let the_end = registry
+            .dimension_type
+            .get("minecraft:the_end")
+            .ok_or_else(|| {
+                eyre!("your registries.json is missing the minecraft:the_end dimension type")
+            })?;
+
+        Ok(Self {
             encoded: encoder.take().to_vec(),
-            the_end_id: VarInt(dimensions.index_of("minecraft:the_end")),
-            the_end_biome_id: biomes.index_of("minecraft:the_end") as u16,
-        }
+            the_end_id: VarInt(the_end_id),
+            the_end_biome_id: the_end_biome_id as u16,
+            the_end_min_y: the_end.min_y(),
+            the_end_height: the_end.height(),
+        })
+    }
+}
+
+/// Tags, resolved to protocol ids and encoded for both configuration and play.
+#[derive(Debug)]
+pub struct TagCache {
+    pub registries: Vec<RegistryTags>,
+    /// [`UpdateTagsConfigC`], sent while configuring.
+    pub encoded: Vec<u8>,
+    /// [`UpdateTagsC`], for re-sending to players already in play.
+    pub encoded_play: Vec<u8>,
+}
+
+impl TryFrom<&Tags> for TagCache {
+    type Error = color_eyre::eyre::Report;
+
+    fn try_from(tags: &Tags) -> Result<Self> {
+        let known = try_registries()?;
+
+        let registries = tags
+            .iter()
+            .map(|(registry, tags)| {
+                let entries = known.entries(registry).ok_or_else(|| {
+                    eyre!("your tags.json has tags for {registry}, which crawlspace can't resolve")
+                })?;
+
+                let tags = tags
+                    .iter()
+                    .map(|(name, values)| {
+                        let ids = values
+                            .iter()
+                            .map(|value| {
+                                entries.get(value).map(|e| e.protocol_id).ok_or_else(|| {
+                                    eyre!("tag {name} has {value}, which isn't in {registry}")
+                                })
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        Ok((name.clone(), ids))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(RegistryTags {
+                    registry: registry.clone(),
+                    tags,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut encoder = Encoder::new();
+        encoder
+            .append_packet(&UpdateTagsConfigC(UpdateTagsC {
+                registries: &registries,
+            }))
+            .wrap_err("Failed to encode tags")?;
+        let encoded = encoder.take().to_vec();
+
+        encoder
+            .append_packet(&UpdateTagsC {
+                registries: &registries,
+            })
+            .wrap_err("Failed to encode tags")?;
+        let encoded_play = encoder.take().to_vec();
+
+        Ok(Self {
+            registries,
+            encoded,
+            encoded_play,
+        })
+    }
+}
+
+fn required_index<T: RegistryItem>(registry: &Registry<T>, id: &str) -> Result<i32> {
+    registry.try_index_of(id).ok_or_else(|| {
+        eyre!(
+            "your registries.json is missing {id} in {} - crawlspace needs it to spawn players",
+            registry.registry_id()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{protocol::packets::login::registry::all_registries, server::tags::try_tags};
+
+    #[test]
+    fn the_end_resolves_in_bundled_registries() {
+        let registries = all_registries().expect("bundled registries should parse");
+        let cache = RegistryCache::try_from(registries).expect("registry cache should build");
+
+        let dimensions = Registry::from(registries.dimension_type.clone());
+        let biomes = Registry::from(registries.biome.clone());
+        assert_eq!(
+            dimensions.try_index_of("minecraft:the_end"),
+            Some(cache.the_end_id.0)
+        );
+        assert_eq!(
+            biomes.try_index_of("minecraft:the_end"),
+            Some(cache.the_end_biome_id as i32)
+        );
+    }
+
+    #[test]
+    fn registry_indices_are_stable_across_builds() {
+        // each parse gets its own HashMap ordering, like separate runs of the server would
+        let parse = || {
+            let registries: AllRegistries =
+                serde_json::from_str(include_str!("../../assets/registries.json"))
+                    .expect("bundled registries should parse");
+            RegistryCache::try_from(&registries).expect("registry cache should build")
+        };
+        let (first, second) = (parse(), parse());
+
+        // entries are sorted by id, so the_end comes after overworld and overworld_caves
+        assert_eq!(first.the_end_id, VarInt(2));
+        assert_eq!(first.the_end_biome_id, 55);
+        assert_eq!(first.the_end_id, second.the_end_id);
+        assert_eq!(first.the_end_biome_id, second.the_end_biome_id);
+        assert_eq!(first.encoded, second.encoded);
+    }
+
+    #[test]
+    fn bundled_tags_resolve() {
+        let cache = TagCache::try_from(try_tags().expect("bundled tags should parse"))
+            .expect("tag cache should build");
+
+        let fluids = cache
+            .registries
+            .iter()
+            .find(|r| r.registry == "minecraft:fluid")
+            .expect("fluids should be tagged");
+        // swimming needs these
+        assert!(fluids
+            .tags
+            .contains(&("minecraft:water".to_owned(), vec![2, 1])));
     }
 }
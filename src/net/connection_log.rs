@@ -0,0 +1,70 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::Mutex,
+};
+
+use color_eyre::eyre::{Context, Result};
+use serde::Serialize;
+
+/// Writes one JSON object per line for every connection, once it ends.
+#[derive(Debug)]
+pub struct ConnectionLog {
+    file: Mutex<File>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionRecord<'a> {
+    /// When the connection was opened, in seconds since the unix epoch.
+    pub timestamp: u64,
+    pub peer_addr: &'a str,
+    pub username: Option<&'a str>,
+    pub uuid: Option<String>,
+    pub protocol_version: Option<i32>,
+    pub reached_play: bool,
+    pub disconnect_reason: &'a str,
+    pub duration_secs: f64,
+}
+
+impl ConnectionLog {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open connection log {path}"))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn write(&self, record: &ConnectionRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+
+        let mut file = self.file.lock().expect("connection log mutex poisoned");
+        file.write_all(line.as_bytes())?;
+
+        Ok(())
+    }
+}
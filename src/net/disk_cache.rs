@@ -0,0 +1,333 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use color_eyre::eyre::{bail, ensure, eyre, Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    protocol::{
+        datatypes::{Bounded, Slot, TextComponent, VarInt},
+        Decode, Encode,
+    },
+    world::{Container, WorldSpec},
+    CrawlState, VERSION_NUM,
+};
+
+use super::cache::WorldCache;
+
+/// Bumped whenever the cache file layout changes, so old caches are ignored.
+const FORMAT_VERSION: i32 = 5;
+
+/// Works out where a world's cache lives. The file name includes a hash of everything the encoded
+/// chunks depend on (the region files' sizes and modified times, the protocol version, the biome
+/// id, the spawnpoint and the chunk bounds), so a changed map simply misses the cache.
+pub fn cache_path(crawlstate: &CrawlState, spec: &WorldSpec, cache_dir: &str) -> Result<PathBuf> {
+    let region_dir = Path::new(&spec.dir).join("region");
+    let mut regions = std::fs::read_dir(&region_dir)
+        .wrap_err_with(|| format!("Failed to read region folder {}", region_dir.display()))?
+        .collect::<Result<Vec<_>, _>>()?;
+    regions.sort_by_key(|e| e.file_name());
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(FORMAT_VERSION.to_be_bytes());
+    hasher.update(VERSION_NUM.to_be_bytes());
+    hasher.update(crawlstate.registry_cache.the_end_biome_id.to_be_bytes());
+    hasher.update(crawlstate.spawnpoint.0.to_be_bytes());
+    hasher.update(crawlstate.spawnpoint.1.to_be_bytes());
+    hasher.update(crawlstate.spawnpoint.2.to_be_bytes());
+    hasher.update([crawlstate.safe_spawn as u8]);
+    for bound in [
+        crawlstate.chunk_bounds.min_x,
+        crawlstate.chunk_bounds.max_x,
+        crawlstate.chunk_bounds.min_z,
+        crawlstate.chunk_bounds.max_z,
+        crawlstate.origin_offset.0,
+        crawlstate.origin_offset.1,
+    ] {
+        hasher.update(bound.to_be_bytes());
+    }
+
+    for region in regions {
+        let metadata = region.metadata()?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+
+        hasher.update(region.file_name().as_encoded_bytes());
+        hasher.update(metadata.len().to_be_bytes());
+        hasher.update(modified.as_nanos().to_be_bytes());
+    }
+
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+
+    Ok(Path::new(cache_dir).join(format!("{}-{}.cache", spec.name, &hash[..16])))
+}
+
+/// Reads a cache written by [`save`], compressed or not.
+pub fn load(path: &Path) -> Result<WorldCache> {
+    let data = std::fs::read(path)?;
+    let (compressed, rest) = data
+        .split_first()
+        .ok_or_else(|| eyre!("cache file is empty"))?;
+
+    let data = match compressed {
+        0 => rest.to_vec(),
+        1 => {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(rest).read_to_end(&mut decompressed)?;
+            decompressed
+        }
+        b => bail!("cache file has an unknown compression flag {b}"),
+    };
+    let mut r = data.as_slice();
+
+    let chunk_count = VarInt::decode(&mut r)?.0;
+    let mut encoded = Vec::new();
+    for _ in 0..chunk_count {
+        let x = r.read_i32::<BigEndian>()?;
+        let z = r.read_i32::<BigEndian>()?;
+        let len = VarInt::decode(&mut r)?.0 as usize;
+        ensure!(len <= r.len(), "cache file is truncated");
+
+        let (packet, rest) = r.split_at(len);
+        encoded.push(((x, z), packet.to_vec()));
+        r = rest;
+    }
+
+    let container_count = VarInt::decode(&mut r)?.0;
+    let mut containers = std::collections::HashMap::new();
+    for _ in 0..container_count {
+        let x = r.read_i32::<BigEndian>()?;
+        let y = r.read_i32::<BigEndian>()?;
+        let z = r.read_i32::<BigEndian>()?;
+
+        let slot_count = VarInt::decode(&mut r)?.0;
+        let mut slots = Vec::new();
+        for _ in 0..slot_count {
+            slots.push(Slot::decode(&mut r)?);
+        }
+
+        let title = Bounded::<&str>::decode(&mut r)?.0;
+        let title = TextComponent::from_json(title)?;
+
+        containers.insert((x, y, z), Container { slots, title });
+    }
+
+    let spawnpoint = (
+        r.read_f64::<BigEndian>()?,
+        r.read_f64::<BigEndian>()?,
+        r.read_f64::<BigEndian>()?,
+    );
+
+    ensure!(r.is_empty(), "cache file has trailing data");
+
+    Ok(WorldCache {
+        encoded,
+        containers,
+        spawnpoint,
+    })
+}
+
+/// Writes a world cache to `path`, gzipped if `compress` is set, and clears out older caches for
+/// the same world.
+pub fn save(world_cache: &WorldCache, spec: &WorldSpec, path: &Path, compress: bool) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+
+        for entry in std::fs::read_dir(dir)?.flatten() {
+            if is_cache_of(&entry.file_name().to_string_lossy(), &spec.name) {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+    }
+
+    let mut file = BufWriter::new(File::create(path)?);
+    compress.encode(&mut file)?;
+
+    match compress {
+        true => {
+            let mut w = GzEncoder::new(file, Compression::default());
+            write_contents(world_cache, &mut w)?;
+            w.finish()?.flush()?;
+        }
+        false => {
+            write_contents(world_cache, &mut file)?;
+            file.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `file_name` is a cache [`cache_path`] would name for the world `world`. Only the
+/// exact `{world}-{hash}.cache` shape counts, so world "a" leaves world "a-b"'s caches alone.
+fn is_cache_of(file_name: &str, world: &str) -> bool {
+    file_name
+        .strip_prefix(world)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.strip_suffix(".cache"))
+        .is_some_and(|hash| hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn write_contents(world_cache: &WorldCache, mut w: impl Write) -> Result<()> {
+    VarInt(world_cache.encoded.len() as i32).encode(&mut w)?;
+    for ((x, z), packet) in &world_cache.encoded {
+        x.encode(&mut w)?;
+        z.encode(&mut w)?;
+        VarInt(packet.len() as i32).encode(&mut w)?;
+        w.write_all(packet)?;
+    }
+
+    VarInt(world_cache.containers.len() as i32).encode(&mut w)?;
+    for ((x, y, z), container) in &world_cache.containers {
+        x.encode(&mut w)?;
+        y.encode(&mut w)?;
+        z.encode(&mut w)?;
+        VarInt(container.slots.len() as i32).encode(&mut w)?;
+        container.slots.encode(&mut w)?;
+        Bounded::<&str>(&container.title.to_json()?).encode(&mut w)?;
+    }
+
+    let (x, y, z) = world_cache.spawnpoint;
+    x.encode(&mut w)?;
+    y.encode(&mut w)?;
+    z.encode(&mut w)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::player::testing,
+        server::registries::registries,
+        world::{fixtures, Item},
+    };
+
+    #[test]
+    fn cached_world_loads_identically() {
+        let map = fixtures::MapDir::new("disk-cache");
+        let chest = fixtures::chunk_with_chest(&[Item {
+            slot: 3,
+            id: "minecraft:diamond".to_owned(),
+            count: 5,
+        }]);
+        let chunks = [(0, 0), (1, 0), (0, -1)].map(|(x, z)| {
+            let chunk = match (x, z) {
+                (0, 0) => chest.clone(),
+                _ => fixtures::chunk(x, z, vec![fixtures::air_section(0)]),
+            };
+            ((x, z), fixtures::compress(&chunk))
+        });
+        map.write_region("r.0.0.mca", &fixtures::region(&chunks[..2]));
+        map.write_region("r.0.-1.mca", &fixtures::region(&chunks[2..]));
+
+        let cache_dir = format!("{}/cache", map.path());
+        let state = testing::state_in(map.path(), &["--cache-dir", &cache_dir]);
+
+        let (_, first) = crate::load_worlds(&state, false).unwrap().remove(0);
+        assert!(cache_path(&state, &state.worlds[0], &cache_dir)
+            .unwrap()
+            .exists());
+
+        let (_, second) = crate::load_worlds(&state, false).unwrap().remove(0);
+        assert_eq!(first.encoded.len(), 3);
+        assert_eq!(first.encoded, second.encoded);
+        assert_eq!(first.spawnpoint, second.spawnpoint);
+
+        let items = |cache: &WorldCache| {
+            let chest = &cache.containers[&(0, 64, 0)];
+            let items = chest
+                .slots
+                .iter()
+                .enumerate()
+                .filter_map(|(i, slot)| slot.item_id().map(|id| (i, id, slot.count())))
+                .collect::<Vec<_>>();
+            (cache.containers.len(), items, format!("{:?}", chest.title))
+        };
+        let diamond = registries().item.entries["minecraft:diamond"].protocol_id;
+        assert_eq!(items(&first).1, [(3, diamond, 5)]);
+        assert_eq!(items(&first), items(&second));
+    }
+
+    #[test]
+    fn saving_leaves_other_worlds_caches_alone() {
+        let map = fixtures::MapDir::new("disk-cache-cleanup");
+        let state = testing::state_in(map.path(), &[]);
+        let world = testing::world(
+            &state,
+            vec![fixtures::single_block_chunk("minecraft:stone")],
+        );
+
+        let dir = Path::new(map.path()).join("cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = dir.join(format!("{}-0123456789abcdef.cache", state.worlds[0].name));
+        let other = dir.join(format!("{}-b-0123456789abcdef.cache", state.worlds[0].name));
+        for path in [&old, &other] {
+            std::fs::write(path, []).unwrap();
+        }
+
+        let new = dir.join(format!("{}-fedcba9876543210.cache", state.worlds[0].name));
+        save(&world, &state.worlds[0], &new, false).unwrap();
+
+        assert!(new.exists());
+        assert!(!old.exists());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn compressed_and_plain_caches_reload_the_same() {
+        let map = fixtures::MapDir::new("disk-cache-compression");
+        let state = testing::state_in(map.path(), &[]);
+        let world = testing::world(
+            &state,
+            vec![
+                fixtures::chunk_with_chest(&[]),
+                fixtures::chunk(1, 0, vec![fixtures::air_section(0)]),
+            ],
+        );
+
+        let mut loaded = Vec::new();
+        for (compress, flag) in [(false, 0), (true, 1)] {
+            let path = Path::new(map.path()).join(format!("{compress}/world.cache"));
+            save(&world, &state.worlds[0], &path, compress).unwrap();
+            assert_eq!(std::fs::read(&path).unwrap()[0], flag);
+            loaded.push(load(&path).unwrap());
+        }
+
+        for cache in loaded {
+            assert_eq!(cache.encoded, world.encoded);
+            assert_eq!(cache.containers.len(), 1);
+            assert_eq!(cache.spawnpoint, world.spawnpoint);
+        }
+    }
+}
@@ -17,6 +17,8 @@
  * <https://www.gnu.org/licenses/>.
  */
 
+use crate::protocol::datatypes::fixed;
+
 #[derive(Default, Debug)]
 pub struct Entity {
     pub x: f64,
@@ -24,9 +26,37 @@ pub struct Entity {
     pub z: f64,
     pub yaw: f32,
     pub pitch: f32,
+    pub on_ground: bool,
+
+    /// Position and rotation as of the last movement broadcast, used to compute deltas.
+    last_broadcast: (f64, f64, f64, f32, f32),
+    /// Set when the server moved the entity itself, so the next broadcast is a teleport even if
+    /// it didn't move far.
+    teleported: bool,
+}
+
+/// What other players need to be told about an entity's movement since the last broadcast.
+#[derive(Debug, PartialEq)]
+pub enum Movement {
+    None,
+    Position(i16, i16, i16),
+    Rotation,
+    PositionAndRotation(i16, i16, i16),
+    /// The entity moved too far for a relative move and needs a full teleport.
+    Teleport,
+}
+
+/// Whether two positions are close enough horizontally for entities at one to be shown to a player
+/// at the other. Like vanilla's tracking range, this checks each axis rather than true distance.
+pub fn in_view_range(a: (f64, f64, f64), b: (f64, f64, f64), range: f64) -> bool {
+    (a.0 - b.0).abs() <= range && (a.2 - b.2).abs() <= range
 }
 
 impl Entity {
+    pub fn position(&self) -> (f64, f64, f64) {
+        (self.x, self.y, self.z)
+    }
+
     pub fn reposition(&mut self, x: f64, y: f64, z: f64) {
         self.x = x;
         self.y = y;
@@ -37,4 +67,101 @@ impl Entity {
         self.yaw = yaw;
         self.pitch = pitch;
     }
+
+    /// Moves the entity somewhere the server decided on, like a position correction. Other
+    /// players are sent a teleport for it rather than a relative move.
+    pub fn teleport(&mut self, x: f64, y: f64, z: f64, yaw: f32, pitch: f32) {
+        self.reposition(x, y, z);
+        self.rotate(yaw, pitch);
+        self.teleported = true;
+    }
+
+    /// Works out how the entity moved since this was last called, and marks the current position
+    /// as broadcast.
+    pub fn take_movement(&mut self) -> Movement {
+        let (px, py, pz, pyaw, ppitch) = self.last_broadcast;
+        self.last_broadcast = (self.x, self.y, self.z, self.yaw, self.pitch);
+
+        if std::mem::take(&mut self.teleported) {
+            return Movement::Teleport;
+        }
+
+        let moved = (px, py, pz) != (self.x, self.y, self.z);
+        let rotated = (pyaw, ppitch) != (self.yaw, self.pitch);
+
+        if !moved {
+            return match rotated {
+                true => Movement::Rotation,
+                false => Movement::None,
+            };
+        }
+
+        match fixed::position_delta((px, py, pz), (self.x, self.y, self.z)) {
+            None => Movement::Teleport,
+            Some((dx, dy, dz)) if rotated => Movement::PositionAndRotation(dx, dy, dz),
+            Some((dx, dy, dz)) => Movement::Position(dx, dy, dz),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_move_is_relative() {
+        let mut entity = Entity::default();
+        entity.reposition(1.0, -0.5, 2.0);
+
+        assert_eq!(
+            entity.take_movement(),
+            Movement::Position(4096, -2048, 8192)
+        );
+        assert_eq!(entity.take_movement(), Movement::None);
+    }
+
+    #[test]
+    fn large_move_is_a_teleport() {
+        let mut entity = Entity::default();
+        entity.reposition(0.0, 0.0, 20.0);
+
+        assert_eq!(entity.take_movement(), Movement::Teleport);
+
+        // deltas carry on from where the teleport put it
+        entity.reposition(0.0, 0.0, 21.0);
+        assert_eq!(entity.take_movement(), Movement::Position(0, 0, 4096));
+    }
+
+    #[test]
+    fn looking_around_is_a_rotation() {
+        let mut entity = Entity::default();
+        entity.rotate(90.0, 10.0);
+        assert_eq!(entity.take_movement(), Movement::Rotation);
+
+        entity.reposition(0.5, 0.0, 0.0);
+        entity.rotate(180.0, 10.0);
+        assert_eq!(
+            entity.take_movement(),
+            Movement::PositionAndRotation(2048, 0, 0)
+        );
+    }
+
+    #[test]
+    fn server_teleports_are_always_teleports() {
+        let mut entity = Entity::default();
+        entity.teleport(0.5, 0.0, 0.0, 0.0, 0.0);
+
+        assert_eq!(entity.take_movement(), Movement::Teleport);
+    }
+
+    #[test]
+    fn view_range_checks_each_axis() {
+        let here = (0.0, 64.0, 0.0);
+
+        assert!(in_view_range(here, (64.0, 64.0, -64.0), 64.0));
+        assert!(!in_view_range(here, (64.5, 64.0, 0.0), 64.0));
+        assert!(!in_view_range(here, (0.0, 64.0, -64.5), 64.0));
+        // height doesn't count
+        assert!(in_view_range(here, (0.0, 320.0, 0.0), 64.0));
+    }
 }
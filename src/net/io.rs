@@ -17,17 +17,24 @@
  * <https://www.gnu.org/licenses/>.
  */
 
-use std::{io::ErrorKind, time::Duration};
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use bytes::BytesMut;
-use color_eyre::eyre::{bail, Context, Result};
+use color_eyre::eyre::{bail, eyre, Context, Result};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::{
         tcp::{OwnedReadHalf, OwnedWriteHalf},
         TcpStream,
     },
-    sync::{Mutex, RwLock},
+    sync::{mpsc, oneshot, Mutex, RwLock},
 };
 
 use crate::protocol::{self, ClientboundPacket, Frame, ServerboundPacket};
@@ -35,15 +42,72 @@ use crate::protocol::{self, ClientboundPacket, Frame, ServerboundPacket};
 #[derive(Debug)]
 pub struct NetIo {
     pub peer_addr: String,
-    pub connected: RwLock<bool>,
+    pub connected: Arc<RwLock<bool>>,
     read_half: Mutex<OwnedReadHalf>,
-    write_half: Mutex<OwnedWriteHalf>,
+    /// Everything written to the connection goes through here to a single writer task, so
+    /// packets from the tick and from chunk streaming tasks can't interleave.
+    writer: mpsc::Sender<Outgoing>,
+    /// Packets that shouldn't wait behind chunks, like keepalives. The writer drains this before
+    /// `writer` whenever the lane is open.
+    urgent_writer: mpsc::Sender<BytesMut>,
+    /// Whether [`Self::tx_urgent`] uses the urgent lane. It's closed until the player is in play,
+    /// since nothing may overtake the packets that get them there.
+    urgent_open: AtomicBool,
     decoder: Mutex<protocol::Decoder>,
     encoder: Mutex<protocol::Encoder>,
 }
 
+/// What the writer task is asked to do.
+#[derive(Debug)]
+enum Outgoing {
+    Bytes(BytesMut),
+    /// Flush everything queued before this, then answer.
+    Flush(oneshot::Sender<()>),
+    /// Start or stop draining the urgent lane first. Closing it writes whatever's left in it.
+    UrgentLane(bool),
+    /// Encrypt everything after this with the shared secret.
+    #[cfg(feature = "encryption")]
+    EnableEncryption([u8; 16]),
+}
+
+#[cfg(feature = "encryption")]
+type Cipher = cfb8::Encryptor<aes::Aes128>;
+
+/// The writer task's end of the connection.
+struct Writer<W = OwnedWriteHalf> {
+    write_half: W,
+    #[cfg(feature = "encryption")]
+    cipher: Option<Cipher>,
+}
+
+impl<W: AsyncWrite + Unpin> Writer<W> {
+    #[allow(unused_mut)]
+    async fn write(&mut self, mut bytes: BytesMut) -> std::io::Result<()> {
+        // encrypting here rather than when encoding keeps the cipher stream in the order bytes
+        // actually go out, whichever lane they came from
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &mut self.cipher {
+            use aes::cipher::{generic_array::GenericArray, BlockEncryptMut};
+
+            // CFB8 works a byte at a time, so there's never a partial block to hold back
+            for byte in bytes.chunks_mut(1) {
+                cipher.encrypt_block_mut(GenericArray::from_mut_slice(byte));
+            }
+        }
+
+        self.write_half.write_all(&bytes).await
+    }
+}
+
 const BUF_SIZE: usize = 4096;
 
+/// How many writes can be queued for a connection before senders wait for the client to catch
+/// up.
+const WRITE_QUEUE_SIZE: usize = 1024;
+
+/// Same, for the urgent lane. Far less goes through it.
+const URGENT_QUEUE_SIZE: usize = 64;
+
 impl NetIo {
     #[must_use]
     pub fn new(stream: TcpStream) -> Self {
@@ -61,16 +125,130 @@ impl NetIo {
             .map_or("Unknown".to_owned(), |a| a.to_string());
         let (read_half, write_half) = stream.into_split();
 
+        Self::from_parts(peer_addr, read_half, write_half)
+    }
+
+    /// Like [`Self::new`], but writing somewhere other than the socket it reads from, e.g. a mock
+    /// writer in tests.
+    pub(crate) fn from_parts<W>(peer_addr: String, read_half: OwnedReadHalf, write_half: W) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let connected = Arc::new(RwLock::new(true));
+
+        let (writer, queue) = mpsc::channel(WRITE_QUEUE_SIZE);
+        let (urgent_writer, urgent_queue) = mpsc::channel(URGENT_QUEUE_SIZE);
+        tokio::spawn(Self::write_loop(
+            peer_addr.clone(),
+            Writer {
+                write_half,
+                #[cfg(feature = "encryption")]
+                cipher: None,
+            },
+            queue,
+            urgent_queue,
+            connected.clone(),
+        ));
+
         Self {
             peer_addr,
-            connected: RwLock::new(true),
+            connected,
             read_half: Mutex::new(read_half),
-            write_half: Mutex::new(write_half),
+            writer,
+            urgent_writer,
+            urgent_open: AtomicBool::new(false),
             decoder: Mutex::new(protocol::Decoder::new()),
             encoder: Mutex::new(protocol::Encoder::new()),
         }
     }
 
+    /// Writes queued packets until the connection is dropped (which closes the queues once
+    /// they're drained) or a write fails.
+    async fn write_loop<W: AsyncWrite + Unpin>(
+        peer_addr: String,
+        mut writer: Writer<W>,
+        mut queue: mpsc::Receiver<Outgoing>,
+        mut urgent_queue: mpsc::Receiver<BytesMut>,
+        connected: Arc<RwLock<bool>>,
+    ) {
+        let mut urgent_open = false;
+
+        loop {
+            let outgoing = tokio::select! {
+                biased;
+                Some(bytes) = urgent_queue.recv(), if urgent_open => Outgoing::Bytes(bytes),
+                Some(outgoing) = queue.recv() => outgoing,
+                else => return,
+            };
+
+            let written = match outgoing {
+                Outgoing::Bytes(bytes) => writer.write(bytes).await,
+                Outgoing::Flush(done) => {
+                    let flushed = writer.write_half.flush().await;
+                    let _ = done.send(());
+                    flushed
+                }
+                Outgoing::UrgentLane(open) => {
+                    urgent_open = open;
+
+                    // anything still in the lane was sent before it closed
+                    let mut drained = Ok(());
+                    if !open {
+                        while let Ok(bytes) = urgent_queue.try_recv() {
+                            drained = writer.write(bytes).await;
+                            if drained.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    drained
+                }
+                #[cfg(feature = "encryption")]
+                Outgoing::EnableEncryption(key) => {
+                    use aes::cipher::KeyIvInit;
+
+                    // minecraft uses the shared secret as both the key and the IV
+                    writer.cipher = Some(Cipher::new(&key.into(), &key.into()));
+                    Ok(())
+                }
+            };
+
+            if let Err(why) = written {
+                debug!("Failed to write to {peer_addr}: {why}");
+                let mut c = connected.write().await;
+                *c = false;
+                return;
+            }
+        }
+    }
+
+    async fn write(&self, bytes: BytesMut) -> Result<()> {
+        self.send(Outgoing::Bytes(bytes)).await
+    }
+
+    async fn send(&self, outgoing: Outgoing) -> Result<()> {
+        self.writer
+            .send(outgoing)
+            .await
+            .map_err(|_| eyre!("connection to {} is closed", self.peer_addr))
+    }
+
+    /// Waits until everything queued so far has been written out.
+    pub async fn flush(&self) -> Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.send(Outgoing::Flush(done)).await?;
+
+        flushed
+            .await
+            .map_err(|_| eyre!("connection to {} closed while flushing", self.peer_addr))
+    }
+
+    /// Marks the connection as closed, so the tick drops it.
+    pub async fn disconnect(&self) {
+        let mut c = self.connected.write().await;
+        *c = false;
+    }
+
     pub async fn connected(&self) -> bool {
         let c = self.connected.read().await;
         *c
@@ -83,44 +261,56 @@ impl NetIo {
         // TODO: maybe move this somewhere else? i don't know if a global timeout of 5 seconds per
         // packet is realistic but for testing it's chill i suppose
         tokio::time::timeout(Duration::from_secs(5), async move {
-            let mut decoder = self.decoder.lock().await;
             loop {
-                if let Some(frame) = decoder.try_read_next().context("failed try_read_next")? {
-                    if frame.id != P::ID {
-                        debug!(
-                            "Got packet ID {} while awaiting {}, discarding",
-                            frame.id,
-                            P::ID
-                        );
-                        continue;
-                    }
-
-                    // TODO: decode here, rather than forcing the consumer to do it.
-                    // probably need to box frame data? idk enough rust for this
-                    return Ok(frame);
-                };
-
-                decoder.reserve_additional(BUF_SIZE);
-                let mut buf = decoder.take_all();
-
-                let mut read_half = self.read_half.lock().await;
-                if read_half
-                    .read_buf(&mut buf)
-                    .await
-                    .context("failed read_buf")?
-                    == 0
-                {
-                    let mut c = self.connected.write().await;
-                    *c = false;
-                    return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+                let frame = self.next_frame().await?;
+                if frame.id != P::ID {
+                    debug!(
+                        "Got packet ID {} while awaiting {}, discarding",
+                        frame.id,
+                        P::ID
+                    );
+                    continue;
                 }
 
-                decoder.add_bytes(buf);
+                // TODO: decode here, rather than forcing the consumer to do it.
+                // probably need to box frame data? idk enough rust for this
+                return Ok(frame);
             }
         })
         .await?
     }
 
+    /// Waits for the next frame regardless of its ID, for when several packets are acceptable.
+    pub async fn rx_any(&self) -> Result<Frame> {
+        tokio::time::timeout(Duration::from_secs(5), self.next_frame()).await?
+    }
+
+    async fn next_frame(&self) -> Result<Frame> {
+        let mut decoder = self.decoder.lock().await;
+        loop {
+            if let Some(frame) = decoder.try_read_next().context("failed try_read_next")? {
+                return Ok(frame);
+            };
+
+            decoder.reserve_additional(BUF_SIZE);
+            let mut buf = decoder.take_all();
+
+            let mut read_half = self.read_half.lock().await;
+            if read_half
+                .read_buf(&mut buf)
+                .await
+                .context("failed read_buf")?
+                == 0
+            {
+                let mut c = self.connected.write().await;
+                *c = false;
+                return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+            }
+
+            decoder.add_bytes(buf);
+        }
+    }
+
     pub async fn tx<P>(&self, packet: &P) -> Result<()>
     where
         P: ClientboundPacket,
@@ -131,14 +321,76 @@ impl NetIo {
         let bytes = encoder.take();
         trace!("raw packet is {} bytes", bytes.len());
         trace!("{:?}", bytes.to_vec());
-        let mut writer = self.write_half.lock().await;
-        Ok(writer.write_all(&bytes).await?)
+        // queue while still holding the encoder, so packets go out in the order they're encoded
+        self.write(bytes).await
+    }
+
+    /// Sends a packet ahead of anything queued normally, like chunks, once the urgent lane is
+    /// open. Until then it's the same as [`Self::tx`].
+    pub async fn tx_urgent<P>(&self, packet: &P) -> Result<()>
+    where
+        P: ClientboundPacket,
+    {
+        trace!("Sending urgent packet {:?}", packet);
+        let mut encoder = self.encoder.lock().await;
+        encoder.append_packet(packet)?;
+        self.write_urgent(encoder.take()).await
     }
 
+    /// [`Self::tx_raw`], but through the urgent lane like [`Self::tx_urgent`].
+    pub async fn tx_raw_urgent(&self, packets: &[u8]) -> Result<()> {
+        trace!("Sending urgent packet {:?}", packets);
+        let mut encoder = self.encoder.lock().await;
+        encoder.append_frames(packets)?;
+        self.write_urgent(encoder.take()).await
+    }
+
+    /// Only call this while holding the encoder, so the lane can't close in between.
+    async fn write_urgent(&self, bytes: BytesMut) -> Result<()> {
+        if !self.urgent_open.load(Ordering::SeqCst) {
+            return self.write(bytes).await;
+        }
+
+        self.urgent_writer
+            .send(bytes)
+            .await
+            .map_err(|_| eyre!("connection to {} is closed", self.peer_addr))
+    }
+
+    /// Opens or closes the urgent lane. Only open it while the player is in play, and once
+    /// everything that gets them there has been queued - urgent packets can overtake anything
+    /// queued before them, but not what was queued before the lane opened.
+    pub async fn set_urgent_lane(&self, open: bool) -> Result<()> {
+        // holding the encoder keeps other sends from landing between the flag and the marker
+        let _encoder = self.encoder.lock().await;
+        self.urgent_open.store(open, Ordering::SeqCst);
+        self.send(Outgoing::UrgentLane(open)).await
+    }
+
+    /// Sends packets that were already encoded by another [`protocol::Encoder`].
     pub async fn tx_raw(&self, packet: &[u8]) -> Result<()> {
         trace!("Sending packet {:?}", packet);
-        let mut writer = self.write_half.lock().await;
-        Ok(writer.write_all(packet).await?)
+        let mut encoder = self.encoder.lock().await;
+        encoder.append_frames(packet)?;
+        self.write(encoder.take()).await
+    }
+
+    /// Switches both directions to compressed framing. Call this right after sending
+    /// `SetCompressionC`, before anything else goes out.
+    #[cfg(feature = "compression")]
+    pub async fn set_compression(&self, threshold: usize) {
+        self.encoder.lock().await.set_compression(Some(threshold));
+        self.decoder.lock().await.set_compression(Some(threshold));
+    }
+
+    /// Switches both directions to encrypting with the shared secret. Call this right after
+    /// reading `EncryptionResponseS`, before anything else goes out.
+    #[cfg(feature = "encryption")]
+    pub async fn enable_encryption(&self, shared_secret: &[u8; 16]) -> Result<()> {
+        self.send(Outgoing::EnableEncryption(*shared_secret))
+            .await?;
+        self.decoder.lock().await.enable_encryption(shared_secret);
+        Ok(())
     }
 
     pub async fn rx_raw(&self) -> Result<Frame> {
@@ -164,3 +416,97 @@ impl NetIo {
         bail!("No packet available")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{
+        net::player::testing::RecordingWriter,
+        protocol::{packets::play::KeepAliveC, Packet},
+    };
+
+    /// A read half for [`NetIo::from_parts`], along with the client end keeping it open.
+    async fn read_half() -> (OwnedReadHalf, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read_half, _) = stream.into_split();
+
+        (read_half, client)
+    }
+
+    #[tokio::test]
+    async fn concurrent_sends_stay_whole_and_in_order() {
+        let (read_half, _client) = read_half().await;
+        let writer = RecordingWriter::default();
+        let io = Arc::new(NetIo::from_parts(
+            "test".to_owned(),
+            read_half,
+            writer.clone(),
+        ));
+
+        let senders = (0..8i64)
+            .map(|task| {
+                let io = io.clone();
+                tokio::spawn(async move {
+                    for seq in 0..100i64 {
+                        io.tx(&KeepAliveC(task << 32 | seq)).await.unwrap();
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for sender in senders {
+            sender.await.unwrap();
+        }
+        io.flush().await.unwrap();
+
+        let mut next = [0i64; 8];
+        for frame in writer.frames() {
+            assert_eq!(frame.id, KeepAliveC::ID);
+            let value = i64::from_be_bytes(frame.body[..].try_into().unwrap());
+            let (task, seq) = ((value >> 32) as usize, value & 0xFFFF_FFFF);
+            assert_eq!(seq, next[task]);
+            next[task] += 1;
+        }
+        assert_eq!(next, [100; 8]);
+    }
+
+    #[tokio::test]
+    async fn urgent_packets_overtake_queued_ones() {
+        let (read_half, _client) = read_half().await;
+        // a small pipe that nobody reads yet, so the writer stalls like it would on a slow client
+        let (write_half, mut client_side) = tokio::io::duplex(64);
+        let io = NetIo::from_parts("test".to_owned(), read_half, write_half);
+
+        io.set_urgent_lane(true).await.unwrap();
+        for seq in 0..200 {
+            io.tx(&KeepAliveC(seq)).await.unwrap();
+        }
+        io.tx_urgent(&KeepAliveC(-1)).await.unwrap();
+
+        let mut decoder = protocol::Decoder::new();
+        let mut received = Vec::new();
+        while received.len() < 201 {
+            let mut buf = BytesMut::with_capacity(BUF_SIZE);
+            assert_ne!(client_side.read_buf(&mut buf).await.unwrap(), 0);
+            decoder.add_bytes(buf);
+            while let Some(frame) = decoder.try_read_next().unwrap() {
+                received.push(i64::from_be_bytes(frame.body[..].try_into().unwrap()));
+            }
+        }
+
+        // only what the writer had already taken before it stalled can go out first
+        let urgent_at = received.iter().position(|&value| value == -1).unwrap();
+        assert!(
+            urgent_at < 10,
+            "urgent packet went out at position {urgent_at}"
+        );
+        received.remove(urgent_at);
+        assert_eq!(received, (0..200).collect::<Vec<_>>());
+    }
+}
@@ -17,25 +17,39 @@
  * <https://www.gnu.org/licenses/>.
  */
 
+use std::net::SocketAddr;
+
 use color_eyre::eyre::Result;
 use player::SharedPlayer;
 use tokio::net::TcpListener;
 
+pub mod auth;
 pub mod cache;
+pub mod connection_log;
+pub mod disk_cache;
+pub mod entity;
 pub mod player;
 
-mod entity;
 mod io;
 
 use crate::CrawlState;
 
 #[cfg(feature = "lan")]
 pub async fn spawn_lan_broadcast(state: CrawlState) -> Result<()> {
-    use std::time::Duration;
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::Duration,
+    };
     use tokio::{net::UdpSocket, time};
 
     let port = state.port;
-    let sock = UdpSocket::bind("0.0.0.0:9753").await?;
+
+    // LAN discovery is IPv4 multicast, so this needs an IPv4 socket even when listening on IPv6
+    let ip = match state.addr {
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+    };
+    let sock = UdpSocket::bind((ip, 9753)).await?;
 
     tokio::spawn(async move {
         let motd = format!("[MOTD]{}[/MOTD][AD]{port}[/AD]", state.description);
@@ -51,13 +65,21 @@ pub async fn spawn_lan_broadcast(state: CrawlState) -> Result<()> {
     Ok(())
 }
 
-pub async fn spawn_net_handler(state: CrawlState) -> Result<()> {
-    let addr = state.addr.clone();
-    let port = state.port;
-    let listener = TcpListener::bind(format!("{addr}:{port}")).await?;
-    warn!("Listening on port {port}.");
-    tokio::spawn(net_handler(state, listener));
-    Ok(())
+/// Starts accepting connections until shutdown, and returns the address it's listening on.
+pub async fn spawn_net_handler(state: CrawlState) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(SocketAddr::new(state.addr, state.port)).await?;
+    let local_addr = listener.local_addr()?;
+    warn!("Listening on port {}.", local_addr.port());
+
+    let token = state.shutdown_token.clone();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = net_handler(state, listener) => (),
+            _ = token.cancelled() => debug!("Stopped listening."),
+        }
+    });
+
+    Ok(local_addr)
 }
 
 async fn net_handler(crawlstate: CrawlState, listener: TcpListener) {
@@ -91,3 +113,21 @@ async fn net_handler(crawlstate: CrawlState, listener: TcpListener) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn ipv4_addresses_bind_ipv4() {
+        let state = player::testing::state(&["--addr", "127.0.0.1", "--port", "0"]);
+
+        let addr = spawn_net_handler(state.clone()).await.unwrap();
+        state.shutdown_token.cancel();
+
+        assert!(addr.is_ipv4());
+        assert_eq!(addr.ip(), Ipv4Addr::LOCALHOST);
+    }
+}
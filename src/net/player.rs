@@ -18,66 +18,202 @@
  */
 
 use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
     sync::{
-        atomic::{AtomicU8, Ordering},
+        atomic::{AtomicBool, AtomicU8, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use color_eyre::eyre::{bail, Result};
+use bytes::BytesMut;
+use color_eyre::eyre::{bail, ensure, eyre, Result};
 use rand::Rng;
 use serde_json::json;
 use thiserror::Error;
 use tokio::{
     net::TcpStream,
-    sync::{Mutex, OwnedSemaphorePermit, RwLock},
-    time::{timeout, Instant},
+    sync::{oneshot, Mutex, OwnedSemaphorePermit, RwLock},
+    task::JoinHandle,
+    time::{error::Elapsed, timeout, Instant},
 };
 use uuid::Uuid;
 
 use crate::{
     protocol::{
-        datatypes::{Bounded, Bytes, Rest, Slot, VarInt},
+        datatypes::{
+            Bounded, Bytes, Metadata, OptionalPrefixed, Rest, Slot, TextComponent, VarInt,
+        },
         packets::{
             login::*,
             play::{
-                ConfirmTeleportS, GameEvent, GameEventC, Gamemode, KeepAliveC, LoginPlayC,
-                OpenScreenC, PlayerInfoUpdateC, PlayerStatus, SetBorderCenterC, SetBorderSizeC,
-                SetCenterChunkC, SetContainerContentC, SetPlayerPositionAndRotationS,
-                SetPlayerPositionS, SetTickingStateC, StepTicksC, SynchronisePositionC, UseItemOnS,
+                AddResourcePackC, AwardStatisticsC, BundleDelimiterC, ChatCommandS, ChatMessageS,
+                ChatMode, ClearTitlesC, ClickContainerS, ClientInformationS, ClientStatusAction,
+                ClientStatusS, CloseContainerC, CloseContainerS, ConfigurationAckS,
+                ConfirmTeleportS, DisconnectC, EquipmentSlot, GameEvent, GameEventC, Gamemode,
+                Hand, InteractS, Interaction, KeepAliveC, KeepAliveS, LoginPlayC, MainHand,
+                MerchantOffersC, OpenScreenC, PickItemS, PingC, PlayerInfoRemoveC,
+                PlayerInfoUpdateC, PlayerStatus, PongS, RemoveEntitiesC, ResourcePackResponseS,
+                ResourcePackResult, RespawnC, ServerDataC, SetBorderCenterC, SetBorderSizeC,
+                SetCameraC, SetCenterChunkC, SetContainerContentC, SetContainerSlotC,
+                SetEntityMetadataC, SetEquipmentC, SetHeadRotationC, SetHeldItemS,
+                SetPlayerPositionAndRotationS, SetPlayerPositionS, SetPlayerRotationS,
+                SetTabListHeaderAndFooterC, SetTickingStateC, SpawnEntityC, StartConfigurationC,
+                StepTicksC, SynchronisePositionC, SystemChatMessageC, TeleportEntityC, Trade,
+                UnloadChunkC, UpdateEntityPositionAndRotationC, UpdateEntityPositionC,
+                UpdateEntityRotationC, UpdateTimeC, UseItemOnS,
             },
         },
-        Frame, Packet, PacketState,
+        Encoder, Frame, Packet, PacketState, PacketStateTransitionError,
+    },
+    server::{
+        registries::registries,
+        window::{Window, WindowType},
     },
-    server::window::{Window, WindowType},
+    state::ResourcePack,
+    world::WorldSpec,
     CrawlState,
 };
 
 #[cfg(feature = "encryption")]
-use crate::protocol::{datatypes::Bytes, packets::login::PluginRequestC};
-
-use super::{entity::Entity, io::NetIo};
+use super::auth::{Authenticator, GameProfile};
+
+use super::{
+    auth::ProfileProperty,
+    cache::WorldCache,
+    connection_log::ConnectionRecord,
+    entity::{Entity, Movement},
+    io::NetIo,
+};
 
 #[derive(Debug)]
 pub struct Player {
     pub id: u16,
     _permit: OwnedSemaphorePermit,
+    /// Whether this player counts towards [`crate::state::State::current_players`], from login
+    /// until they leave.
+    holds_slot: AtomicBool,
     pub io: NetIo,
+    /// The last of the writes queued by [`SharedPlayer::send_in_background`]. Each one waits for
+    /// the one before it, so they go out in order.
+    background_write: std::sync::Mutex<Option<JoinHandle<()>>>,
     frame_queue: Mutex<Vec<Frame>>,
 
     crawlstate: CrawlState,
     packet_state: RwLock<PacketState>,
 
     uuid: RwLock<Option<Uuid>>,
+    username: RwLock<Option<String>>,
+    /// Skin and cape, from the session server in online mode. Empty otherwise.
+    properties: RwLock<Vec<ProfileProperty>>,
+    protocol_version: RwLock<Option<i32>>,
+    /// The hostname the client connected with, from the handshake.
+    host: RwLock<Option<String>>,
     tp_state: RwLock<TeleportState>,
+    /// The name of the world the player is in.
+    world: RwLock<String>,
 
     last_keepalive: RwLock<Instant>,
+    /// The id and send time of the last [`KeepAliveC`] that hasn't been answered.
+    pending_keepalive: Mutex<Option<(i64, Instant)>>,
+    /// The id and send time of the last [`PingC`] that hasn't been answered.
+    pending_ping: Mutex<Option<(i32, Instant)>>,
+    /// When the player last moved or interacted with something, for kicking idle players.
+    last_active: RwLock<Instant>,
+    /// Round trip time from the last answered keepalive or ping.
+    latency: RwLock<Option<Duration>>,
 
     entity: RwLock<Entity>,
 
     next_window_id: Mutex<u8>,
     window: RwLock<Option<Window>>,
+    /// What crawlspace has put in the player's inventory, by inventory index. Anything the
+    /// client moves around itself isn't tracked.
+    inventory: RwLock<HashMap<i16, Slot>>,
+    /// The hotbar slot the player has selected, 0-8.
+    held_slot: AtomicU8,
+
+    settings: RwLock<Option<ClientSettings>>,
+    chunk_view: Mutex<ChunkView>,
+    /// Other players currently within entity view range of this one.
+    tracked: Mutex<HashSet<u16>>,
+
+    connected_at: SystemTime,
+    connection_logged: AtomicBool,
+    /// Set once the player is back in play after reconfiguring, until the tick catches them up.
+    rejoined: AtomicBool,
+}
+
+/// The chunks a client has been sent, and the center and distance they were sent for.
+#[derive(Debug, Default)]
+struct ChunkView {
+    center: Option<(i32, i32)>,
+    view_distance: u8,
+    loaded: HashSet<(i32, i32)>,
+}
+
+/// The furthest render distance, in chunks, that crawlspace will tell clients to use.
+const MAX_VIEW_DISTANCE: u8 = 32;
+
+/// Vanilla's chat length limit. Longer messages are cut off.
+const MAX_CHAT_LENGTH: usize = 256;
+
+/// How long a player has to load a forced resource pack before they're disconnected.
+const RESOURCE_PACK_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often players in play are sent a keepalive.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a client has to answer a keepalive before it's dropped: two missed windows.
+const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(2 * KEEPALIVE_INTERVAL.as_secs());
+
+/// Player entity metadata indices, for the outer skin layers shown and which hand is the main one.
+const PLAYER_SKIN_PARTS_INDEX: u8 = 17;
+const PLAYER_MAIN_HAND_INDEX: u8 = 18;
+
+/// Every outer skin layer, which is what clients show until they're told otherwise.
+const DEFAULT_SKIN_PARTS: u8 = 0x7F;
+
+/// How long a kick waits for the disconnect packet to go out before giving up on the client.
+const KICK_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A chat message a player sent, for whatever's reading [`crate::state::State::chat_recv`].
+#[derive(Debug, Clone)]
+pub struct ChatEvent {
+    pub player: SharedPlayer,
+    pub username: String,
+    /// The message, already cut down to [`MAX_CHAT_LENGTH`].
+    pub message: String,
+}
+
+/// An owned copy of the settings the client reports in [`ClientInformationS`].
+#[derive(Debug, Clone)]
+pub struct ClientSettings {
+    pub locale: String,
+    pub view_distance: u8,
+    pub chat_mode: ChatMode,
+    pub chat_colors: bool,
+    pub displayed_skin_parts: u8,
+    pub main_hand: MainHand,
+    pub enable_text_filtering: bool,
+    pub allow_server_listings: bool,
+}
+
+impl From<ClientInformationS<'_>> for ClientSettings {
+    fn from(value: ClientInformationS<'_>) -> Self {
+        Self {
+            locale: value.locale.0.to_owned(),
+            // the client is supposed to send 2-32, but don't trust it
+            view_distance: (value.view_distance.max(2) as u8).min(MAX_VIEW_DISTANCE),
+            chat_mode: value.chat_mode,
+            chat_colors: value.chat_colors,
+            displayed_skin_parts: value.displayed_skin_parts,
+            main_hand: value.main_hand,
+            enable_text_filtering: value.enable_text_filtering,
+            allow_server_listings: value.allow_server_listings,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -97,24 +233,51 @@ impl SharedPlayer {
         id: u16,
         connection: TcpStream,
     ) -> Self {
+        Self::with_io(crawlstate, permit, id, NetIo::new(connection))
+    }
+
+    fn with_io(crawlstate: CrawlState, permit: OwnedSemaphorePermit, id: u16, io: NetIo) -> Self {
+        let world = crawlstate.worlds[0].name.clone();
+
         Self(Arc::new(Player {
             id,
-            io: NetIo::new(connection),
+            io,
+            background_write: std::sync::Mutex::new(None),
             frame_queue: Mutex::new(Vec::new()),
             _permit: permit,
+            holds_slot: AtomicBool::new(false),
 
             crawlstate,
             packet_state: RwLock::new(PacketState::Handshaking),
 
             uuid: RwLock::new(None),
+            username: RwLock::new(None),
+            properties: RwLock::new(Vec::new()),
+            protocol_version: RwLock::new(None),
+            host: RwLock::new(None),
             tp_state: RwLock::new(TeleportState::Clear),
+            world: RwLock::new(world),
 
             last_keepalive: RwLock::new(Instant::now()),
+            pending_keepalive: Mutex::new(None),
+            pending_ping: Mutex::new(None),
+            last_active: RwLock::new(Instant::now()),
+            latency: RwLock::new(None),
 
             entity: RwLock::new(Entity::default()),
 
             next_window_id: Mutex::new(1),
             window: RwLock::new(None),
+            inventory: RwLock::new(HashMap::new()),
+            held_slot: AtomicU8::new(0),
+
+            settings: RwLock::new(None),
+            chunk_view: Mutex::new(ChunkView::default()),
+            tracked: Mutex::new(HashSet::new()),
+
+            connected_at: SystemTime::now(),
+            connection_logged: AtomicBool::new(false),
+            rejoined: AtomicBool::new(false),
         }))
     }
 
@@ -133,11 +296,22 @@ impl SharedPlayer {
 
         // crawlspace intentionally doesn't support legacy pings :3
         match timeout(Duration::from_secs(5), self.handshake()).await {
-            Err(e) => warn!("Timed out waiting for {} to connect: {e}", self.0.id),
-            Ok(Err(why)) => warn!("Error handshaking: {why}"),
+            Err(e) => {
+                warn!("Timed out waiting for {} to connect: {e}", self.0.id);
+                self.release_slot();
+                self.log_disconnect("timed out during handshake").await;
+            }
+            Ok(Err(why)) => {
+                warn!("Error handshaking: {why}");
+                self.release_slot();
+                self.log_disconnect(&format!("handshake failed: {why}"))
+                    .await;
+            }
             Ok(Ok(())) => {
                 let s = self.0.packet_state.read().await;
                 if let PacketState::Status = *s {
+                    drop(s);
+                    self.log_disconnect("status ping").await;
                     return;
                 }
                 drop(s);
@@ -149,12 +323,45 @@ impl SharedPlayer {
 
                 match self.begin_play().await {
                     Ok(()) => debug!("Play loop for {} done.", self.id()),
-                    Err(why) => error!("Failed to play player {}! {why}", self.id()),
+                    Err(why) => {
+                        error!("Failed to play player {}! {why}", self.id());
+                        self.release_slot();
+                        self.log_disconnect(&format!("failed to start play: {why}"))
+                            .await;
+                    }
                 }
             }
         }
     }
 
+    /// Takes one of the [`crate::state::State::max_players`] slots, if there's one left. Logins
+    /// racing each other can't both take the last one.
+    fn reserve_slot(&self) -> bool {
+        let state = &self.0.crawlstate;
+        let reserved = state
+            .current_players
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                (current < state.max_players).then_some(current + 1)
+            })
+            .is_ok();
+
+        if reserved {
+            self.0.holds_slot.store(true, Ordering::SeqCst);
+        }
+        reserved
+    }
+
+    /// Gives back the slot [`Self::reserve_slot`] took, if the player has one. Safe to call more
+    /// than once.
+    pub fn release_slot(&self) {
+        if self.0.holds_slot.swap(false, Ordering::SeqCst) {
+            self.0
+                .crawlstate
+                .current_players
+                .fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
     async fn handshake(&self) -> Result<()> {
         let state = self.0.crawlstate.clone();
 
@@ -168,21 +375,37 @@ impl SharedPlayer {
             );
         }
 
+        {
+            let mut protocol_version = self.0.protocol_version.write().await;
+            *protocol_version = Some(p.protocol_version.0);
+        }
+
+        debug!(
+            "Player {} connected via {}:{}",
+            self.id(),
+            p.host(),
+            p.server_port
+        );
+
+        {
+            let mut host = self.0.host.write().await;
+            *host = Some(p.host());
+        }
+
         let next_state = p.next_state;
 
-        let mut s = self.0.packet_state.write().await;
         match next_state {
             PacketState::Status => {
-                *s = PacketState::Status;
-                drop(s);
+                self.transition_to(PacketState::Status).await?;
                 self.handle_status().await?;
             }
             PacketState::Login => {
-                *s = PacketState::Login;
-                drop(s);
+                self.transition_to(PacketState::Login).await?;
                 self.login().await?;
             }
-            s => unimplemented!("state {:#?} unimplemented after handshake", s),
+            // transfers aren't supported yet, and nothing else can be asked for. erroring out
+            // logs a warning and closes the connection
+            s => bail!("client asked for unsupported state {s:?} after handshake"),
         }
 
         Ok(())
@@ -191,6 +414,7 @@ impl SharedPlayer {
     async fn handle_status(&self) -> Result<()> {
         self.0.io.rx::<StatusRequestS>().await?;
         let state = self.0.crawlstate.clone();
+        let host = self.0.host.read().await.clone();
 
         let res = json!({
             "version": {
@@ -198,11 +422,11 @@ impl SharedPlayer {
                 "protocol": state.version_number,
             },
             "players": {
-                "online": state.current_players,
+                "online": state.current_players.load(Ordering::SeqCst),
                 "max": state.max_players
             },
             "description": {
-                "text": state.description
+                "text": state.motd_for(host.as_deref())
             },
             "enforcesSecureChat": false
         });
@@ -225,44 +449,123 @@ impl SharedPlayer {
         let login = self.0.io.rx::<LoginStartS>().await?;
         let login: LoginStartS = login.decode()?;
 
+        // status pings from other versions are fine, but they can't play
+        let protocol_version = self.0.protocol_version.read().await.unwrap_or_default();
+        if protocol_version != state.version_number {
+            let reason = if protocol_version < state.version_number {
+                format!("Outdated client! Please use {}", state.version_name)
+            } else {
+                format!("Outdated server! I'm still on {}", state.version_name)
+            };
+            self.kick(reason).await;
+            bail!(
+                "client protocol version {protocol_version} doesn't match server version {}",
+                state.version_number
+            );
+        }
+
         // need to manually clone this or else the reference to self.io lives too long
         // TODO: clean up lifetimes on encode/decode - possibly just clone strings?
-        let uuid = login.player_uuid;
-        let username = login.name.0.to_owned();
+        // without online mode, the uuid the client sent is taken as is
+        #[allow(unused_mut)]
+        let mut uuid = login.player_uuid;
+        #[allow(unused_mut)]
+        let mut username = login.name.0.to_owned();
+        #[allow(unused_mut)]
+        let mut properties = Vec::new();
+
+        // the connection limit counts status pings and logins too, so it isn't enough here
+        if !self.reserve_slot() {
+            self.kick("Server full").await;
+            bail!("server is full");
+        }
+
+        #[cfg(feature = "encryption")]
+        if let Some(authenticator) = &state.authenticator {
+            let profile = self.authenticate(authenticator, &username).await?;
+            uuid = profile.id;
+            username = profile.name;
+            properties = profile.properties;
+        }
 
         if state.velocity_forwarding {
             let understood = self.login_velocity().await?;
 
             if !understood {
-                warn!(
-                    "Velocity forwarding is on, but client {} did not properly respond to our forwarding request. This will kick in future.",
+                self.kick("This server requires you to connect through Velocity.")
+                    .await;
+                bail!(
+                    "Velocity forwarding is on, but client {} did not properly respond to our forwarding request",
                     self.0.id
-                )
+                );
             }
         }
 
+        #[cfg(feature = "compression")]
+        if let Some(threshold) = state.compression_threshold {
+            self.0
+                .io
+                .tx(&SetCompressionC {
+                    threshold: VarInt(threshold as i32),
+                })
+                .await?;
+            self.0.io.set_compression(threshold).await;
+        }
+
         let success = LoginSuccessC {
             uuid,
             username: Bounded(&username),
-            properties: Vec::new(),
+            properties: properties
+                .iter()
+                .map(ProfileProperty::as_property)
+                .collect(),
             strict_error_handling: false,
         };
 
         {
             let mut own_uuid = self.0.uuid.write().await;
             *own_uuid = Some(uuid);
+            let mut own_username = self.0.username.write().await;
+            *own_username = Some(username.clone());
+            let mut own_properties = self.0.properties.write().await;
+            *own_properties = properties.clone();
         }
 
         self.0.io.tx(&success).await?;
         self.0.io.rx::<LoginAckS>().await?;
 
+        self.0
+            .io
+            .tx(&FeatureFlagsC {
+                flags: &state.feature_flags,
+            })
+            .await?;
+
         let clientbound_known_packs = KnownPacksC::of_version(&state.version_name);
         self.0.io.tx(&clientbound_known_packs).await?;
 
-        // TODO: maybe(?) actually handle this
-        self.0.io.rx::<KnownPacksS>().await?;
+        // the client sends its settings at the start of configuration, so pick those up while
+        // waiting for known packs
+        loop {
+            let frame = self.0.io.rx_any().await?;
+
+            if frame.id == ClientInformationConfigS::ID {
+                let info: ClientInformationConfigS = frame.decode()?;
+                self.update_settings(info.0).await;
+            } else if frame.id == KnownPacksS::ID {
+                // TODO: maybe(?) actually handle this
+                break;
+            } else {
+                debug!(
+                    "Got packet ID {} while awaiting {}, discarding",
+                    frame.id,
+                    KnownPacksS::ID
+                );
+            }
+        }
 
         self.0.io.tx_raw(&state.registry_cache.encoded).await?;
+        self.0.io.tx_raw(&state.tag_cache.encoded).await?;
 
         self.0.io.tx(&FinishConfigurationC).await?;
         self.0.io.rx::<FinishConfigurationAckS>().await?;
@@ -270,6 +573,50 @@ impl SharedPlayer {
         Ok(())
     }
 
+    /// Runs the encryption handshake, then checks with the session server that the player owns
+    /// the account they're logging in as.
+    #[cfg(feature = "encryption")]
+    async fn authenticate(
+        &self,
+        authenticator: &Authenticator,
+        username: &str,
+    ) -> Result<GameProfile> {
+        let verify_token: [u8; 4] = rand::thread_rng().gen();
+
+        self.0
+            .io
+            .tx(&EncryptionRequestC {
+                server_id: Bounded(""),
+                public_key: &authenticator.public_key,
+                verify_token: &verify_token,
+                should_authenticate: true,
+            })
+            .await?;
+
+        let res = self.0.io.rx::<EncryptionResponseS>().await?;
+        let res: EncryptionResponseS = res.decode()?;
+
+        color_eyre::eyre::ensure!(
+            authenticator.decrypt(res.verify_token)? == verify_token,
+            "{username} sent back the wrong verify token"
+        );
+        let shared_secret: [u8; 16] = authenticator
+            .decrypt(res.shared_secret)?
+            .try_into()
+            .map_err(|_| eyre!("{username} sent a shared secret that isn't 16 bytes"))?;
+
+        self.0.io.enable_encryption(&shared_secret).await?;
+
+        let server_hash = authenticator.server_hash(&shared_secret);
+        match authenticator.has_joined(username, &server_hash).await? {
+            Some(profile) => Ok(profile),
+            None => {
+                self.kick("Failed to verify username!").await;
+                bail!("{username} failed to authenticate with the session server");
+            }
+        }
+    }
+
     async fn login_velocity(&self) -> Result<bool> {
         let req = PluginRequestC {
             message_id: VarInt(0),
@@ -283,13 +630,88 @@ impl SharedPlayer {
         let res: PluginResponseS = res.decode()?;
 
         // todo: replace with a profile maybe?
-        Ok(res.data.is_some() && req.message_id.0 == res.message_id.0)
+        Ok(res.data.0.is_some() && req.message_id.0 == res.message_id.0)
     }
 
     async fn begin_play(&self) -> Result<()> {
-        let mut packet_state = self.0.packet_state.write().await;
-        *packet_state = PacketState::Play;
+        self.transition_to(PacketState::Play).await?;
+
+        let state = self.0.crawlstate.clone();
+
+        self.send_play_login(&state.worlds[0]).await?;
+
+        let motd = state
+            .motd_for(self.0.host.read().await.as_deref())
+            .to_owned();
+        self.0
+            .io
+            .tx(&ServerDataC {
+                motd: motd.into(),
+                icon: OptionalPrefixed(None),
+                enforces_secure_chat: false,
+            })
+            .await?;
+
+        self.0
+            .io
+            .tx(&SetTabListHeaderAndFooterC {
+                header: state.tab_header.as_str().into(),
+                footer: state.tab_footer.as_str().into(),
+            })
+            .await?;
+
+        if let Some(pack) = &state.resource_pack {
+            self.send_resource_pack(pack).await?;
+        }
+
+        let spawn = self.spawnpoint().await;
+        self.teleport_awaiting(spawn.0, spawn.1, spawn.2, 0.0, 0.0)
+            .await?;
+
+        self.send_border().await?;
+
+        {
+            let properties = self.0.properties.read().await;
+            let properties: Vec<_> = properties
+                .iter()
+                .map(ProfileProperty::as_property)
+                .collect();
+
+            self.0
+                .io
+                .tx(&PlayerInfoUpdateC {
+                    players: &[PlayerStatus::for_player(self.uuid().await)
+                        .add_player("You're alone...", &properties)
+                        .update_listed(true)],
+                })
+                .await?;
+        }
+
+        let await_chunks = GameEventC::from(GameEvent::StartWaitingForLevelChunks);
+        self.0.io.tx(&await_chunks).await?;
+
+        let set_center = SetCenterChunkC {
+            x: VarInt(spawn.0.floor() as i32 / 16),
+            y: VarInt(spawn.2.floor() as i32 / 16),
+        };
+        self.0.io.tx(&set_center).await?;
+
+        // everything getting the player into play is queued, so urgent packets can go ahead now
+        self.0.io.set_urgent_lane(true).await?;
+
+        // FIXME: GROSS LOL?????? this should(?) change ownership of the player to the server
+        // thread but realistically who knows burhhhh
+        if let Err(why) = state.player_send.send(self.clone()).await {
+            return Err(why.into());
+        }
+        self.spawn_read_loop();
+
+        Ok(())
+    }
 
+    /// Sends the login (play) packet that puts the client in `world`, and the tick and time
+    /// state that go with it.
+    async fn send_play_login(&self, world: &WorldSpec) -> Result<()> {
         let state = self.0.crawlstate.clone();
 
         let max_players: i32 = state.max_players.try_into().unwrap_or(50);
@@ -297,21 +719,25 @@ impl SharedPlayer {
         let login = LoginPlayC {
             entity_id: self.0.id as i32,
             is_hardcore: false,
-            dimension_names: vec![Bounded::<&'static str>("minecraft:the_end")],
+            dimension_names: state
+                .worlds
+                .iter()
+                .map(|w| Bounded(w.dimension_name.as_str()))
+                .collect(),
             max_players: VarInt(max_players),
-            view_distance: VarInt(32),
+            view_distance: VarInt(self.view_distance().await as i32),
             simulation_distance: VarInt(8),
             reduced_debug_info: !cfg!(debug_assertions),
             enable_respawn_screen: false,
             do_limited_crafting: false,
             dimension_type: state.registry_cache.the_end_id,
-            dimension_name: Bounded::<&'static str>("minecraft:the_end"),
+            dimension_name: Bounded(world.dimension_name.as_str()),
             hashed_seed: 0,
-            gamemode: Gamemode::Creative,
+            gamemode: state.gamemode,
             previous_gamemode: Some(Gamemode::Adventure),
             is_debug: false,
             is_superflat: false,
-            death_location: None,
+            death_location: OptionalPrefixed(None),
             portal_cooldown: VarInt(0),
             enforces_secure_chat: false,
         };
@@ -322,53 +748,237 @@ impl SharedPlayer {
             .io
             .tx(&SetTickingStateC {
                 tick_rate: 20.0,
-                is_frozen: false,
+                is_frozen: state.freeze_time,
             })
             .await?;
 
         self.0.io.tx(&StepTicksC(10)).await?;
 
-        let spawnpoint = state.spawnpoint;
-        self.teleport_awaiting(spawnpoint.0, spawnpoint.1, spawnpoint.2, 0.0, 0.0)
-            .await?;
-
         self.0
             .io
-            .tx(&SetBorderCenterC {
-                x: spawnpoint.0,
-                z: spawnpoint.2,
+            .tx(&UpdateTimeC {
+                world_age: 0,
+                time_of_day: 6000,
+                advancing: !state.freeze_time,
             })
-            .await?;
+            .await
+    }
+
+    /// Sends the border's center and size. The client forgets these whenever it respawns or
+    /// rejoins.
+    async fn send_border(&self) -> Result<()> {
+        let border = *self.0.crawlstate.border.read().await;
 
         self.0
             .io
-            .tx(&SetBorderSizeC(state.border_radius as f64 * 2.0))
+            .tx(&SetBorderCenterC {
+                x: border.center_x,
+                z: border.center_z,
+            })
             .await?;
 
+        self.0.io.tx(&SetBorderSizeC(border.diameter)).await
+    }
+
+    /// Moves the connection to another state, or errors if that isn't a step the protocol allows
+    /// from where it is now.
+    async fn transition_to(&self, next: PacketState) -> Result<()> {
+        let mut packet_state = self.0.packet_state.write().await;
+        if !packet_state.can_transition_to(next) {
+            Err(PacketStateTransitionError {
+                from: *packet_state,
+                to: next,
+            })?;
+        }
+
+        *packet_state = next;
+        Ok(())
+    }
+
+    /// Whether the player is in play, as opposed to logging in or reconfiguring. Only players in
+    /// play should be sent play packets.
+    pub async fn in_play(&self) -> bool {
+        matches!(*self.0.packet_state.read().await, PacketState::Play)
+    }
+
+    /// Sends the tags again, the same ones the player got while configuring, e.g. after the client
+    /// was sent something that changed them. Unlike registries, tags don't need reconfiguring.
+    pub async fn send_tags(&self) -> Result<()> {
+        let state = self.0.crawlstate.clone();
+        self.0.io.tx_raw(&state.tag_cache.encoded_play).await
+    }
+
+    /// Sends the player back to configuration to get registries again, e.g. after they've
+    /// changed. The rest happens in [`Self::handle_frame`] as the client answers, and once it's
+    /// back in play the server resends chunks, the border and entities.
+    pub async fn reconfigure(&self) -> Result<()> {
+        // configuration packets can't have play packets overtaking them
+        self.0.io.set_urgent_lane(false).await?;
+        self.0.io.tx(&StartConfigurationC).await
+    }
+
+    /// Called once the client has acknowledged [`StartConfigurationC`] and is in configuration.
+    async fn start_reconfiguration(&self) -> Result<()> {
+        self.transition_to(PacketState::Configuration).await?;
+
+        let state = self.0.crawlstate.clone();
+
         self.0
             .io
-            .tx(&PlayerInfoUpdateC {
-                players: &[PlayerStatus::for_player(self.uuid().await)
-                    .add_player("You're alone...", &[])
-                    .update_listed(true)],
+            .tx(&FeatureFlagsC {
+                flags: &state.feature_flags,
             })
             .await?;
 
+        let clientbound_known_packs = KnownPacksC::of_version(&state.version_name);
+        self.0.io.tx(&clientbound_known_packs).await
+    }
+
+    /// Handles packets while reconfiguring. Configuration has its own packet ids, so these can't
+    /// go through the play handlers.
+    async fn handle_config_frame(&self, frame: Frame) -> Result<()> {
+        match frame.id {
+            ClientInformationConfigS::ID => {
+                let info: ClientInformationConfigS = frame.decode()?;
+                self.update_settings(info.0).await;
+            }
+
+            KnownPacksS::ID => {
+                let state = self.0.crawlstate.clone();
+                self.0.io.tx_raw(&state.registry_cache.encoded).await?;
+                self.0.io.tx_raw(&state.tag_cache.encoded).await?;
+                self.0.io.tx(&FinishConfigurationC).await?;
+            }
+
+            FinishConfigurationAckS::ID => self.rejoin_play().await?,
+
+            id => debug!("Got packet ID {id} while reconfiguring, discarding"),
+        }
+
+        Ok(())
+    }
+
+    /// Puts a reconfigured player back into play where they were. The client starts over with an
+    /// empty world, so the tick resends everything once [`Self::take_rejoined`] says so.
+    async fn rejoin_play(&self) -> Result<()> {
+        self.transition_to(PacketState::Play).await?;
+
+        let state = self.0.crawlstate.clone();
+        let world = self.world().await;
+        let Some(spec) = state.worlds.iter().find(|w| w.name == world) else {
+            bail!("player is in unknown world {world}");
+        };
+
+        self.send_play_login(spec).await?;
+
+        {
+            let mut view = self.0.chunk_view.lock().await;
+            *view = ChunkView::default();
+            let mut tracked = self.0.tracked.lock().await;
+            tracked.clear();
+        }
+
+        self.send_border().await?;
+
+        {
+            let properties = self.0.properties.read().await;
+            let properties: Vec<_> = properties
+                .iter()
+                .map(ProfileProperty::as_property)
+                .collect();
+
+            self.0
+                .io
+                .tx(&PlayerInfoUpdateC {
+                    players: &[PlayerStatus::for_player(self.uuid().await)
+                        .add_player("You're alone...", &properties)
+                        .update_listed(true)],
+                })
+                .await?;
+        }
+
         let await_chunks = GameEventC::from(GameEvent::StartWaitingForLevelChunks);
         self.0.io.tx(&await_chunks).await?;
 
-        let set_center = SetCenterChunkC {
-            x: VarInt(spawnpoint.0.floor() as i32 / 16),
-            y: VarInt(spawnpoint.2.floor() as i32 / 16),
+        let (x, y, z, yaw, pitch) = {
+            let entity = self.0.entity.read().await;
+            (entity.x, entity.y, entity.z, entity.yaw, entity.pitch)
         };
-        self.0.io.tx(&set_center).await?;
+        self.teleport(x, y, z, yaw, pitch).await?;
 
-        // FIXME: GROSS LOL?????? this should(?) change ownership of the player to the server
-        // thread but realistically who knows burhhhh
-        state.player_send.send(self.clone()).await?;
-        self.spawn_read_loop();
+        self.0.rejoined.store(true, Ordering::Relaxed);
+        self.0.io.set_urgent_lane(true).await
+    }
 
-        Ok(())
+    /// Whether the player came back from reconfiguring since this was last called, and needs the
+    /// border and entities sent again.
+    pub fn take_rejoined(&self) -> bool {
+        self.0.rejoined.swap(false, Ordering::Relaxed)
+    }
+
+    /// Sends the player a resource pack. If it's forced, this waits until the client has loaded it
+    /// and errors if it couldn't, so the player never spawns without it.
+    async fn send_resource_pack(&self, pack: &ResourcePack) -> Result<()> {
+        self.0
+            .io
+            .tx(&AddResourcePackC {
+                uuid: pack.uuid,
+                url: Bounded(pack.url.as_str()),
+                hash: Bounded(pack.hash.as_str()),
+                forced: pack.forced,
+                prompt_message: OptionalPrefixed(None),
+            })
+            .await?;
+
+        if !pack.forced {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        loop {
+            // downloading can take a while, so keep the client from timing out in the meantime
+            let frame = match self.0.io.rx_any().await {
+                Ok(frame) => frame,
+                Err(why) if why.downcast_ref::<Elapsed>().is_some() => {
+                    if started.elapsed() > RESOURCE_PACK_TIMEOUT {
+                        bail!(
+                            "Player {} took too long to load the resource pack",
+                            self.0.id
+                        );
+                    }
+
+                    self.ping(rand::random()).await?;
+                    continue;
+                }
+                Err(why) => return Err(why),
+            };
+
+            match frame.id {
+                ResourcePackResponseS::ID => {
+                    let response: ResourcePackResponseS = frame.decode()?;
+                    if response.uuid != pack.uuid || response.result.is_pending() {
+                        continue;
+                    }
+
+                    match response.result {
+                        ResourcePackResult::SuccessfullyDownloaded => return Ok(()),
+                        result => bail!(
+                            "Player {} couldn't load the resource pack: {result:?}",
+                            self.0.id
+                        ),
+                    }
+                }
+                ClientInformationS::ID => {
+                    let info: ClientInformationS = frame.decode()?;
+                    self.update_settings(info).await;
+                }
+                KeepAliveS::ID => self.handle_keepalive(frame.decode()?).await,
+                id => debug!(
+                    "Got packet ID {id} while awaiting {}, discarding",
+                    ResourcePackResponseS::ID
+                ),
+            }
+        }
     }
 
     pub async fn handle_all_packets(&self) -> Result<()> {
@@ -377,18 +987,38 @@ impl SharedPlayer {
             std::mem::take(&mut *frame_queue)
         };
 
+        #[cfg(feature = "timings")]
+        let server = self.0.crawlstate.get_server().await;
+
         for packet in packets {
+            #[cfg(feature = "timings")]
+            let (id, start) = (packet.id, Instant::now());
+
             self.handle_frame(packet).await?;
+
+            #[cfg(feature = "timings")]
+            server.record_packet_timing(id, start.elapsed());
         }
 
         Ok(())
     }
 
     pub async fn keepalive(&self) -> Result<()> {
+        // configuration has its own keepalive, and reconfiguring doesn't take long anyway
+        if !self.in_play().await {
+            return Ok(());
+        }
+
         let last_keepalive = self.0.last_keepalive.read().await;
         let now = Instant::now();
 
-        if now - *last_keepalive < Duration::from_secs(10) {
+        if now - *last_keepalive < KEEPALIVE_INTERVAL {
+            return Ok(());
+        }
+
+        // don't replace a keepalive that's still waiting for an answer, or a slow client's reply
+        // would never match
+        if self.0.pending_keepalive.lock().await.is_some() {
             return Ok(());
         }
 
@@ -403,68 +1033,496 @@ impl SharedPlayer {
 
         // if this times out then the player just hasn't requested ping yet lol
         match timeout(Duration::from_secs(1), self.ping(id)).await {
-            Ok(Ok(())) | Err(_) => Ok(()),
-            Ok(Err(why)) => Err(why),
+            Ok(Ok(())) | Err(_) => (),
+            Ok(Err(why)) => return Err(why),
         }
-    }
 
-    async fn ping(&self, id: i64) -> Result<()> {
-        let ka = KeepAliveC(id);
-        self.0.io.tx(&ka).await?;
-        // TODO: check return keepalive, kick
-        Ok(())
+        self.probe_latency().await
     }
 
-    pub async fn uuid(&self) -> Uuid {
-        let uuid = self.0.uuid.read().await;
-        uuid.expect("uuid() called on uninitialized player - only call this after login!")
-    }
+    /// Sends a [`PingC`] to time how long the client takes to answer it. Unlike keepalives,
+    /// clients answer these straight away.
+    async fn probe_latency(&self) -> Result<()> {
+        let id = rand::random();
 
-    pub async fn teleport_awaiting(
-        &self,
-        x: f64,
-        y: f64,
-        z: f64,
-        yaw: f32,
-        pitch: f32,
-    ) -> Result<()> {
         {
-            let tp_state = self.0.tp_state.read().await;
-            if *tp_state != TeleportState::Clear {
-                bail!("Player {} already has a teleport pending", self.0.id);
-            };
+            let mut pending_ping = self.0.pending_ping.lock().await;
+            *pending_ping = Some((id, Instant::now()));
         }
 
-        let tp = SynchronisePositionC::new(x, y, z, yaw, pitch);
+        self.0.io.tx(&PingC(id)).await
+    }
+
+    /// The round trip time from the last answered keepalive or latency probe, if there's been
+    /// one.
+    pub async fn latency(&self) -> Option<Duration> {
+        *self.0.latency.read().await
+    }
+
+    async fn ping(&self, id: i64) -> Result<()> {
         {
-            let mut tp_state = self.0.tp_state.write().await;
-            // player will be given 5 (FIVE) SECONDS TO ACK!!!!!
-            *tp_state = TeleportState::Pending(tp.id, Instant::now());
+            let mut pending_keepalive = self.0.pending_keepalive.lock().await;
+            *pending_keepalive = Some((id, Instant::now()));
         }
-        self.0.io.tx(&tp).await?;
 
-        let tp_ack = self.0.io.rx::<ConfirmTeleportS>().await?;
-        let tp_ack = tp_ack.decode::<ConfirmTeleportS>()?;
+        let ka = KeepAliveC(id);
+        self.0.io.tx_urgent(&ka).await
+    }
 
-        match tokio::time::timeout(Duration::from_secs(5), self.confirm_teleport(tp_ack.id)).await {
-            Ok(Ok(())) => {
-                let mut tp_state = self.0.tp_state.write().await;
-                *tp_state = TeleportState::Clear;
-            }
-            Ok(Err(why)) => {
-                warn!("Spawning player {} failed: {why}", self.0.id);
-                Err(why)?;
-            }
-            Err(why) => {
-                warn!("Spawning player {} timed out: {why}", self.0.id);
-                Err(why)?;
+    /// Clears the pending keepalive and records the latency, if `packet` answers it.
+    async fn handle_keepalive(&self, packet: KeepAliveS) {
+        let mut pending_keepalive = self.0.pending_keepalive.lock().await;
+        match *pending_keepalive {
+            Some((id, sent)) if id == packet.0 => {
+                *pending_keepalive = None;
+                let rtt = sent.elapsed();
+                debug!("Player {} keepalive took {}ms", self.0.id, rtt.as_millis());
+                let mut latency = self.0.latency.write().await;
+                *latency = Some(rtt);
             }
+            _ => debug!(
+                "Player {} sent unexpected keepalive {}",
+                self.0.id, packet.0
+            ),
         }
-        Ok(())
     }
 
-    async fn confirm_teleport(&self, id: i32) -> Result<(), TeleportError> {
-        let tp_state = self.0.tp_state.read().await;
+    /// Whether the player has sat on a keepalive for longer than [`KEEPALIVE_TIMEOUT`].
+    pub async fn keepalive_timed_out(&self) -> bool {
+        let pending_keepalive = self.0.pending_keepalive.lock().await;
+        pending_keepalive.is_some_and(|(_, sent)| sent.elapsed() > KEEPALIVE_TIMEOUT)
+    }
+
+    async fn update_settings(&self, info: ClientInformationS<'_>) {
+        let settings = ClientSettings::from(info);
+        debug!("Player {} updated settings: {settings:?}", self.id());
+
+        let mut s = self.0.settings.write().await;
+        *s = Some(settings);
+    }
+
+    /// The settings the client last reported, if any.
+    pub async fn settings(&self) -> Option<ClientSettings> {
+        let settings = self.0.settings.read().await;
+        settings.clone()
+    }
+
+    /// The render distance to use for this player: the client's requested distance, capped at
+    /// what the server allows.
+    pub async fn view_distance(&self) -> u8 {
+        let settings = self.0.settings.read().await;
+        settings
+            .as_ref()
+            .map_or(MAX_VIEW_DISTANCE, |s| s.view_distance)
+    }
+
+    /// The chunk the player is currently standing in.
+    pub async fn chunk_position(&self) -> (i32, i32) {
+        let entity = self.0.entity.read().await;
+        (
+            (entity.x.floor() as i32) >> 4,
+            (entity.z.floor() as i32) >> 4,
+        )
+    }
+
+    /// Whether the player has moved into another chunk or changed their view distance since
+    /// chunks were last streamed to them. Returns false while a stream is already in progress.
+    pub async fn needs_chunk_update(&self) -> bool {
+        let center = self.chunk_position().await;
+        let view_distance = self.view_distance().await;
+
+        match self.0.chunk_view.try_lock() {
+            Ok(view) => view.center != Some(center) || view.view_distance != view_distance,
+            Err(_) => false,
+        }
+    }
+
+    /// Unloads every chunk the player has, so the next update sends them all again.
+    pub async fn forget_chunks(&self) -> Result<()> {
+        let mut view = self.0.chunk_view.lock().await;
+        for (x, z) in view.loaded.drain() {
+            self.0.io.tx(&UnloadChunkC { x, z }).await?;
+        }
+
+        *view = ChunkView::default();
+        Ok(())
+    }
+
+    /// Sends the player every cached chunk within their view distance that they don't have yet,
+    /// and unloads any they have that are now out of range.
+    pub async fn stream_chunks(&self, world_cache: &WorldCache) -> Result<()> {
+        let mut view = self.0.chunk_view.lock().await;
+        let (cx, cz) = self.chunk_position().await;
+        let view_distance = self.view_distance().await;
+        let distance = view_distance as i32;
+
+        if view.center != Some((cx, cz)) {
+            self.0
+                .io
+                .tx(&SetCenterChunkC {
+                    x: VarInt(cx),
+                    y: VarInt(cz),
+                })
+                .await?;
+        }
+
+        let in_range =
+            |(x, z): (i32, i32)| (x - cx).abs() <= distance && (z - cz).abs() <= distance;
+
+        let out_of_range = view
+            .loaded
+            .iter()
+            .filter(|c| !in_range(**c))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for (x, z) in out_of_range {
+            self.0.io.tx(&UnloadChunkC { x, z }).await?;
+            view.loaded.remove(&(x, z));
+        }
+
+        // nearest first, ring by ring, so the chunks around the player show up before the edges
+        let mut to_send = world_cache
+            .encoded
+            .iter()
+            .filter(|(pos, _)| in_range(*pos) && !view.loaded.contains(pos))
+            .collect::<Vec<_>>();
+        to_send.sort_by_key(|((x, z), _)| {
+            let (dx, dz) = (x - cx, z - cz);
+            (dx.abs().max(dz.abs()), dx * dx + dz * dz)
+        });
+
+        for (pos, packet) in to_send {
+            self.0.io.tx_raw(packet).await?;
+            view.loaded.insert(*pos);
+        }
+
+        view.center = Some((cx, cz));
+        view.view_distance = view_distance;
+
+        Ok(())
+    }
+
+    pub async fn uuid(&self) -> Uuid {
+        let uuid = self.0.uuid.read().await;
+        uuid.expect("uuid() called on uninitialized player - only call this after login!")
+    }
+
+    pub async fn username(&self) -> String {
+        let username = self.0.username.read().await;
+        username
+            .clone()
+            .expect("username() called on uninitialized player - only call this after login!")
+    }
+
+    /// Disconnects the player, showing them `reason`. The connection is usually already dead when
+    /// someone gets kicked, so failing to send the reason is ignored.
+    pub async fn kick(&self, reason: impl Into<TextComponent>) {
+        let reason = reason.into();
+        let state = *self.0.packet_state.read().await;
+
+        let sent = match state {
+            PacketState::Login => self.0.io.tx(&LoginDisconnectC { reason }).await,
+            PacketState::Configuration => self.0.io.tx(&DisconnectConfigC { reason }).await,
+            PacketState::Play => self.0.io.tx_urgent(&DisconnectC { reason }).await,
+            // nothing to tell a client that's only pinging
+            PacketState::Handshaking | PacketState::Status | PacketState::Transfer => Ok(()),
+        };
+
+        let flushed = match sent {
+            Ok(()) => timeout(KICK_FLUSH_TIMEOUT, self.0.io.flush()).await,
+            Err(why) => Ok(Err(why)),
+        };
+
+        match flushed {
+            Ok(Ok(())) => (),
+            Ok(Err(why)) => debug!("Failed to send disconnect to player {}: {why}", self.id()),
+            Err(_) => debug!("Timed out sending disconnect to player {}", self.id()),
+        }
+
+        self.0.io.disconnect().await;
+    }
+
+    /// Writes this connection to the connection log, if one is configured. Only the first call
+    /// for a connection is recorded.
+    pub async fn log_disconnect(&self, reason: &str) {
+        let Some(ref log) = self.0.crawlstate.connection_log else {
+            return;
+        };
+
+        if self.0.connection_logged.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let username = self.0.username.read().await;
+        let uuid = self.0.uuid.read().await;
+        let protocol_version = *self.0.protocol_version.read().await;
+        let reached_play = matches!(
+            *self.0.packet_state.read().await,
+            PacketState::Play | PacketState::Configuration
+        );
+
+        let record = ConnectionRecord {
+            timestamp: self
+                .0
+                .connected_at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            peer_addr: &self.0.io.peer_addr,
+            username: username.as_deref(),
+            uuid: uuid.map(|u| u.to_string()),
+            protocol_version,
+            reached_play,
+            disconnect_reason: reason,
+            duration_secs: self
+                .0
+                .connected_at
+                .elapsed()
+                .map_or(0.0, |d| d.as_secs_f64()),
+        };
+
+        if let Err(why) = log.write(&record) {
+            warn!("Failed to write connection log for {}: {why}", self.id());
+        }
+    }
+
+    pub async fn teleport_awaiting(
+        &self,
+        x: f64,
+        y: f64,
+        z: f64,
+        yaw: f32,
+        pitch: f32,
+    ) -> Result<()> {
+        {
+            let tp_state = self.0.tp_state.read().await;
+            if *tp_state != TeleportState::Clear {
+                bail!("Player {} already has a teleport pending", self.0.id);
+            };
+        }
+
+        let tp = SynchronisePositionC::new(x, y, z, yaw, pitch);
+        {
+            let mut tp_state = self.0.tp_state.write().await;
+            // player will be given 5 (FIVE) SECONDS TO ACK!!!!!
+            *tp_state = TeleportState::Pending(tp.id, Instant::now());
+        }
+        self.0.io.tx(&tp).await?;
+
+        let tp_ack = self.0.io.rx::<ConfirmTeleportS>().await?;
+        let tp_ack = tp_ack.decode::<ConfirmTeleportS>()?;
+
+        match tokio::time::timeout(Duration::from_secs(5), self.confirm_teleport(tp_ack.id)).await {
+            Ok(Ok(())) => {
+                let mut tp_state = self.0.tp_state.write().await;
+                *tp_state = TeleportState::Clear;
+
+                let mut entity = self.0.entity.write().await;
+                entity.teleport(x, y, z, yaw, pitch);
+            }
+            Ok(Err(why)) => {
+                warn!("Spawning player {} failed: {why}", self.0.id);
+                Err(why)?;
+            }
+            Err(why) => {
+                warn!("Spawning player {} timed out: {why}", self.0.id);
+                Err(why)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Teleports the player without waiting for them to acknowledge it. The acknowledgement is
+    /// picked up by the play loop, so use this instead of [`Self::teleport_awaiting`] once the
+    /// player is in play.
+    pub async fn teleport(&self, x: f64, y: f64, z: f64, yaw: f32, pitch: f32) -> Result<()> {
+        let tp = SynchronisePositionC::new(x, y, z, yaw, pitch);
+
+        {
+            let mut tp_state = self.0.tp_state.write().await;
+            if *tp_state != TeleportState::Clear {
+                bail!("Player {} already has a teleport pending", self.0.id);
+            }
+            *tp_state = TeleportState::Pending(tp.id, Instant::now());
+        }
+
+        {
+            let mut entity = self.0.entity.write().await;
+            entity.teleport(x, y, z, yaw, pitch);
+        }
+
+        self.0.io.tx(&tp).await
+    }
+
+    /// Locks the player's view to the given entity, which must already be spawned for them. Pass
+    /// the player's own id to give them their camera back.
+    pub async fn set_camera(&self, entity_id: i32) -> Result<()> {
+        self.0
+            .io
+            .tx(&SetCameraC {
+                camera_id: VarInt(entity_id),
+            })
+            .await
+    }
+
+    /// Hides the player's current title. If `reset` is set, the title fade times go back to their
+    /// defaults too.
+    pub async fn clear_titles(&self, reset: bool) -> Result<()> {
+        self.0.io.tx(&ClearTitlesC { reset }).await
+    }
+
+    /// Shows the player a message from the server, either in chat or above their hotbar.
+    pub async fn send_system_message(
+        &self,
+        message: impl Into<TextComponent>,
+        overlay: bool,
+    ) -> Result<()> {
+        self.0
+            .io
+            .tx_urgent(&SystemChatMessageC {
+                content: message.into(),
+                overlay,
+            })
+            .await
+    }
+
+    /// Sends several chat messages in one write, for things like a scrolling ticker.
+    pub async fn send_messages(&self, messages: &[TextComponent]) -> Result<()> {
+        let mut encoder = Encoder::new();
+        for message in messages {
+            encoder.append_packet(&SystemChatMessageC {
+                content: message.clone(),
+                overlay: false,
+            })?;
+        }
+
+        self.0.io.tx_raw_urgent(&encoder.take()).await
+    }
+
+    /// The name of the world the player is in.
+    pub async fn world(&self) -> String {
+        self.0.world.read().await.clone()
+    }
+
+    /// Respawns the player into another world's dimension and puts them back at spawn. Chunks for
+    /// the new world still need streaming afterwards.
+    pub async fn change_world(&self, spec: &WorldSpec) -> Result<()> {
+        let state = self.0.crawlstate.clone();
+
+        {
+            let mut world = self.0.world.write().await;
+            *world = spec.name.clone();
+        }
+
+        self.0
+            .io
+            .tx(&RespawnC {
+                dimension_type: state.registry_cache.the_end_id,
+                dimension_name: Bounded(spec.dimension_name.as_str()),
+                hashed_seed: 0,
+                gamemode: state.gamemode,
+                previous_gamemode: Some(Gamemode::Adventure),
+                is_debug: false,
+                is_superflat: false,
+                death_location: OptionalPrefixed(None),
+                portal_cooldown: VarInt(0),
+                data_kept: 0,
+            })
+            .await?;
+
+        // the client throws away its chunks and border when it respawns
+        {
+            let mut view = self.0.chunk_view.lock().await;
+            *view = ChunkView::default();
+        }
+
+        self.send_border().await?;
+
+        let await_chunks = GameEventC::from(GameEvent::StartWaitingForLevelChunks);
+        self.0.io.tx(&await_chunks).await?;
+
+        let (x, y, z) = self.spawnpoint().await;
+        self.teleport(x, y, z, 0.0, 0.0).await
+    }
+
+    /// Shows the end poem and credits if `roll_credits`, otherwise just the "you won" respawn. The
+    /// client asks to respawn once it's done, which sends the player back to spawn.
+    pub async fn win_game(&self, roll_credits: bool) -> Result<()> {
+        self.0
+            .io
+            .tx(&GameEventC::from(GameEvent::WinGame(roll_credits)))
+            .await
+    }
+
+    /// Sends the player back to spawn. There's no death in crawlspace, so this is all a respawn
+    /// does.
+    pub async fn respawn(&self) -> Result<()> {
+        let (x, y, z) = self.spawnpoint().await;
+        self.teleport(x, y, z, 0.0, 0.0).await
+    }
+
+    /// Where the player spawns in the world they're in.
+    async fn spawnpoint(&self) -> (f64, f64, f64) {
+        let server = self.0.crawlstate.get_server().await;
+        server.spawnpoint(&self.world().await).await
+    }
+
+    /// A soft "kill" for our no-combat world: closes whatever the player has open and puts them
+    /// back at spawn.
+    pub async fn reset(&self) -> Result<()> {
+        self.close_window().await?;
+        self.respawn().await
+    }
+
+    /// Closes the player's open window, if they have one.
+    pub async fn close_window(&self) -> Result<()> {
+        let window = {
+            let mut window = self.0.window.write().await;
+            window.take()
+        };
+
+        if let Some(window) = window {
+            self.0
+                .io
+                .tx(&CloseContainerC {
+                    window_id: window.id,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Resets the idle timer, since the player just did something.
+    async fn mark_active(&self) {
+        let mut last_active = self.0.last_active.write().await;
+        *last_active = Instant::now();
+    }
+
+    /// How long it's been since the player last moved or interacted with something.
+    pub async fn idle_for(&self) -> Duration {
+        self.0.last_active.read().await.elapsed()
+    }
+
+    /// Whether the player can be kicked for being idle, i.e. isn't an op or exempted.
+    pub async fn idle_kickable(&self) -> bool {
+        let username = self.0.username.read().await;
+        let state = &self.0.crawlstate;
+        username
+            .as_ref()
+            .is_some_and(|u| !state.ops.contains(u) && !state.idle_kick_exempt.contains(u))
+    }
+
+    /// Whether the player is allowed to run operator-only commands.
+    pub async fn is_op(&self) -> bool {
+        let username = self.0.username.read().await;
+        username
+            .as_ref()
+            .is_some_and(|u| self.0.crawlstate.ops.contains(u))
+    }
+
+    async fn confirm_teleport(&self, id: i32) -> Result<(), TeleportError> {
+        let tp_state = self.0.tp_state.read().await;
         match *tp_state {
             TeleportState::Clear => Err(TeleportError::Unexpected),
             TeleportState::Pending(expected, _) if id == expected => Ok(()),
@@ -525,14 +1583,41 @@ impl SharedPlayer {
     }
 
     async fn handle_frame(&self, frame: Frame) -> Result<()> {
+        if matches!(
+            *self.0.packet_state.read().await,
+            PacketState::Configuration
+        ) {
+            return self.handle_config_frame(frame).await;
+        }
+
+        // movement only counts if the player actually moved, which is checked below
+        if matches!(
+            frame.id,
+            UseItemOnS::ID
+                | InteractS::ID
+                | ChatCommandS::ID
+                | ChatMessageS::ID
+                | ClickContainerS::ID
+                | PickItemS::ID
+                | SetHeldItemS::ID
+        ) {
+            self.mark_active().await;
+        }
+
         match frame.id {
+            ConfigurationAckS::ID => self.start_reconfiguration().await?,
+
             SetPlayerPositionS::ID => {
                 let packet: SetPlayerPositionS = frame.decode()?;
 
                 let tp_state = self.0.tp_state.read().await;
                 if *tp_state == TeleportState::Clear {
                     let mut entity = self.0.entity.write().await;
+                    if entity.position() != (packet.x, packet.feet_y, packet.z) {
+                        self.mark_active().await;
+                    }
                     entity.reposition(packet.x, packet.feet_y, packet.z);
+                    entity.on_ground = packet.on_ground;
                 }
             }
 
@@ -542,21 +1627,149 @@ impl SharedPlayer {
                 let tp_state = self.0.tp_state.read().await;
                 if *tp_state == TeleportState::Clear {
                     let mut entity = self.0.entity.write().await;
+                    if entity.position() != (packet.x, packet.feet_y, packet.z)
+                        || (entity.yaw, entity.pitch) != (packet.yaw, packet.pitch)
+                    {
+                        self.mark_active().await;
+                    }
                     entity.reposition(packet.x, packet.feet_y, packet.z);
                     entity.rotate(packet.yaw, packet.pitch);
+                    entity.on_ground = packet.on_ground;
                 }
             }
 
+            SetPlayerRotationS::ID => {
+                let packet: SetPlayerRotationS = frame.decode()?;
+
+                let mut entity = self.0.entity.write().await;
+                if (entity.yaw, entity.pitch) != (packet.yaw, packet.pitch) {
+                    self.mark_active().await;
+                }
+                entity.rotate(packet.yaw, packet.pitch);
+                entity.on_ground = packet.on_ground;
+            }
+
             ConfirmTeleportS::ID => {
                 let packet: ConfirmTeleportS = frame.decode()?;
                 self.check_teleports(Some(packet)).await?;
             }
 
+            ClientStatusS::ID => {
+                let packet: ClientStatusS = frame.decode()?;
+                match packet.action {
+                    ClientStatusAction::PerformRespawn => self.respawn().await?,
+                    ClientStatusAction::RequestStats => {
+                        // we don't track any stats, so just give the client an empty list
+                        self.0
+                            .io
+                            .tx(&AwardStatisticsC {
+                                statistics: Vec::new(),
+                            })
+                            .await?;
+                    }
+                }
+            }
+
+            ClientInformationS::ID => {
+                let packet: ClientInformationS = frame.decode()?;
+                self.update_settings(packet).await;
+            }
+
             UseItemOnS::ID => {
                 let packet: UseItemOnS = frame.decode()?;
                 self.handle_use_item(packet).await?;
             }
 
+            ChatCommandS::ID => {
+                let packet: ChatCommandS = frame.decode()?;
+                let server = self.0.crawlstate.get_server().await;
+                server.queue_command(self.clone(), packet.command.0).await;
+            }
+
+            ChatMessageS::ID => {
+                let packet: ChatMessageS = frame.decode()?;
+
+                let username = self.0.username.read().await.clone();
+                let Some(username) = username else {
+                    debug!("Player {} chatted before logging in, ignoring", self.id());
+                    return Ok(());
+                };
+
+                let message = packet
+                    .message
+                    .0
+                    .chars()
+                    .take(MAX_CHAT_LENGTH)
+                    .collect::<String>();
+
+                let server = self.0.crawlstate.get_server().await;
+                server
+                    .queue_chat(format!("<{username}> {message}").into())
+                    .await;
+
+                // nothing has to be listening, so don't hold up packet handling waiting for room
+                let event = ChatEvent {
+                    player: self.clone(),
+                    username,
+                    message,
+                };
+                if let Err(why) = self.0.crawlstate.chat_send.try_send(event) {
+                    trace!("Dropped chat event from player {}: {why}", self.id());
+                }
+            }
+
+            CloseContainerS::ID => {
+                let packet: CloseContainerS = frame.decode()?;
+                let mut window = self.0.window.write().await;
+                if window.as_ref().is_some_and(|w| w.id == packet.window_id) {
+                    *window = None;
+                }
+            }
+
+            ClickContainerS::ID => {
+                let packet: ClickContainerS = frame.decode()?;
+                if !self.0.crawlstate.container_editing {
+                    self.resync_window(packet.window_id).await?;
+                }
+            }
+
+            KeepAliveS::ID => self.handle_keepalive(frame.decode()?).await,
+
+            PongS::ID => {
+                let packet: PongS = frame.decode()?;
+                let pending = self.0.pending_ping.lock().await.take();
+                match pending {
+                    Some((id, sent)) if id == packet.0 => {
+                        let rtt = sent.elapsed();
+                        debug!("Player {} latency: {}ms", self.0.id, rtt.as_millis());
+                        let mut latency = self.0.latency.write().await;
+                        *latency = Some(rtt);
+                    }
+                    _ => debug!("Player {} sent unexpected pong {}", self.0.id, packet.0),
+                }
+            }
+
+            PickItemS::ID => {
+                let packet: PickItemS = frame.decode()?;
+                self.pick_item(packet.slot_to_use).await?;
+            }
+
+            SetHeldItemS::ID => {
+                let packet: SetHeldItemS = frame.decode()?;
+                ensure!(
+                    (0..9).contains(&packet.slot),
+                    "held slot {} isn't in the hotbar",
+                    packet.slot
+                );
+                self.0.held_slot.store(packet.slot as u8, Ordering::Relaxed);
+            }
+
+            InteractS::ID => {
+                let packet: InteractS = frame.decode()?;
+                self.on_interact_entity(packet.entity_id, &packet.interaction, packet.sneaking)
+                    .await?;
+            }
+
             id => {
                 debug!(
                     "Got packet with id {id} from player {}, ignoring",
@@ -568,6 +1781,402 @@ impl SharedPlayer {
         Ok(())
     }
 
+    /// Encodes the packets other players need to see this player's latest movement, or `None` if
+    /// they haven't moved since the last call. Small moves are sent as deltas, and anything past
+    /// ~8 blocks falls back to a full teleport.
+    pub async fn movement_packets(&self) -> Result<Option<BytesMut>> {
+        let mut entity = self.0.entity.write().await;
+        let entity_id = self.0.id as i32;
+        let on_ground = entity.on_ground;
+        let mut encoder = Encoder::new();
+
+        let movement = entity.take_movement();
+        let rotated = !matches!(movement, Movement::None | Movement::Position(..));
+
+        match movement {
+            Movement::None => return Ok(None),
+            Movement::Position(delta_x, delta_y, delta_z) => {
+                encoder.append_packet(&UpdateEntityPositionC {
+                    entity_id,
+                    delta_x,
+                    delta_y,
+                    delta_z,
+                    on_ground,
+                })?;
+            }
+            Movement::Rotation => {
+                encoder.append_packet(&UpdateEntityRotationC {
+                    entity_id,
+                    yaw: entity.yaw,
+                    pitch: entity.pitch,
+                    on_ground,
+                })?;
+            }
+            Movement::PositionAndRotation(delta_x, delta_y, delta_z) => {
+                encoder.append_packet(&UpdateEntityPositionAndRotationC {
+                    entity_id,
+                    delta_x,
+                    delta_y,
+                    delta_z,
+                    yaw: entity.yaw,
+                    pitch: entity.pitch,
+                    on_ground,
+                })?;
+            }
+            Movement::Teleport => {
+                encoder.append_packet(&TeleportEntityC {
+                    entity_id,
+                    x: entity.x,
+                    y: entity.y,
+                    z: entity.z,
+                    yaw: entity.yaw,
+                    pitch: entity.pitch,
+                    on_ground,
+                })?;
+            }
+        }
+
+        if rotated {
+            encoder.append_packet(&SetHeadRotationC {
+                entity_id,
+                head_yaw: entity.yaw,
+            })?;
+        }
+
+        Ok(Some(encoder.take()))
+    }
+
+    /// Encodes what another client needs to show this player when they come into view: their
+    /// profile (unlisted, so the tab list stays as it is), then a bundle with their entity where
+    /// they are now, which way their head's turned, their skin layers and main hand, and what
+    /// they're holding and wearing.
+    pub async fn spawn_packets(&self) -> Result<BytesMut> {
+        let kind = registries()
+            .entity_type
+            .entries
+            .get("minecraft:player")
+            .ok_or_else(|| eyre!("Couldn't find registry entry for entity type minecraft:player"))?
+            .protocol_id;
+
+        let uuid = self.uuid().await;
+        let username = self.username().await;
+        let properties = self.0.properties.read().await;
+        let properties: Vec<_> = properties
+            .iter()
+            .map(ProfileProperty::as_property)
+            .collect();
+        let entity = self.0.entity.read().await;
+        let mut encoder = Encoder::new();
+
+        encoder.append_packet(&PlayerInfoUpdateC {
+            players: &[PlayerStatus::for_player(uuid).add_player(&username, &properties)],
+        })?;
+
+        encoder.append_packet(&BundleDelimiterC)?;
+        encoder.append_packet(&SpawnEntityC {
+            entity_id: self.0.id as i32,
+            entity_uuid: uuid,
+            kind,
+            x: entity.x,
+            y: entity.y,
+            z: entity.z,
+            pitch: entity.pitch,
+            yaw: entity.yaw,
+            head_yaw: entity.yaw,
+            data: 0,
+            velocity_x: 0,
+            velocity_y: 0,
+            velocity_z: 0,
+        })?;
+        encoder.append_packet(&SetHeadRotationC {
+            entity_id: self.0.id as i32,
+            head_yaw: entity.yaw,
+        })?;
+
+        // players who haven't sent settings yet show every skin layer and are right handed,
+        // like a fresh client
+        let (skin_parts, main_hand) = match &*self.0.settings.read().await {
+            Some(settings) => (settings.displayed_skin_parts, settings.main_hand),
+            None => (DEFAULT_SKIN_PARTS, MainHand::Right),
+        };
+        let main_hand = match main_hand {
+            MainHand::Left => 0,
+            MainHand::Right => 1,
+        };
+
+        encoder.append_packet(&SetEntityMetadataC {
+            entity_id: self.0.id as i32,
+            metadata: vec![
+                (PLAYER_SKIN_PARTS_INDEX, Metadata::Byte(skin_parts as i8)),
+                (PLAYER_MAIN_HAND_INDEX, Metadata::Byte(main_hand)),
+            ],
+        })?;
+
+        let equipment = {
+            let inventory = self.0.inventory.read().await;
+            let held = self.0.held_slot.load(Ordering::Relaxed) as i16;
+            [
+                (EquipmentSlot::MainHand, held),
+                (EquipmentSlot::OffHand, 40),
+                (EquipmentSlot::Boots, 36),
+                (EquipmentSlot::Leggings, 37),
+                (EquipmentSlot::Chestplate, 38),
+                (EquipmentSlot::Helmet, 39),
+            ]
+            .into_iter()
+            .map(|(slot, index)| (slot, inventory.get(&index).cloned().unwrap_or_default()))
+            .collect()
+        };
+        encoder.append_packet(&SetEquipmentC {
+            entity_id: self.0.id as i32,
+            equipment,
+        })?;
+
+        encoder.append_packet(&BundleDelimiterC)?;
+
+        Ok(encoder.take())
+    }
+
+    pub async fn position(&self) -> (f64, f64, f64) {
+        self.0.entity.read().await.position()
+    }
+
+    /// Encodes what removes other players' entities and profiles, once they're out of view.
+    pub fn despawn_packets(entity_ids: Vec<i32>, uuids: Vec<Uuid>) -> Result<BytesMut> {
+        let mut encoder = Encoder::new();
+        encoder.append_packet(&RemoveEntitiesC { entity_ids })?;
+        encoder.append_packet(&PlayerInfoRemoveC { players: uuids })?;
+        Ok(encoder.take())
+    }
+
+    /// Queues already encoded packets without waiting for room in the write queue, so a client
+    /// that's behind (e.g. still loading chunks) can't hold up whoever's sending. They still go
+    /// out in the order they were queued.
+    pub fn send_in_background(&self, packets: BytesMut) {
+        let player = self.clone();
+        self.after_background_writes(async move {
+            if let Err(why) = player.0.io.tx_raw(&packets).await {
+                warn!("Failed to send to player {}: {why}", player.id());
+            }
+        });
+    }
+
+    /// Waits until everything sent so far, in the background or not, has been written out.
+    pub async fn flush(&self) -> Result<()> {
+        let (done, written) = oneshot::channel();
+        self.after_background_writes(async move {
+            let _ = done.send(());
+        });
+        let _ = written.await;
+
+        self.0.io.flush().await
+    }
+
+    /// Runs `write` once the background writes queued before it are done.
+    fn after_background_writes(&self, write: impl Future<Output = ()> + Send + 'static) {
+        let mut last = self
+            .0
+            .background_write
+            .lock()
+            .expect("Failed to lock background write mutex");
+        let previous = last.take();
+
+        *last = Some(tokio::spawn(async move {
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            write.await;
+        }));
+    }
+
+    /// Replaces the set of players this player can see, returning the ones that just came into
+    /// view and the ones that just left it.
+    pub async fn update_tracked(&self, visible: HashSet<u16>) -> (HashSet<u16>, HashSet<u16>) {
+        let mut tracked = self.0.tracked.lock().await;
+        let entered = visible.difference(&tracked).copied().collect();
+        let left = tracked.difference(&visible).copied().collect();
+        *tracked = visible;
+        (entered, left)
+    }
+
+    /// Called when the player attacks or right clicks another entity. Right clicking an NPC runs
+    /// its handler, if it has one.
+    pub async fn on_interact_entity(
+        &self,
+        entity_id: i32,
+        interaction: &Interaction,
+        sneaking: bool,
+    ) -> Result<()> {
+        debug!(
+            "Player {} interacted with entity {entity_id}: {interaction:?} (sneaking: {sneaking})",
+            self.id()
+        );
+
+        // the client sends an interact for each hand (plus interact at for some entities), so
+        // only react to the main hand one to avoid running handlers twice
+        if !matches!(interaction, Interaction::Interact { hand: Hand::Main }) {
+            return Ok(());
+        }
+
+        let server = self.0.crawlstate.get_server().await;
+        if let Some(handler) = server.npc_on_interact(entity_id).await {
+            let player = self.clone();
+            tokio::spawn(async move {
+                if let Err(why) = handler.run(player.clone()).await {
+                    warn!(
+                        "NPC {entity_id} handler failed for player {}: {why}",
+                        player.id()
+                    );
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Picks an id for a new window. 0 is the player's inventory, so it's skipped.
+    async fn next_window_id(&self) -> u8 {
+        let mut next_window_id = self.0.next_window_id.lock().await;
+        let id = *next_window_id;
+        *next_window_id = next_window_id.wrapping_add(1);
+        if *next_window_id == 0 {
+            *next_window_id = 1;
+        }
+        id
+    }
+
+    /// Opens a merchant window showing a fixed list of trades.
+    pub async fn open_merchant(&self, trades: Vec<Trade>) -> Result<()> {
+        let id = self.next_window_id().await;
+
+        let window = Window {
+            id,
+            kind: WindowType::Merchant,
+            title: "Shop".into(),
+            // two inputs and the result
+            slots: vec![Slot::default(); 3],
+        };
+
+        self.0.io.tx(&OpenScreenC::from(&window)).await?;
+        self.0
+            .io
+            .tx(&MerchantOffersC {
+                window_id: id as i32,
+                trades,
+                villager_level: 1,
+                experience: 0,
+                is_regular_villager: false,
+                can_restock: false,
+            })
+            .await?;
+
+        {
+            let mut sw = self.0.window.write().await;
+            *sw = Some(window);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces whatever the player is holding on their cursor, e.g. with [`Slot::default`] to
+    /// empty it.
+    pub async fn set_cursor_item(&self, item: Slot) -> Result<()> {
+        self.0.io.tx(&SetContainerSlotC::cursor(item)).await
+    }
+
+    /// Swaps out the contents of the player's open window, e.g. for a loot refresh. Some clients
+    /// don't redraw an open window on a content update, so with `reopen_containers` set the
+    /// window is closed and opened again first.
+    pub async fn refresh_container(&self, contents: Vec<Slot>) -> Result<()> {
+        let mut window = self.0.window.write().await;
+        let Some(window) = window.as_mut() else {
+            return Ok(());
+        };
+
+        window.slots = contents;
+
+        if self.0.crawlstate.reopen_containers {
+            self.0
+                .io
+                .tx(&CloseContainerC {
+                    window_id: window.id,
+                })
+                .await?;
+            self.0.io.tx(&OpenScreenC::from(&*window)).await?;
+        }
+
+        self.0
+            .io
+            .tx(&SetContainerContentC {
+                window_id: window.id,
+                // FIXME: track this correctly
+                state_id: 0,
+                slot_data: window.slots.clone(),
+                carried_item: Slot::default(),
+            })
+            .await
+    }
+
+    /// Puts an item in the player's inventory, e.g. a compass in the hotbar. See
+    /// [`SetContainerSlotC::player_inventory`] for how slots are numbered.
+    pub async fn set_player_inventory_slot(&self, slot: i16, item: Slot) -> Result<()> {
+        {
+            let mut inventory = self.0.inventory.write().await;
+            match item.item_id() {
+                Some(_) => inventory.insert(slot, item.clone()),
+                None => inventory.remove(&slot),
+            };
+        }
+
+        self.0
+            .io
+            .tx(&SetContainerSlotC::player_inventory(slot, item))
+            .await
+    }
+
+    /// Swaps the item in inventory slot `slot` with the one in the player's hand, for a middle
+    /// click on a block whose item is elsewhere in their inventory.
+    async fn pick_item(&self, slot: i32) -> Result<()> {
+        let slot = i16::try_from(slot)
+            .ok()
+            .filter(|s| (0..36).contains(s))
+            .ok_or_else(|| eyre!("can't pick item from inventory slot {slot}"))?;
+        let held = self.0.held_slot.load(Ordering::Relaxed) as i16;
+
+        if slot == held {
+            return Ok(());
+        }
+
+        let (picked, replaced) = {
+            let inventory = self.0.inventory.read().await;
+            let item = |s: i16| inventory.get(&s).cloned().unwrap_or_default();
+            (item(slot), item(held))
+        };
+
+        self.set_player_inventory_slot(held, picked).await?;
+        self.set_player_inventory_slot(slot, replaced).await
+    }
+
+    /// Puts an open window's contents back how they were and empties the cursor, undoing
+    /// whatever the client thinks a click did.
+    async fn resync_window(&self, window_id: u8) -> Result<()> {
+        let window = self.0.window.read().await;
+        let Some(window) = window.as_ref().filter(|w| w.id == window_id) else {
+            return Ok(());
+        };
+
+        self.0
+            .io
+            .tx(&SetContainerContentC {
+                window_id,
+                // FIXME: track this correctly
+                state_id: 0,
+                slot_data: window.slots.clone(),
+                carried_item: Slot::default(),
+            })
+            .await
+    }
+
     async fn handle_use_item(&self, packet: UseItemOnS) -> Result<()> {
         let crawlstate = self.0.crawlstate.clone();
         let server = crawlstate.get_server().await;
@@ -578,23 +2187,16 @@ impl SharedPlayer {
 
         debug!("Player {} clicked at {}, {}, {}", self.id(), x, y, z);
 
-        match server.get_container(x, y, z) {
+        match server.get_container(&self.world().await, x, y, z).await {
             None => (),
             Some(container) => {
-                let id = {
-                    let mut next_window_id = self.0.next_window_id.lock().await;
-                    let id = *next_window_id;
-                    *next_window_id = next_window_id.wrapping_add(1);
-                    if *next_window_id == 0 {
-                        *next_window_id = 1;
-                    }
-                    id
-                };
+                let id = self.next_window_id().await;
 
                 let window = Window {
                     id,
                     kind: WindowType::Generic9x3,
-                    title: "Hi".into(),
+                    title: container.title,
+                    slots: container.slots.clone(),
                 };
 
                 self.0.io.tx(&OpenScreenC::from(&window)).await?;
@@ -605,7 +2207,7 @@ impl SharedPlayer {
                         window_id: id,
                         // FIXME: track this correctly
                         state_id: 0,
-                        slot_data: container.0,
+                        slot_data: container.slots,
                         carried_item: Slot::default(),
                     })
                     .await?;
@@ -632,3 +2234,803 @@ pub enum TeleportError {
     #[error("Waiting for teleport acknowledgement for id {0}")]
     Pending(i32),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::protocol::{Decode, Encode};
+
+    #[tokio::test]
+    async fn respawn_request_teleports_to_spawn() {
+        let state = testing::state(&["-x", "8", "-y", "70", "-z", "24"]);
+        testing::server(&state, Vec::new()).await;
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        player
+            .handle_frame(testing::frame(ClientStatusS::ID, &[0]))
+            .await
+            .unwrap();
+
+        let teleport = client.next_packet(SynchronisePositionC::ID).await;
+        let mut spawn = Vec::new();
+        for coord in [8.0f64, 70.0, 24.0] {
+            coord.encode(&mut spawn).unwrap();
+        }
+        assert_eq!(&teleport.body[..24], &spawn[..]);
+        assert_eq!(player.position().await, (8.0, 70.0, 24.0));
+    }
+
+    /// The inventory slot and item a [`SetContainerSlotC`] for the player's inventory sets.
+    fn inventory_update(frame: &Frame) -> (i16, Option<i32>) {
+        assert_eq!(frame.id, SetContainerSlotC::ID);
+        assert_eq!(frame.body[0] as i8, -2);
+
+        let mut body = &frame.body[1..];
+        VarInt::decode(&mut body).unwrap();
+        let slot = i16::from_be_bytes([body[0], body[1]]);
+        let item = Slot::decode(&mut &body[2..]).unwrap();
+
+        (slot, item.item_id())
+    }
+
+    #[tokio::test]
+    async fn picking_stone_puts_it_in_hand() {
+        let state = testing::state(&[]);
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        let stone = crate::world::Item {
+            slot: 0,
+            id: "minecraft:stone".to_owned(),
+            count: 64,
+        };
+        let stone_id = registries().item.entries["minecraft:stone"].protocol_id;
+
+        player
+            .set_player_inventory_slot(20, stone.into())
+            .await
+            .unwrap();
+        client.next_frame().await;
+
+        player
+            .handle_frame(testing::frame(SetHeldItemS::ID, &[0x00, 0x03]))
+            .await
+            .unwrap();
+        player
+            .handle_frame(testing::frame(PickItemS::ID, &[20]))
+            .await
+            .unwrap();
+
+        let held = inventory_update(&client.next_frame().await);
+        let picked_from = inventory_update(&client.next_frame().await);
+        assert_eq!(held, (3, Some(stone_id)));
+        assert_eq!(picked_from, (20, None));
+    }
+
+    #[tokio::test]
+    async fn picking_outside_the_inventory_fails() {
+        let state = testing::state(&[]);
+        let (player, _client) = testing::player(&state, 0, "alice").await;
+
+        let mut body = Vec::new();
+        VarInt(36).encode(&mut body).unwrap();
+        assert!(player
+            .handle_frame(testing::frame(PickItemS::ID, &body))
+            .await
+            .is_err());
+        assert!(player
+            .handle_frame(testing::frame(SetHeldItemS::ID, &[0x00, 0x09]))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn finished_connection_is_logged_once() {
+        let path = std::env::temp_dir().join(format!(
+            "crawlspace-connections-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let state = testing::state(&["--connection-log", path.to_str().unwrap()]);
+        let (player, _client) = testing::player(&state, 0, "alice").await;
+        *player.0.protocol_version.write().await = Some(state.version_number);
+
+        player.log_disconnect("Left the game").await;
+        player.log_disconnect("Connection closed").await;
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(lines.len(), 1);
+
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert!(record["timestamp"].as_u64().unwrap() > 0);
+        assert!(record["peer_addr"]
+            .as_str()
+            .unwrap()
+            .starts_with("127.0.0.1:"));
+        assert_eq!(record["username"], "alice");
+        assert_eq!(record["uuid"], Uuid::from_u128(1).to_string());
+        assert_eq!(record["protocol_version"], state.version_number);
+        assert_eq!(record["reached_play"], true);
+        assert_eq!(record["disconnect_reason"], "Left the game");
+        assert!(record["duration_secs"].as_f64().unwrap() >= 0.0);
+    }
+
+    #[tokio::test]
+    async fn pong_records_latency() {
+        let state = testing::state(&[]);
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+
+        // a pong for some other ping doesn't count
+        player.probe_latency().await.unwrap();
+        player.0.io.flush().await.unwrap();
+        let ping = client.next_packet(PingC::ID).await;
+        let other = (i32::from_be_bytes(ping.body[..].try_into().unwrap()) ^ 1).to_be_bytes();
+        player
+            .handle_frame(testing::frame(PongS::ID, &other))
+            .await
+            .unwrap();
+        assert_eq!(player.latency().await, None);
+
+        player.probe_latency().await.unwrap();
+        player.0.io.flush().await.unwrap();
+        let ping = client.next_packet(PingC::ID).await;
+        player
+            .handle_frame(testing::frame(PongS::ID, &ping.body))
+            .await
+            .unwrap();
+        assert!(player.latency().await.is_some());
+    }
+
+    /// Counts how many writes it's given, and accepts all of each.
+    #[derive(Clone, Default)]
+    struct CountingWriter(Arc<AtomicUsize>);
+
+    impl tokio::io::AsyncWrite for CountingWriter {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn batched_messages_are_one_write() {
+        let state = testing::state(&[]);
+        let writer = CountingWriter::default();
+        let player = testing::player_writing_to(&state, 0, "alice", writer.clone()).await;
+
+        let messages = (0..10)
+            .map(|i| TextComponent::from(format!("line {i}")))
+            .collect::<Vec<_>>();
+        player.send_messages(&messages).await.unwrap();
+        player.0.io.flush().await.unwrap();
+
+        assert_eq!(writer.0.load(Ordering::SeqCst), 1);
+    }
+
+    /// A player with a chest open.
+    async fn player_with_chest_open(state: &CrawlState) -> (SharedPlayer, testing::Client) {
+        let (player, mut client) = testing::player(state, 0, "alice").await;
+        *player.0.window.write().await = Some(Window {
+            id: 1,
+            kind: WindowType::Generic9x3,
+            title: "Chest".into(),
+            slots: vec![Slot::default(); 27],
+        });
+        client.drain(&player).await;
+
+        (player, client)
+    }
+
+    #[tokio::test]
+    async fn refreshing_only_updates_contents() {
+        let state = testing::state(&[]);
+        let (player, mut client) = player_with_chest_open(&state).await;
+
+        player
+            .refresh_container(vec![Slot::default(); 27])
+            .await
+            .unwrap();
+        assert_eq!(client.drain(&player).await, [SetContainerContentC::ID]);
+    }
+
+    #[tokio::test]
+    async fn refreshing_can_reopen_instead() {
+        let state = testing::state(&["--reopen-containers"]);
+        let (player, mut client) = player_with_chest_open(&state).await;
+
+        player
+            .refresh_container(vec![Slot::default(); 27])
+            .await
+            .unwrap();
+        assert_eq!(
+            client.drain(&player).await,
+            [
+                CloseContainerC::ID,
+                OpenScreenC::ID,
+                SetContainerContentC::ID
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn illegal_transitions_leave_the_state_alone() {
+        let state = testing::state(&[]);
+        let (player, _client) = testing::player(&state, 0, "alice").await;
+
+        let err = player
+            .transition_to(PacketState::Handshaking)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<PacketStateTransitionError>().is_some());
+        assert!(matches!(
+            *player.0.packet_state.read().await,
+            PacketState::Play
+        ));
+    }
+
+    #[tokio::test]
+    async fn joining_gets_the_live_border() {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+        server.set_border(16.0, -32.0, 48.0).await.unwrap();
+
+        let border = *state.border.read().await;
+        assert_eq!(
+            (border.center_x, border.center_z, border.diameter),
+            (16.0, -32.0, 48.0)
+        );
+
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        player.send_border().await.unwrap();
+
+        let center = client.next_packet(SetBorderCenterC::ID).await;
+        let mut expected = Vec::new();
+        16.0f64.encode(&mut expected).unwrap();
+        (-32.0f64).encode(&mut expected).unwrap();
+        assert_eq!(&center.body[..], &expected[..]);
+
+        let size = client.next_packet(SetBorderSizeC::ID).await;
+        assert_eq!(&size.body[..], &48.0f64.to_be_bytes()[..]);
+    }
+
+    #[tokio::test]
+    async fn reconfiguring_gets_back_to_play() {
+        let state = testing::state(&[]);
+        testing::server(&state, Vec::new()).await;
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+
+        player.reconfigure().await.unwrap();
+        client.next_packet(StartConfigurationC::ID).await;
+        player
+            .handle_frame(testing::frame(ConfigurationAckS::ID, &[]))
+            .await
+            .unwrap();
+        assert!(matches!(
+            *player.0.packet_state.read().await,
+            PacketState::Configuration
+        ));
+
+        client.next_packet(KnownPacksC::ID).await;
+        player
+            .handle_frame(testing::frame(KnownPacksS::ID, &[0]))
+            .await
+            .unwrap();
+        client.next_packet(UpdateTagsConfigC::ID).await;
+        client.next_packet(FinishConfigurationC::ID).await;
+        player
+            .handle_frame(testing::frame(FinishConfigurationAckS::ID, &[]))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            *player.0.packet_state.read().await,
+            PacketState::Play
+        ));
+        assert!(player.take_rejoined());
+        client.next_packet(LoginPlayC::ID).await;
+    }
+
+    #[tokio::test]
+    async fn resent_tags_match_the_login_tags() {
+        use crate::protocol::{packets::play::UpdateTagsC, Decoder};
+
+        let state = testing::state(&[]);
+        let writer = testing::RecordingWriter::default();
+        let player = testing::player_writing_to(&state, 0, "alice", writer.clone()).await;
+
+        player.send_tags().await.unwrap();
+        player.0.io.flush().await.unwrap();
+
+        let mut decoder = Decoder::new();
+        decoder.add_bytes(BytesMut::from(&state.tag_cache.encoded[..]));
+        let login = decoder.try_read_next().unwrap().unwrap();
+        assert_eq!(login.id, UpdateTagsConfigC::ID);
+
+        let frames = writer.frames();
+        let [resent] = &frames[..] else {
+            panic!("expected one packet, got {}", frames.len());
+        };
+        assert_eq!(resent.id, UpdateTagsC::ID);
+        // configuration and play only differ in the packet id
+        assert_eq!(resent.body, login.body);
+    }
+
+    #[tokio::test]
+    async fn chunks_stream_nearest_first() {
+        use crate::{protocol::packets::play::ChunkDataUpdateLightC, world::fixtures};
+
+        let state = testing::state(&[]);
+        let chunks = (-3..=3)
+            .flat_map(|x| (-3..=3).map(move |z| (x, z)))
+            .map(|(x, z)| fixtures::chunk(x, z, vec![fixtures::air_section(0)]))
+            .collect();
+        let world = testing::world(&state, chunks);
+
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        testing::move_to(&player, 8.0, 100.0, 8.0).await;
+        player
+            .update_settings(ClientInformationS {
+                locale: Bounded("en_us"),
+                view_distance: 2,
+                chat_mode: ChatMode::Enabled,
+                chat_colors: true,
+                displayed_skin_parts: 0,
+                main_hand: MainHand::Right,
+                enable_text_filtering: false,
+                allow_server_listings: true,
+            })
+            .await;
+
+        player.stream_chunks(&world).await.unwrap();
+        player.0.io.flush().await.unwrap();
+
+        let mut sent = Vec::new();
+        for _ in 0..25 {
+            let chunk = client.next_packet(ChunkDataUpdateLightC::ID).await;
+            let x = i32::from_be_bytes(chunk.body[..4].try_into().unwrap());
+            let z = i32::from_be_bytes(chunk.body[4..8].try_into().unwrap());
+            sent.push((x, z));
+        }
+        assert!(client
+            .try_next_frame(Duration::from_millis(100))
+            .await
+            .is_none_or(|f| f.id != ChunkDataUpdateLightC::ID));
+
+        // the center, then the ring around it (sides before corners), then the outer ring
+        assert_eq!(sent[0], (0, 0));
+        let order = sent
+            .iter()
+            .map(|(x, z)| (x.abs().max(z.abs()), x * x + z * z))
+            .collect::<Vec<_>>();
+        assert!(order.is_sorted());
+        assert_eq!(order.iter().filter(|(ring, _)| *ring == 1).count(), 8);
+        assert_eq!(order.iter().filter(|(ring, _)| *ring == 2).count(), 16);
+    }
+
+    /// Runs [`SharedPlayer::begin_play`] in the background, as if the player just logged in.
+    async fn spawn_begin_play(player: &SharedPlayer) -> tokio::task::JoinHandle<Result<()>> {
+        *player.0.packet_state.write().await = PacketState::Login;
+
+        let player = player.clone();
+        tokio::spawn(async move { player.begin_play().await })
+    }
+
+    fn pack_response(pack: &Frame, result: u8) -> Vec<u8> {
+        let mut response = pack.body[..16].to_vec();
+        response.push(result);
+        response
+    }
+
+    #[tokio::test]
+    async fn forced_pack_holds_back_spawning() {
+        let state = testing::state(&[
+            "--resource-pack",
+            "https://example.com/pack.zip",
+            "--resource-pack-forced",
+        ]);
+        testing::server(&state, Vec::new()).await;
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        let playing = spawn_begin_play(&player).await;
+
+        let pack = client.next_packet(AddResourcePackC::ID).await;
+        while let Some(frame) = client.try_next_frame(Duration::from_millis(200)).await {
+            assert_ne!(frame.id, SynchronisePositionC::ID);
+            assert_ne!(frame.id, SetCenterChunkC::ID);
+        }
+
+        // accepted, but still downloading
+        client
+            .send(ResourcePackResponseS::ID, &pack_response(&pack, 3))
+            .await;
+        assert!(client
+            .try_next_frame(Duration::from_millis(200))
+            .await
+            .is_none());
+
+        client
+            .send(ResourcePackResponseS::ID, &pack_response(&pack, 0))
+            .await;
+        client.next_packet(SynchronisePositionC::ID).await;
+        client.next_packet(SetCenterChunkC::ID).await;
+        playing.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn racing_logins_cant_overfill_the_server() {
+        let state = testing::state(&["--max-players", "1"]);
+        let (alice, _alice_client) = testing::player(&state, 0, "alice").await;
+        let (bob, _bob_client) = testing::player(&state, 1, "bob").await;
+
+        let (a, b) = tokio::join!(
+            tokio::spawn({
+                let alice = alice.clone();
+                async move { alice.reserve_slot() }
+            }),
+            tokio::spawn({
+                let bob = bob.clone();
+                async move { bob.reserve_slot() }
+            }),
+        );
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert!(a != b, "exactly one login should get the last slot");
+        assert_eq!(state.current_players.load(Ordering::SeqCst), 1);
+
+        // releasing twice only frees the one slot
+        let (winner, loser) = if a { (&alice, &bob) } else { (&bob, &alice) };
+        winner.release_slot();
+        winner.release_slot();
+        loser.release_slot();
+        assert_eq!(state.current_players.load(Ordering::SeqCst), 0);
+        assert!(loser.reserve_slot());
+    }
+
+    #[tokio::test]
+    async fn keepalives_time_out_after_two_missed_windows() {
+        let state = testing::state(&[]);
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        let ago = |elapsed: Duration| Instant::now().checked_sub(elapsed).unwrap();
+
+        // nothing's due until a window has passed
+        *player.0.last_keepalive.write().await = ago(KEEPALIVE_INTERVAL - Duration::from_secs(1));
+        player.keepalive().await.unwrap();
+        assert!(player.0.pending_keepalive.lock().await.is_none());
+
+        *player.0.last_keepalive.write().await = ago(KEEPALIVE_INTERVAL);
+        player.keepalive().await.unwrap();
+        client.next_packet(KeepAliveC::ID).await;
+
+        let set_sent = |sent: Instant| {
+            let player = player.clone();
+            async move {
+                let mut pending = player.0.pending_keepalive.lock().await;
+                let (id, _) = pending.expect("a keepalive should be pending");
+                *pending = Some((id, sent));
+            }
+        };
+
+        // one window missed, and most of the next
+        set_sent(ago(2 * KEEPALIVE_INTERVAL - Duration::from_secs(1))).await;
+        assert!(!player.keepalive_timed_out().await);
+
+        set_sent(ago(2 * KEEPALIVE_INTERVAL + Duration::from_secs(1))).await;
+        assert!(player.keepalive_timed_out().await);
+    }
+
+    #[tokio::test]
+    async fn keepalives_are_answered_while_a_pack_loads() {
+        let state = testing::state(&[
+            "--resource-pack",
+            "https://example.com/pack.zip",
+            "--resource-pack-forced",
+        ]);
+        testing::server(&state, Vec::new()).await;
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        let playing = spawn_begin_play(&player).await;
+
+        let pack = client.next_packet(AddResourcePackC::ID).await;
+
+        // the client goes quiet long enough for the server to ping it
+        let keepalive = loop {
+            let frame = client
+                .try_next_frame(Duration::from_secs(10))
+                .await
+                .expect("the server should ping while waiting");
+            if frame.id == KeepAliveC::ID {
+                break frame;
+            }
+        };
+        client.send(KeepAliveS::ID, &keepalive.body).await;
+        client
+            .send(ResourcePackResponseS::ID, &pack_response(&pack, 0))
+            .await;
+        playing.await.unwrap().unwrap();
+
+        assert!(!player.keepalive_timed_out().await);
+        assert!(player.0.pending_keepalive.lock().await.is_none());
+        assert!(player.latency().await.is_some());
+    }
+
+    fn handshake_body(next_state: i32) -> Vec<u8> {
+        let mut body = Vec::new();
+        VarInt(767).encode(&mut body).unwrap();
+        Bounded::<&str, 255>("localhost").encode(&mut body).unwrap();
+        body.extend(25565u16.to_be_bytes());
+        VarInt(next_state).encode(&mut body).unwrap();
+        body
+    }
+
+    #[tokio::test]
+    async fn unsupported_handshake_states_error_out() {
+        let state = testing::state(&[]);
+
+        // 3 is a transfer, which isn't supported yet. 7 isn't a state at all
+        for next_state in [3, 7] {
+            let (player, mut client) = testing::player(&state, 0, "alice").await;
+            *player.0.packet_state.write().await = PacketState::Handshaking;
+
+            client
+                .send(HandshakeS::ID, &handshake_body(next_state))
+                .await;
+            assert!(player.handshake().await.is_err());
+            assert!(matches!(
+                *player.0.packet_state.read().await,
+                PacketState::Handshaking
+            ));
+        }
+    }
+}
+
+/// A player connected to a fake client, for tests that need one.
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::Arc;
+
+    use bytes::BytesMut;
+    use clap::Parser;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::Semaphore,
+    };
+
+    use super::*;
+    use crate::{
+        args::Args,
+        protocol::{Decoder, Encode},
+        server::Server,
+        state::State,
+        world,
+    };
+
+    /// Builds state from command line arguments, after a world that's never loaded.
+    pub fn state(args: &[&str]) -> CrawlState {
+        state_in("test", args)
+    }
+
+    /// Builds state from command line arguments, with `map_dir` as the first world.
+    pub fn state_in(map_dir: &str, args: &[&str]) -> CrawlState {
+        let mut argv = vec!["crawlspace", map_dir];
+        argv.extend_from_slice(args);
+
+        let args = Args::try_parse_from(argv).expect("test args should parse");
+        Arc::new(
+            State::new(crate::VERSION, crate::VERSION_NUM, args).expect("test state should build"),
+        )
+    }
+
+    /// Creates a server for `state` with `worlds` loaded. It isn't ticked unless a test does it.
+    pub async fn server(state: &CrawlState, worlds: Vec<(String, WorldCache)>) -> Arc<Server> {
+        let server = Server::new(state.clone(), worlds, crate::TICK_RATE);
+        state.set_server(server.clone()).await;
+        server
+    }
+
+    /// Builds a world's cache out of fixture chunks.
+    pub fn world(state: &CrawlState, chunks: Vec<world::Chunk>) -> WorldCache {
+        let chunks = chunks.into_iter().map(|c| ((c.x_pos, c.z_pos), c));
+        WorldCache::from_anvil(state.clone(), &world::World(chunks.collect()))
+    }
+
+    /// Connects a player and puts them straight into play, as if they'd logged in as
+    /// `username`. The urgent lane stays closed, so packets arrive in the order they're sent.
+    pub async fn player(state: &CrawlState, id: u16, username: &str) -> (SharedPlayer, Client) {
+        let (stream, accepted) = connect().await;
+
+        let permit = Arc::new(Semaphore::new(1)).acquire_owned().await.unwrap();
+        let player = SharedPlayer::new(state.clone(), permit, id, accepted);
+        join_play(&player, username).await;
+
+        let client = Client {
+            stream,
+            decoder: Decoder::new(),
+        };
+
+        (player, client)
+    }
+
+    /// Like [`player`], but everything sent to the player goes to `writer` instead of a client.
+    pub async fn player_writing_to(
+        state: &CrawlState,
+        id: u16,
+        username: &str,
+        writer: impl AsyncWrite + Unpin + Send + 'static,
+    ) -> SharedPlayer {
+        let (_stream, accepted) = connect().await;
+        let peer_addr = accepted.peer_addr().unwrap().to_string();
+        let (read_half, _) = accepted.into_split();
+
+        let permit = Arc::new(Semaphore::new(1)).acquire_owned().await.unwrap();
+        let io = NetIo::from_parts(peer_addr, read_half, writer);
+        let player = SharedPlayer::with_io(state.clone(), permit, id, io);
+        join_play(&player, username).await;
+
+        player
+    }
+
+    /// A loopback connection, as the client and server ends.
+    async fn connect() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stream, accepted) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        (stream.unwrap(), accepted.unwrap().0)
+    }
+
+    async fn join_play(player: &SharedPlayer, username: &str) {
+        *player.0.packet_state.write().await = PacketState::Play;
+        *player.0.uuid.write().await = Some(Uuid::from_u128(player.id() as u128 + 1));
+        *player.0.username.write().await = Some(username.to_owned());
+    }
+
+    /// Moves a player's entity, as if they'd walked there.
+    pub async fn move_to(player: &SharedPlayer, x: f64, y: f64, z: f64) {
+        player.0.entity.write().await.reposition(x, y, z);
+    }
+
+    /// Makes it look like the player hasn't done anything for `idle`.
+    pub async fn idle_for(player: &SharedPlayer, idle: Duration) {
+        *player.0.last_active.write().await = Instant::now().checked_sub(idle).unwrap();
+    }
+
+    /// Queues a serverbound packet for the next [`SharedPlayer::handle_all_packets`], as if the
+    /// read loop had just received it.
+    #[cfg(feature = "timings")]
+    pub async fn queue(player: &SharedPlayer, frame: Frame) {
+        player.0.frame_queue.lock().await.push(frame);
+    }
+
+    /// A serverbound packet, as the read loop would hand it to [`SharedPlayer::handle_frame`].
+    pub fn frame(id: i32, body: &[u8]) -> Frame {
+        Frame {
+            id,
+            body: BytesMut::from(body),
+        }
+    }
+
+    /// Keeps everything written to it, for [`player_writing_to`], so tests can see exactly what
+    /// went out. It only takes a few bytes per write, so interleaved writes would show.
+    #[derive(Clone, Default)]
+    pub struct RecordingWriter(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl RecordingWriter {
+        /// Everything written so far, as frames.
+        pub fn frames(&self) -> Vec<Frame> {
+            let mut decoder = Decoder::new();
+            decoder.add_bytes(BytesMut::from(&self.0.lock().unwrap()[..]));
+
+            let mut frames = Vec::new();
+            while let Some(frame) = decoder.try_read_next().unwrap() {
+                frames.push(frame);
+            }
+            frames
+        }
+    }
+
+    impl AsyncWrite for RecordingWriter {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let n = buf.len().min(3);
+            self.0.lock().unwrap().extend_from_slice(&buf[..n]);
+            std::task::Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    /// The client end of a test player's connection.
+    pub struct Client {
+        stream: TcpStream,
+        decoder: Decoder,
+    }
+
+    impl Client {
+        /// Sends an uncompressed packet to the server, as a real client would.
+        pub async fn send(&mut self, id: i32, body: &[u8]) {
+            let mut packet = Vec::new();
+            VarInt(id).encode(&mut packet).unwrap();
+            packet.extend_from_slice(body);
+
+            let mut buf = Vec::new();
+            VarInt(packet.len() as i32).encode(&mut buf).unwrap();
+            buf.extend(packet);
+            self.stream.write_all(&buf).await.unwrap();
+        }
+
+        /// Waits for the next packet the server sent.
+        pub async fn next_frame(&mut self) -> Frame {
+            self.try_next_frame(Duration::from_secs(5))
+                .await
+                .expect("timed out waiting for a packet")
+        }
+
+        /// Waits up to `wait` for the next packet the server sent.
+        pub async fn try_next_frame(&mut self, wait: Duration) -> Option<Frame> {
+            loop {
+                if let Some(frame) = self.decoder.try_read_next().unwrap() {
+                    return Some(frame);
+                }
+
+                let mut buf = BytesMut::with_capacity(4096);
+                let read = timeout(wait, self.stream.read_buf(&mut buf)).await.ok()?;
+                assert_ne!(read.unwrap(), 0, "server closed the connection");
+                self.decoder.add_bytes(buf);
+            }
+        }
+
+        /// Skips packets until one with `id` comes along.
+        pub async fn next_packet(&mut self, id: i32) -> Frame {
+            loop {
+                let frame = self.next_frame().await;
+                if frame.id == id {
+                    return frame;
+                }
+            }
+        }
+
+        /// The ids of every packet the server sent until `player`'s queue was written out.
+        pub async fn drain(&mut self, player: &SharedPlayer) -> Vec<i32> {
+            player.flush().await.unwrap();
+
+            let mut ids = Vec::new();
+            while let Some(frame) = self.try_next_frame(Duration::from_millis(100)).await {
+                ids.push(frame.id);
+            }
+            ids
+        }
+    }
+}
@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use color_eyre::eyre::Result;
+
+use crate::protocol::{Decode, Encode};
+
+/// A rotation in degrees, sent as a single byte of 1/256ths of a full turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Angle(pub f32);
+
+impl Encode for Angle {
+    fn encode(&self, w: impl std::io::Write) -> Result<()> {
+        // going through i32 wraps negative and past-360 angles around, where casting straight to
+        // u8 would clamp them
+        ((self.0 / 360.0 * 256.0) as i32 as u8).encode(w)
+    }
+}
+
+impl<'a> Decode<'a> for Angle {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(Self(u8::decode(r)? as f32 / 256.0 * 360.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn byte(degrees: f32) -> u8 {
+        let mut buf = Vec::new();
+        Angle(degrees).encode(&mut buf).unwrap();
+        buf[0]
+    }
+
+    #[test]
+    fn angles_wrap_at_a_full_turn() {
+        assert_eq!(byte(0.0), 0);
+        assert_eq!(byte(90.0), 64);
+        assert_eq!(byte(359.0), 255);
+        assert_eq!(byte(360.0), 0);
+        assert_eq!(byte(450.0), 64);
+    }
+
+    #[test]
+    fn negative_angles_count_back_from_a_full_turn() {
+        assert_eq!(byte(-90.0), 192);
+        assert_eq!(byte(-360.0), 0);
+        // rounds toward zero, so a hair under 0 is still 0
+        assert_eq!(byte(-1.0), 0);
+    }
+
+    #[test]
+    fn angles_decode_to_degrees() {
+        assert_eq!(Angle::decode(&mut &[192][..]).unwrap(), Angle(270.0));
+        assert_eq!(Angle::decode(&mut &[0][..]).unwrap(), Angle(0.0));
+    }
+}
@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+/// Number of fixed-point units per block in relative move packets. Each axis is sent as a short,
+/// so a single relative move can only carry an entity ~8 blocks.
+pub const DELTA_SCALE: f64 = 4096.0;
+
+/// Computes the relative move delta for one axis, or `None` if the move is too large to fit in a
+/// short (more than ~8 blocks) and the entity needs a full teleport instead.
+pub fn delta(prev: f64, curr: f64) -> Option<i16> {
+    // rounding both ends (like vanilla) rather than truncating keeps repeated small moves from
+    // drifting away from the real position
+    let delta = (curr * DELTA_SCALE).round() as i64 - (prev * DELTA_SCALE).round() as i64;
+    i16::try_from(delta).ok()
+}
+
+/// Computes relative move deltas for all three axes. Returns `None` if any axis moved too far.
+pub fn position_delta(prev: (f64, f64, f64), curr: (f64, f64, f64)) -> Option<(i16, i16, i16)> {
+    Some((
+        delta(prev.0, curr.0)?,
+        delta(prev.1, curr.1)?,
+        delta(prev.2, curr.2)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT: f64 = 1.0 / DELTA_SCALE;
+
+    #[test]
+    fn delta_is_in_4096ths_of_a_block() {
+        assert_eq!(delta(0.0, 1.0), Some(4096));
+        assert_eq!(delta(10.5, 10.0), Some(-2048));
+        assert_eq!(delta(-3.25, -3.0), Some(1024));
+    }
+
+    #[test]
+    fn delta_rounds_instead_of_truncating() {
+        assert_eq!(delta(0.0, 0.8 * UNIT), Some(1));
+        assert_eq!(delta(0.0, -0.8 * UNIT), Some(-1));
+        assert_eq!(delta(0.0, 0.2 * UNIT), Some(0));
+    }
+
+    #[test]
+    fn small_moves_dont_drift() {
+        let (mut sent, mut pos) = (0i64, 0.0);
+        for _ in 0..1000 {
+            let next = pos + 0.7 * UNIT;
+            sent += delta(pos, next).unwrap() as i64;
+            pos = next;
+        }
+
+        assert_eq!(sent, (pos * DELTA_SCALE).round() as i64);
+    }
+
+    #[test]
+    fn delta_clamps_at_eight_blocks() {
+        // a short only goes up to 32767, so exactly 8 blocks forward is already too far
+        assert_eq!(delta(0.0, 8.0), None);
+        assert_eq!(delta(0.0, 8.0 - UNIT), Some(i16::MAX));
+        assert_eq!(delta(0.0, 8.0 + UNIT), None);
+
+        assert_eq!(delta(0.0, -8.0), Some(i16::MIN));
+        assert_eq!(delta(0.0, -8.0 - UNIT), None);
+    }
+
+    #[test]
+    fn delta_clamps_for_negative_positions() {
+        assert_eq!(delta(-100.0, -108.0), Some(i16::MIN));
+        assert_eq!(delta(-100.0, -108.0 - UNIT), None);
+        assert_eq!(delta(-100.0, -92.0 - UNIT), Some(i16::MAX));
+        assert_eq!(delta(-100.0, -92.0), None);
+    }
+
+    #[test]
+    fn position_delta_needs_every_axis_to_fit() {
+        assert_eq!(
+            position_delta((0.0, 64.0, 0.0), (1.0, 63.0, 0.5)),
+            Some((4096, -4096, 2048))
+        );
+        assert_eq!(position_delta((0.0, 64.0, 0.0), (1.0, 64.0, 9.0)), None);
+    }
+}
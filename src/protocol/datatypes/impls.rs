@@ -52,6 +52,12 @@ impl Encode for i8 {
     }
 }
 
+impl<'a> Decode<'a> for u8 {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(r.read_u8()?)
+    }
+}
+
 impl Encode for u8 {
     fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
         Ok(w.write_u8(*self)?)
@@ -64,6 +70,12 @@ impl Encode for i16 {
     }
 }
 
+impl<'a> Decode<'a> for i32 {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(r.read_i32::<BigEndian>()?)
+    }
+}
+
 impl Encode for i32 {
     fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
         Ok(w.write_i32::<BigEndian>(*self)?)
@@ -130,7 +142,35 @@ where
     }
 }
 
-impl<T> Encode for Vec<T>
+/// An optional value with a boolean in front saying whether it's there, which is how most of the
+/// protocol sends them. Plain [`Option`] writes nothing for `None`, for packets that flag it
+/// somewhere else.
+#[derive(Debug, Clone)]
+pub struct OptionalPrefixed<T>(pub Option<T>);
+
+impl<T> Encode for OptionalPrefixed<T>
+where
+    T: Encode,
+{
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        self.0.is_some().encode(&mut w)?;
+        self.0.encode(&mut w)
+    }
+}
+
+impl<'a, T> Decode<'a> for OptionalPrefixed<T>
+where
+    T: Decode<'a>,
+{
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(Self(match bool::decode(r)? {
+            true => Some(T::decode(r)?),
+            false => None,
+        }))
+    }
+}
+
+impl<T> Encode for [T]
 where
     T: Encode,
 {
@@ -143,6 +183,24 @@ where
     }
 }
 
+impl<T> Encode for &[T]
+where
+    T: Encode,
+{
+    fn encode(&self, w: impl std::io::Write) -> Result<()> {
+        (**self).encode(w)
+    }
+}
+
+impl<T> Encode for Vec<T>
+where
+    T: Encode,
+{
+    fn encode(&self, w: impl std::io::Write) -> Result<()> {
+        self.as_slice().encode(w)
+    }
+}
+
 impl<'a, T> DecodeSized<'a> for Vec<T>
 where
     T: Decode<'a>,
@@ -172,3 +230,37 @@ impl<'a> Encode for Bytes<'a> {
         Ok(w.write_all(self.0)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(value: impl Encode) -> Vec<u8> {
+        let mut buf = Vec::new();
+        value.encode(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn slices_encode_like_vecs() {
+        let items: [i16; 3] = [1, -2, 0x0304];
+        let expected = [0x00, 0x01, 0xFF, 0xFE, 0x03, 0x04];
+
+        assert_eq!(encode(&items[..]), expected);
+        assert_eq!(encode(items.to_vec()), expected);
+        assert!(encode(&[] as &[i16]).is_empty());
+    }
+
+    #[test]
+    fn optional_prefixed_round_trips() {
+        let some = encode(OptionalPrefixed(Some(0x01020304i32)));
+        assert_eq!(some, [1, 1, 2, 3, 4]);
+        let decoded = OptionalPrefixed::<i32>::decode(&mut &some[..]).unwrap();
+        assert_eq!(decoded.0, Some(0x01020304));
+
+        let none = encode(OptionalPrefixed::<i32>(None));
+        assert_eq!(none, [0]);
+        let decoded = OptionalPrefixed::<i32>::decode(&mut &none[..]).unwrap();
+        assert_eq!(decoded.0, None);
+    }
+}
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use color_eyre::eyre::Result;
+
+use crate::protocol::Encode;
+
+use super::{Slot, TextComponent, VarInt};
+
+/// A single entity metadata value. Each is sent tagged with its type, so the variant decides
+/// what the client expects at a given index.
+#[derive(Debug, Clone)]
+pub enum Metadata {
+    Byte(i8),
+    VarInt(i32),
+    Float(f32),
+    TextComponent(TextComponent),
+    Slot(Slot),
+    Boolean(bool),
+    Vector3(f32, f32, f32),
+    Quaternion(f32, f32, f32, f32),
+}
+
+impl Metadata {
+    fn type_id(&self) -> i32 {
+        match self {
+            Metadata::Byte(_) => 0,
+            Metadata::VarInt(_) => 1,
+            Metadata::Float(_) => 3,
+            Metadata::TextComponent(_) => 5,
+            Metadata::Slot(_) => 7,
+            Metadata::Boolean(_) => 8,
+            Metadata::Vector3(..) => 29,
+            Metadata::Quaternion(..) => 30,
+        }
+    }
+}
+
+impl Encode for Metadata {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        VarInt(self.type_id()).encode(&mut w)?;
+
+        match self {
+            Metadata::Byte(v) => v.encode(&mut w),
+            Metadata::VarInt(v) => VarInt(*v).encode(&mut w),
+            Metadata::Float(v) => v.encode(&mut w),
+            Metadata::TextComponent(v) => {
+                fastnbt::to_bytes_with_opts(v, fastnbt::SerOpts::network_nbt())?.encode(&mut w)
+            }
+            Metadata::Slot(v) => v.encode(&mut w),
+            Metadata::Boolean(v) => v.encode(&mut w),
+            Metadata::Vector3(x, y, z) => {
+                x.encode(&mut w)?;
+                y.encode(&mut w)?;
+                z.encode(&mut w)
+            }
+            Metadata::Quaternion(x, y, z, v) => {
+                x.encode(&mut w)?;
+                y.encode(&mut w)?;
+                z.encode(&mut w)?;
+                v.encode(&mut w)
+            }
+        }
+    }
+}
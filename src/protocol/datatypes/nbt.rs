@@ -0,0 +1,183 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use byteorder::{BigEndian, ReadBytesExt};
+use color_eyre::eyre::{bail, ensure, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::protocol::{Decode, Encode};
+
+/// Anything serde can handle, sent as network NBT: a compound with no root name.
+#[derive(Debug, Clone)]
+pub struct Nbt<T>(pub T);
+
+impl<T> Encode for Nbt<T>
+where
+    T: Serialize,
+{
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        let bytes = fastnbt::to_bytes_with_opts(&self.0, fastnbt::SerOpts::network_nbt())?;
+        Ok(w.write_all(&bytes)?)
+    }
+}
+
+impl<'a, T> Decode<'a> for Nbt<T>
+where
+    T: DeserializeOwned,
+{
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        // fastnbt doesn't say how much it read, so find where the NBT ends first
+        let len = network_nbt_len(r).context("Failed to find the end of NBT")?;
+        let (nbt, rest) = r.split_at(len);
+        *r = rest;
+
+        Ok(Self(fastnbt::from_bytes_with_opts(
+            nbt,
+            fastnbt::DeOpts::network_nbt(),
+        )?))
+    }
+}
+
+const TAG_END: u8 = 0;
+const TAG_COMPOUND: u8 = 10;
+
+/// How many bytes the network NBT at the start of `buf` takes up.
+fn network_nbt_len(buf: &[u8]) -> Result<usize> {
+    let mut r = buf;
+    match r.read_u8()? {
+        TAG_END => (),
+        tag => skip_payload(&mut r, tag, 0)?,
+    }
+
+    Ok(buf.len() - r.len())
+}
+
+/// Nesting deeper than this is rejected, same as vanilla.
+const MAX_DEPTH: usize = 512;
+
+fn skip_payload(r: &mut &[u8], tag: u8, depth: usize) -> Result<()> {
+    ensure!(
+        depth <= MAX_DEPTH,
+        "NBT is nested more than {MAX_DEPTH} deep"
+    );
+
+    match tag {
+        1 => skip(r, 1)?,
+        2 => skip(r, 2)?,
+        3 | 5 => skip(r, 4)?,
+        4 | 6 => skip(r, 8)?,
+        7 => skip_array(r, 1)?,
+        8 => {
+            let len = r.read_u16::<BigEndian>()? as usize;
+            skip(r, len)?;
+        }
+        9 => {
+            let element = r.read_u8()?;
+            let len = r.read_i32::<BigEndian>()?;
+            ensure!(len >= 0, "NBT list has negative length {len}");
+
+            for _ in 0..len {
+                skip_payload(r, element, depth + 1)?;
+            }
+        }
+        TAG_COMPOUND => loop {
+            let tag = r.read_u8()?;
+            if tag == TAG_END {
+                break;
+            }
+
+            let name_len = r.read_u16::<BigEndian>()? as usize;
+            skip(r, name_len)?;
+            skip_payload(r, tag, depth + 1)?;
+        },
+        11 => skip_array(r, 4)?,
+        12 => skip_array(r, 8)?,
+        tag => bail!("Unknown NBT tag {tag}"),
+    }
+
+    Ok(())
+}
+
+fn skip_array(r: &mut &[u8], element_size: usize) -> Result<()> {
+    let len = r.read_i32::<BigEndian>()?;
+    ensure!(len >= 0, "NBT array has negative length {len}");
+    skip(r, len as usize * element_size)
+}
+
+fn skip(r: &mut &[u8], n: usize) -> Result<()> {
+    ensure!(
+        n <= r.len(),
+        "NBT ends early: needed {n} bytes, {} left",
+        r.len()
+    );
+    *r = &r[n..];
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sign {
+        text: String,
+        glowing: bool,
+        lines: Vec<i32>,
+        position: Position,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        x: i64,
+        y: i16,
+    }
+
+    fn sign() -> Sign {
+        Sign {
+            text: "Hi".to_owned(),
+            glowing: true,
+            lines: vec![1, 2, 3],
+            position: Position { x: -5, y: 64 },
+        }
+    }
+
+    #[test]
+    fn compounds_round_trip() {
+        let mut encoded = Vec::new();
+        Nbt(sign()).encode(&mut encoded).unwrap();
+        // whatever comes after the NBT is left alone
+        encoded.push(0xAB);
+
+        let mut r = &encoded[..];
+        let Nbt(decoded) = Nbt::<Sign>::decode(&mut r).unwrap();
+        assert_eq!(decoded, sign());
+        assert_eq!(r, [0xAB]);
+    }
+
+    #[test]
+    fn truncated_compounds_are_rejected() {
+        let mut encoded = Vec::new();
+        Nbt(sign()).encode(&mut encoded).unwrap();
+        encoded.pop();
+
+        assert!(Nbt::<Sign>::decode(&mut &encoded[..]).is_err());
+    }
+}
@@ -17,7 +17,13 @@
  * <https://www.gnu.org/licenses/>.
  */
 
-use crate::{protocol::Encode, server::registries::REGISTRIES, world::Item};
+use color_eyre::eyre::{bail, ensure};
+
+use crate::{
+    protocol::{Decode, Encode},
+    server::registries::registries,
+    world::Item,
+};
 
 use super::VarInt;
 
@@ -30,11 +36,69 @@ pub struct Slot {
 }
 
 #[derive(Debug, Clone)]
-pub enum Component {}
+pub enum Component {
+    /// The items inside a bundle. Bundles are behind the `minecraft:bundle` feature flag in
+    /// 1.21.1, so they need that enabled to show up.
+    BundleContents(Vec<Slot>),
+}
+
+impl Component {
+    fn name(&self) -> &'static str {
+        match self {
+            Component::BundleContents(_) => "minecraft:bundle_contents",
+        }
+    }
+
+    /// The component's protocol id, from the data component type registry.
+    pub fn id(&self) -> i32 {
+        registries()
+            .data_component_type
+            .entries
+            .get(self.name())
+            .expect("Couldn't find registry entry for data component")
+            .protocol_id
+    }
+}
+
+impl Encode for Component {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        match self {
+            Component::BundleContents(items) => {
+                VarInt(items.len() as i32).encode(&mut w)?;
+                items.encode(&mut w)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The most components a decoded slot may claim to add or remove. There are well under this
+/// many component types, so anything more is a malformed (or malicious) packet.
+const MAX_COMPONENTS: i32 = 256;
+
+impl Slot {
+    /// The item's protocol id, or `None` if the slot is empty.
+    pub fn item_id(&self) -> Option<i32> {
+        self.item_id.filter(|_| self.item_count > 0)
+    }
+
+    pub fn count(&self) -> i8 {
+        self.item_count
+    }
+
+    /// Adds a component to the item, e.g. to fill a bundle.
+    pub fn with_component(mut self, component: Component) -> Self {
+        self.components_to_add
+            .get_or_insert_with(Vec::new)
+            .push(component);
+        self
+    }
+}
 
 impl From<Item> for Slot {
     fn from(value: Item) -> Self {
-        let item_id = REGISTRIES
+        let item_id = registries()
             .item
             .entries
             .get(&value.id)
@@ -91,14 +155,15 @@ impl Encode for Slot {
             .encode(&mut w)?;
 
             if let Some(ref components_to_add) = self.components_to_add {
-                for _component in components_to_add {
-                    unimplemented!("Encoding components is not implemented");
+                for component in components_to_add {
+                    VarInt(component.id()).encode(&mut w)?;
+                    component.encode(&mut w)?;
                 }
             }
 
             if let Some(ref components_to_remove) = self.components_to_remove {
-                for _component in components_to_remove {
-                    unimplemented!("Encoding components is not implemented");
+                for component in components_to_remove {
+                    VarInt(*component).encode(&mut w)?;
                 }
             }
         }
@@ -106,3 +171,109 @@ impl Encode for Slot {
         Ok(())
     }
 }
+
+impl<'a> Decode<'a> for Slot {
+    fn decode(r: &mut &'a [u8]) -> color_eyre::eyre::Result<Self> {
+        let item_count = VarInt::decode(r)?.0;
+        if item_count <= 0 {
+            return Ok(Self::default());
+        }
+
+        let item_id = VarInt::decode(r)?.0;
+        let to_add = VarInt::decode(r)?.0;
+        let to_remove = VarInt::decode(r)?.0;
+
+        // check these before looping so a huge count can't make us spin or allocate
+        ensure!(
+            (0..=MAX_COMPONENTS).contains(&to_add),
+            "slot claims to add {to_add} components (max {MAX_COMPONENTS})"
+        );
+        ensure!(
+            (0..=MAX_COMPONENTS).contains(&to_remove),
+            "slot claims to remove {to_remove} components (max {MAX_COMPONENTS})"
+        );
+
+        if to_add > 0 {
+            bail!("Decoding components is not implemented");
+        }
+
+        let mut components_to_remove = Vec::with_capacity(to_remove as usize);
+        for _ in 0..to_remove {
+            components_to_remove.push(VarInt::decode(r)?.0);
+        }
+
+        Ok(Self {
+            item_count: item_count.try_into()?,
+            item_id: Some(item_id),
+            components_to_add: None,
+            components_to_remove: (!components_to_remove.is_empty())
+                .then_some(components_to_remove),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(to_add: i32, to_remove: i32) -> Vec<u8> {
+        let mut body = Vec::new();
+        for value in [1, 5, to_add, to_remove] {
+            VarInt(value).encode(&mut body).unwrap();
+        }
+        body
+    }
+
+    #[test]
+    fn absurd_component_counts_are_rejected() {
+        for (to_add, to_remove) in [(i32::MAX, 0), (0, i32::MAX), (-1, 0), (0, -1), (257, 0)] {
+            assert!(Slot::decode(&mut &slot(to_add, to_remove)[..]).is_err());
+        }
+    }
+
+    #[test]
+    fn removed_components_decode() {
+        let mut body = slot(0, 2);
+        body.extend([3, 4]);
+
+        let decoded = Slot::decode(&mut &body[..]).unwrap();
+        assert_eq!(decoded.item_id(), Some(5));
+        assert_eq!(decoded.count(), 1);
+        assert_eq!(decoded.components_to_remove, Some(vec![3, 4]));
+    }
+
+    fn item(id: &str, count: i32) -> Slot {
+        Slot::from(Item {
+            slot: 0,
+            id: id.to_owned(),
+            count,
+        })
+    }
+
+    fn encode(slot: &Slot) -> Vec<u8> {
+        let mut buf = Vec::new();
+        slot.encode(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn bundle_contents_encode() {
+        let contents = vec![item("minecraft:stone", 3), item("minecraft:dirt", 1)];
+        let bundle =
+            item("minecraft:bundle", 1).with_component(Component::BundleContents(contents));
+        assert_eq!(Component::BundleContents(Vec::new()).id(), 30);
+
+        // count, bundle item id (930), one component to add, none to remove
+        let mut expected = vec![1, 0xA2, 0x07, 1, 0];
+        // bundle_contents, two slots: 3 stone and 1 dirt
+        expected.extend([30, 2, 3, 1, 0, 0, 1, 28, 0, 0]);
+        assert_eq!(encode(&bundle), expected);
+    }
+
+    #[test]
+    fn empty_bundle_encodes_an_empty_list() {
+        let bundle =
+            item("minecraft:bundle", 1).with_component(Component::BundleContents(Vec::new()));
+        assert_eq!(encode(&bundle), [1, 0xA2, 0x07, 1, 0, 30, 0]);
+    }
+}
@@ -57,13 +57,26 @@ impl<'a, const BOUND: usize> Encode for Bounded<&'a str, BOUND> {
     fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
         let len = self.0.encode_utf16().count();
 
-        ensure!(len < BOUND, "length of string {len} exceeds bound {BOUND}");
+        ensure!(len <= BOUND, "length of string {len} exceeds bound {BOUND}");
 
         VarInt(self.0.len() as i32).encode(&mut w)?;
         Ok(w.write_all(self.0.as_bytes())?)
     }
 }
 
+/// For strings that need to outlive the frame they came in, like usernames or chat.
+impl<'a, const BOUND: usize> Decode<'a> for Bounded<String, BOUND> {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(Bounded(Bounded::<&'a str, BOUND>::decode(r)?.0.to_owned()))
+    }
+}
+
+impl<const BOUND: usize> Encode for Bounded<String, BOUND> {
+    fn encode(&self, w: impl std::io::Write) -> Result<()> {
+        Bounded::<&str, BOUND>(&self.0).encode(w)
+    }
+}
+
 impl Encode for str {
     fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
         VarInt(self.len() as i32).encode(&mut w)?;
@@ -71,6 +84,19 @@ impl Encode for str {
     }
 }
 
+/// Owned strings are checked against the default bound. Use [`Bounded`] for anything tighter.
+impl<'a> Decode<'a> for String {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(Bounded::<&'a str>::decode(r)?.0.to_owned())
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, w: impl std::io::Write) -> Result<()> {
+        Bounded::<&str>(self).encode(w)
+    }
+}
+
 impl<'a, const BOUND: usize> Encode for Bounded<Bytes<'a>, BOUND> {
     fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
         let len = self.0 .0.len();
@@ -162,4 +188,80 @@ impl<'a, const BOUND: usize> Decode<'a> for Rest<Bytes<'a>, BOUND> {
 
         Ok(Rest(content))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_at_the_bound_round_trip() {
+        let string = "a".repeat(32767);
+
+        let mut encoded = Vec::new();
+        string.encode(&mut encoded).unwrap();
+
+        let mut r = &encoded[..];
+        assert_eq!(String::decode(&mut r).unwrap(), string);
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn strings_past_the_bound_are_rejected() {
+        let string = "a".repeat(32768);
+        assert!(string.encode(Vec::new()).is_err());
+
+        // same thing, but from a client that didn't check
+        let mut encoded = Vec::new();
+        VarInt(string.len() as i32).encode(&mut encoded).unwrap();
+        encoded.extend(string.as_bytes());
+        assert!(String::decode(&mut &encoded[..]).is_err());
+    }
+
+    #[test]
+    fn owned_bounded_strings_round_trip() {
+        let mut encoded = Vec::new();
+        Bounded::<String, 16>("alice".to_owned())
+            .encode(&mut encoded)
+            .unwrap();
+
+        let mut r = &encoded[..];
+        assert_eq!(Bounded::<String, 16>::decode(&mut r).unwrap().0, "alice");
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn bounds_count_utf16_units() {
+        // 6 bytes, but only 3 units
+        let mut encoded = Vec::new();
+        Bounded::<String, 4>("ééé".to_owned())
+            .encode(&mut encoded)
+            .unwrap();
+        assert_eq!(
+            Bounded::<String, 4>::decode(&mut &encoded[..]).unwrap().0,
+            "ééé"
+        );
+
+        // 3 chars, but the emoji take 2 units each
+        let over = "😀😀a";
+        assert!(Bounded::<String, 4>(over.to_owned())
+            .encode(Vec::new())
+            .is_err());
+
+        let mut encoded = Vec::new();
+        over.encode(&mut encoded).unwrap();
+        assert!(Bounded::<String, 4>::decode(&mut &encoded[..]).is_err());
+    }
+
+    #[test]
+    fn negative_lengths_are_rejected() {
+        let mut encoded = Vec::new();
+        VarInt(-1).encode(&mut encoded).unwrap();
+        encoded.extend(b"hi");
+        assert!(String::decode(&mut &encoded[..]).is_err());
+    }
+
+    #[test]
+    fn truncated_strings_are_rejected() {
+        assert!(String::decode(&mut &[5, b'h', b'i'][..]).is_err());
+    }
+}
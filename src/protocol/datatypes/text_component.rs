@@ -17,22 +17,136 @@
  * <https://www.gnu.org/licenses/>.
  */
 
-use serde::Serialize;
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "TextComponentJson")]
 pub struct TextComponent {
     text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bold: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    italic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    underlined: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strikethrough: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    obfuscated: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    extra: Vec<TextComponent>,
+}
+
+impl TextComponent {
+    /// Parses a JSON text component, like the ones vanilla stores in `CustomName`. Only plain
+    /// text and styling are kept; translations, scores etc. come out empty.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Sets the color, either a named color like `gold` or a hex color like `#ff8800`.
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.bold = Some(bold);
+        self
+    }
+
+    pub fn italic(mut self, italic: bool) -> Self {
+        self.italic = Some(italic);
+        self
+    }
+
+    pub fn underlined(mut self, underlined: bool) -> Self {
+        self.underlined = Some(underlined);
+        self
+    }
+
+    pub fn strikethrough(mut self, strikethrough: bool) -> Self {
+        self.strikethrough = Some(strikethrough);
+        self
+    }
+
+    pub fn obfuscated(mut self, obfuscated: bool) -> Self {
+        self.obfuscated = Some(obfuscated);
+        self
+    }
+
+    /// Appends a child component, which inherits this one's styling.
+    pub fn append(mut self, extra: impl Into<TextComponent>) -> Self {
+        self.extra.push(extra.into());
+        self
+    }
 }
 
 impl From<String> for TextComponent {
     fn from(value: String) -> Self {
-        Self { text: value }
+        Self {
+            text: value,
+            ..Default::default()
+        }
     }
 }
 impl From<&str> for TextComponent {
     fn from(value: &str) -> Self {
-        Self {
-            text: value.to_owned(),
+        value.to_owned().into()
+    }
+}
+
+/// The shapes a text component can take in JSON: a bare string, a list where the rest are
+/// children of the first, or a full object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextComponentJson {
+    Plain(String),
+    List(Vec<TextComponent>),
+    Styled(StyledJson),
+}
+
+#[derive(Deserialize)]
+struct StyledJson {
+    #[serde(default)]
+    text: String,
+    color: Option<String>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    underlined: Option<bool>,
+    strikethrough: Option<bool>,
+    obfuscated: Option<bool>,
+    #[serde(default)]
+    extra: Vec<TextComponent>,
+}
+
+impl From<TextComponentJson> for TextComponent {
+    fn from(value: TextComponentJson) -> Self {
+        match value {
+            TextComponentJson::Plain(text) => text.into(),
+            TextComponentJson::List(parts) => {
+                let mut parts = parts.into_iter();
+                let mut first = parts.next().unwrap_or_default();
+                first.extra.extend(parts);
+                first
+            }
+            TextComponentJson::Styled(s) => Self {
+                text: s.text,
+                color: s.color,
+                bold: s.bold,
+                italic: s.italic,
+                underlined: s.underlined,
+                strikethrough: s.strikethrough,
+                obfuscated: s.obfuscated,
+                extra: s.extra,
+            },
         }
     }
 }
@@ -173,74 +173,41 @@ impl VarLong {
         unsafe { core::mem::transmute(res) }
     }
 
-    // TODO: need to confirm this works. for now it's just a naive translation of avx2,
-    // but could definitely be improved -- blocking NEON implementation of Encode
-    //
-    // #[inline(always)]
-    // #[cfg(target_feature = "neon")]
-    // fn num_to_vector_stage1(self) -> [u8; 16] {
-    //     use std::arch::aarch64::*;
-    //
-    //     let mut res = [0u64; 2];
-    //     let x = self;
-    //
-    //     let b = unsafe { vdupq_n_s64(self.0 as i64) };
-    //     let c = unsafe {
-    //         vorrq_s64(
-    //             vorrq_s64(
-    //                 vshlq_s64(
-    //                     vandq_s64(
-    //                         b,
-    //                         vcombine_s64(
-    //                             vcreate_s64(0x000003f800000000),
-    //                             vcreate_s64(0x00000007f0000000),
-    //                         ),
-    //                     ),
-    //                     vcombine_s64(vcreate_s64(5), vcreate_s64(4)),
-    //                 ),
-    //                 vshlq_s64(
-    //                     vandq_s64(
-    //                         b,
-    //                         vcombine_s64(
-    //                             vcreate_s64(0x00fe000000000000),
-    //                             vcreate_s64(0x0001fc0000000000),
-    //                         ),
-    //                     ),
-    //                     vcombine_s64(vcreate_s64(7), vcreate_s64(6)),
-    //                 ),
-    //             ),
-    //             vorrq_s64(
-    //                 vshlq_s64(
-    //                     vandq_s64(
-    //                         b,
-    //                         vcombine_s64(
-    //                             vcreate_s64(0x0000000000003f80),
-    //                             vcreate_s64(0x000000000000007f),
-    //                         ),
-    //                     ),
-    //                     vcombine_s64(vcreate_s64(1), vcreate_s64(0)),
-    //                 ),
-    //                 vshlq_s64(
-    //                     vandq_s64(
-    //                         b,
-    //                         vcombine_s64(
-    //                             vcreate_s64(0x000000000fe00000),
-    //                             vcreate_s64(0x00000000001fc000),
-    //                         ),
-    //                     ),
-    //                     vcombine_s64(vcreate_s64(3), vcreate_s64(2)),
-    //                 ),
-    //             ),
-    //         )
-    //     };
-    //     let d = unsafe { vorrq_s64(c, vshrq_n_s64::<8>(c)) };
-    //
-    //     res[0] = unsafe { vgetq_lane_s64(d, 0) as u64 };
-    //     res[1] =
-    //         ((x.0 as u64 & 0x7f00000000000000) >> 56) | ((x.0 as u64 & 0x8000000000000000) >> 55);
-    //
-    //     unsafe { core::mem::transmute(res) }
-    // }
+    #[inline(always)]
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    fn num_to_vector_stage1(self) -> [u8; 16] {
+        use std::arch::aarch64::*;
+        let mut res = [0u64; 2];
+        let x = self.0 as u64;
+
+        // same idea as avx2: each lane shifts four of the first eight 7-bit groups into their own
+        // bytes, then the two lanes are merged
+        unsafe {
+            let b = vdupq_n_u64(x);
+            let spread = |masks: [u64; 2], shifts: [i64; 2]| {
+                vshlq_u64(
+                    vandq_u64(b, vld1q_u64(masks.as_ptr())),
+                    vld1q_s64(shifts.as_ptr()),
+                )
+            };
+
+            let c = vorrq_u64(
+                vorrq_u64(
+                    spread([0x000000000000007f, 0x0000000000003f80], [0, 1]),
+                    spread([0x00000000001fc000, 0x000000000fe00000], [2, 3]),
+                ),
+                vorrq_u64(
+                    spread([0x00000007f0000000, 0x000003f800000000], [4, 5]),
+                    spread([0x0001fc0000000000, 0x00fe000000000000], [6, 7]),
+                ),
+            );
+
+            res[0] = vgetq_lane_u64::<0>(c) | vgetq_lane_u64::<1>(c);
+        }
+        res[1] = ((x & 0x7f00000000000000) >> 56) | ((x & 0x8000000000000000) >> 55);
+
+        unsafe { core::mem::transmute(res) }
+    }
 }
 
 impl Encode for VarLong {
@@ -279,10 +246,54 @@ impl Encode for VarLong {
         }
     }
 
-    // TODO: implement this using neon? not likely we'll use arm-based servers but maybe nice for
-    // local testing?
-    #[cfg(not(any(target_feature = "bmi2", target_feature = "avx2")))]
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
     fn encode(&self, mut w: impl Write) -> Result<()> {
+        use std::arch::aarch64::*;
+
+        let stage1 = self.num_to_vector_stage1();
+
+        // MSBs are all clear at this point, so the highest non-zero byte is the last one needed.
+        // zero still takes a byte
+        let bytes = (16 - u128::from_le_bytes(stage1).leading_zeros() as usize / 8).max(1);
+
+        let mut merged = [0u8; 16];
+        unsafe {
+            // every byte before the last one gets its continuation bit
+            let ascend =
+                vld1q_u8([0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15].as_ptr());
+            let mask = vcltq_u8(ascend, vdupq_n_u8(bytes as u8 - 1));
+            let msbmask = vandq_u8(mask, vdupq_n_u8(0x80));
+
+            vst1q_u8(
+                merged.as_mut_ptr(),
+                vorrq_u8(vld1q_u8(stage1.as_ptr()), msbmask),
+            );
+        }
+
+        Ok(w.write_all(&merged[..bytes])?)
+    }
+
+    #[cfg(not(any(
+        target_feature = "bmi2",
+        target_feature = "avx2",
+        all(target_arch = "aarch64", target_feature = "neon")
+    )))]
+    fn encode(&self, w: impl Write) -> Result<()> {
+        self.encode_scalar(w)
+    }
+}
+
+impl VarLong {
+    /// The plain loop, for when there's no SIMD. Tests check the SIMD paths against it.
+    #[cfg(any(
+        test,
+        not(any(
+            target_feature = "bmi2",
+            target_feature = "avx2",
+            all(target_arch = "aarch64", target_feature = "neon")
+        ))
+    ))]
+    fn encode_scalar(&self, mut w: impl Write) -> Result<()> {
         use byteorder::WriteBytesExt;
 
         let mut val = self.0 as u64;
@@ -296,3 +307,65 @@ impl Encode for VarLong {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every power of two, one below it and its negative, plus the extremes.
+    fn boundaries() -> Vec<i64> {
+        let mut values = vec![0, i64::MIN, i64::MAX];
+        for bit in 0..63 {
+            let n = 1i64 << bit;
+            values.extend([n, n - 1, -n]);
+        }
+        values
+    }
+
+    #[test]
+    fn varlongs_round_trip() {
+        for value in boundaries() {
+            let mut encoded = Vec::new();
+            VarLong(value).encode(&mut encoded).unwrap();
+
+            let mut r = &encoded[..];
+            assert_eq!(VarLong::decode(&mut r).unwrap(), VarLong(value));
+            assert!(r.is_empty(), "{value} left bytes behind");
+        }
+    }
+
+    /// Whichever path `encode` compiled to (NEON on aarch64) has to match the scalar loop.
+    #[test]
+    fn simd_matches_scalar() {
+        for value in [0, 1, 127, 128, i64::MAX, -1, i64::MIN]
+            .into_iter()
+            .chain(boundaries())
+        {
+            let (mut simd, mut scalar) = (Vec::new(), Vec::new());
+            VarLong(value).encode(&mut simd).unwrap();
+            VarLong(value).encode_scalar(&mut scalar).unwrap();
+            assert_eq!(simd, scalar, "{value} encoded differently");
+        }
+    }
+
+    #[test]
+    fn known_varlongs() {
+        for (value, expected) in [
+            (0, &[0x00][..]),
+            (127, &[0x7F]),
+            (128, &[0x80, 0x01]),
+            (
+                -1,
+                &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01],
+            ),
+            (
+                i64::MIN,
+                &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01],
+            ),
+        ] {
+            let mut encoded = Vec::new();
+            VarLong(value).encode(&mut encoded).unwrap();
+            assert_eq!(encoded, expected, "{value}");
+        }
+    }
+}
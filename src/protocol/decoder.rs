@@ -28,13 +28,19 @@ use super::{
 };
 
 #[cfg(feature = "encryption")]
-type _Cipher = cfb8::Decryptor<aes::Aes128>;
+type Cipher = cfb8::Decryptor<aes::Aes128>;
 
 #[derive(Default, Debug)]
 pub struct Decoder {
     buf: BytesMut,
+    /// Set once the client's been sent `SetCompressionC`, after which every frame carries a data
+    /// length. `None` means compression is off.
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
+    /// Set once the login handshake's shared secret is known, after which every byte read is
+    /// decrypted as it comes in.
     #[cfg(feature = "encryption")]
-    _compression_threshold: i32,
+    cipher: Option<Cipher>,
 }
 
 #[derive(Debug, Clone)]
@@ -74,11 +80,28 @@ impl Decoder {
     pub fn new() -> Self {
         Self {
             buf: BytesMut::default(),
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
             #[cfg(feature = "encryption")]
-            _compression_threshold: -1, // disabled
+            cipher: None,
         }
     }
 
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    /// Decrypts everything from here on, including anything already buffered but not yet read.
+    #[cfg(feature = "encryption")]
+    pub fn enable_encryption(&mut self, key: &[u8; 16]) {
+        use aes::cipher::KeyIvInit;
+
+        let mut cipher = Cipher::new(key.into(), key.into());
+        decrypt(&mut cipher, &mut self.buf);
+        self.cipher = Some(cipher);
+    }
+
     pub fn reserve_additional(&mut self, additional: usize) {
         self.buf.reserve(additional);
     }
@@ -108,9 +131,15 @@ impl Decoder {
             return Ok(None);
         }
 
-        // TODO: use compression here
         self.buf.advance(len.len());
+        #[allow(unused_mut)]
         let mut data = self.buf.split_to(len.0 as usize);
+
+        #[cfg(feature = "compression")]
+        if let Some(threshold) = self.compression_threshold {
+            data = decompress(data, threshold)?;
+        }
+
         buf = &data[..];
 
         let packet_id = VarInt::decode(&mut buf)
@@ -130,7 +159,65 @@ impl Decoder {
         self.buf.split_off(self.buf.len())
     }
 
-    pub fn add_bytes(&mut self, bytes: BytesMut) {
+    #[allow(unused_mut)]
+    pub fn add_bytes(&mut self, mut bytes: BytesMut) {
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &mut self.cipher {
+            decrypt(cipher, &mut bytes);
+        }
+
         self.buf.unsplit(bytes);
     }
 }
+
+#[cfg(feature = "encryption")]
+fn decrypt(cipher: &mut Cipher, bytes: &mut [u8]) {
+    use aes::cipher::{generic_array::GenericArray, BlockDecryptMut};
+
+    // CFB8 works a byte at a time, so there's never a partial block to hold back
+    for byte in bytes.chunks_mut(1) {
+        cipher.decrypt_block_mut(GenericArray::from_mut_slice(byte));
+    }
+}
+
+/// Unwraps a frame sent with compression on, which is the data length followed by either the
+/// packet as is (for a data length of 0) or the zlib compressed packet.
+#[cfg(feature = "compression")]
+fn decompress(mut data: BytesMut, threshold: usize) -> Result<BytesMut> {
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    let mut r = &data[..];
+    let data_len = VarInt::decode(&mut r)
+        .context("Failed to decode data length")?
+        .0;
+    let header_len = data.len() - r.len();
+
+    if data_len == 0 {
+        data.advance(header_len);
+        return Ok(data);
+    }
+
+    ensure!(
+        0 < data_len && data_len <= MAX_PACKET_SIZE,
+        "Data length {data_len} is out of bounds (min 1, max {MAX_PACKET_SIZE})",
+    );
+    ensure!(
+        data_len as usize >= threshold,
+        "Compressed packet of {data_len} bytes is under the compression threshold {threshold}",
+    );
+
+    let mut packet = Vec::with_capacity(data_len as usize);
+    ZlibDecoder::new(r)
+        .take(data_len as u64 + 1)
+        .read_to_end(&mut packet)
+        .context("Failed to decompress packet")?;
+
+    ensure!(
+        packet.len() == data_len as usize,
+        "Decompressed packet is {} bytes, but its data length said {data_len}",
+        packet.len()
+    );
+
+    Ok(BytesMut::from(&packet[..]))
+}
@@ -22,17 +22,15 @@ use color_eyre::eyre::{ensure, Result};
 
 use crate::protocol::{Encode, MAX_PACKET_SIZE};
 
-use super::{
-    datatypes::{VarInt, VariableNumber},
-    ClientboundPacket,
-};
-
-#[cfg(feature = "encryption")]
-type _Cipher = cfb8::Encryptor<aes::Aes128>;
+use super::{datatypes::VarInt, ClientboundPacket};
 
 #[derive(Default, Debug)]
 pub struct Encoder {
     buf: BytesMut,
+    /// Packets at least this big are zlib compressed, once the client's been sent
+    /// `SetCompressionC`. `None` means compression is off.
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
 }
 
 impl Encoder {
@@ -64,30 +62,114 @@ impl Encoder {
         Ok(())
     }
 
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
     pub fn append_packet<P>(&mut self, packet: &P) -> Result<()>
     where
         P: ClientboundPacket,
     {
         let initial_len = self.buf.len();
         packet.encode_packet((&mut self.buf).writer())?;
+        self.frame(initial_len)
+    }
 
-        let packet_size = self.buf.len() - initial_len;
+    /// Appends packets already framed by another encoder, like the cached chunk packets. Those
+    /// are never compressed, so they're framed again if this encoder compresses.
+    pub fn append_frames(&mut self, frames: &[u8]) -> Result<()> {
+        #[cfg(feature = "compression")]
+        if self.compression_threshold.is_some() {
+            use crate::protocol::Decode;
+
+            let mut frames = frames;
+            while !frames.is_empty() {
+                let len = VarInt::decode(&mut frames)?.0 as usize;
+                ensure!(
+                    len <= frames.len(),
+                    "frame is {len} bytes, but only {} are left",
+                    frames.len()
+                );
+
+                let (packet, rest) = frames.split_at(len);
+                let initial_len = self.buf.len();
+                self.buf.extend_from_slice(packet);
+                self.frame(initial_len)?;
+                frames = rest;
+            }
+
+            return Ok(());
+        }
+
+        self.buf.extend_from_slice(frames);
+        Ok(())
+    }
+
+    /// Puts a length header (and the data length, if compressing) in front of the packet that
+    /// starts at `start`, compressing it if it's big enough.
+    fn frame(&mut self, start: usize) -> Result<()> {
+        let packet_size = self.buf.len() - start;
 
         ensure!(
             (packet_size as i32) < MAX_PACKET_SIZE,
             "packet size {packet_size} exceeds max {MAX_PACKET_SIZE}!"
         );
 
-        let header_size = VarInt(packet_size as i32).len();
+        #[cfg(feature = "compression")]
+        if let Some(threshold) = self.compression_threshold {
+            if packet_size >= threshold {
+                return self.compress(start);
+            }
+
+            // a data length of 0 means the packet isn't compressed
+            let mut header = Vec::new();
+            VarInt(packet_size as i32 + 1).encode(&mut header)?;
+            VarInt(0).encode(&mut header)?;
+            self.insert_header(start, &header);
+            return Ok(());
+        }
+
+        let mut header = Vec::new();
+        VarInt(packet_size as i32).encode(&mut header)?;
+        self.insert_header(start, &header);
+
+        Ok(())
+    }
+
+    fn insert_header(&mut self, start: usize, header: &[u8]) {
+        let packet_size = self.buf.len() - start;
+
+        self.buf.put_bytes(0, header.len());
+        self.buf
+            .copy_within(start..start + packet_size, start + header.len());
+        self.buf[start..start + header.len()].copy_from_slice(header);
+    }
+
+    #[cfg(feature = "compression")]
+    fn compress(&mut self, start: usize) -> Result<()> {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let data_len = self.buf.len() - start;
+
+        let mut compressor = ZlibEncoder::new(Vec::new(), Compression::default());
+        compressor.write_all(&self.buf[start..])?;
+        let compressed = compressor.finish()?;
 
-        self.buf.put_bytes(0, header_size);
-        self.buf.copy_within(
-            initial_len..initial_len + packet_size,
-            initial_len + header_size,
+        let mut data_len_header = Vec::new();
+        VarInt(data_len as i32).encode(&mut data_len_header)?;
+
+        let packet_size = data_len_header.len() + compressed.len();
+        ensure!(
+            (packet_size as i32) < MAX_PACKET_SIZE,
+            "compressed packet size {packet_size} exceeds max {MAX_PACKET_SIZE}!"
         );
 
-        let front = &mut self.buf[initial_len..];
-        VarInt(packet_size as i32).encode(front)?;
+        self.buf.truncate(start);
+        VarInt(packet_size as i32).encode((&mut self.buf).writer())?;
+        self.buf.extend_from_slice(&data_len_header);
+        self.buf.extend_from_slice(&compressed);
 
         Ok(())
     }
@@ -96,3 +178,63 @@ impl Encoder {
         self.buf.split()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::protocol::{Decoder, Packet};
+
+    #[derive(Debug)]
+    struct Blob(Vec<u8>);
+
+    impl Packet for Blob {
+        const ID: i32 = 0x42;
+    }
+
+    impl Encode for Blob {
+        fn encode(&self, mut w: impl Write) -> Result<()> {
+            Ok(w.write_all(&self.0)?)
+        }
+    }
+
+    /// Sends a small and a large packet through, and returns how many bytes they took.
+    fn round_trip(encoder: &mut Encoder, decoder: &mut Decoder) -> usize {
+        let small = Blob(vec![7; 16]);
+        let large = Blob(vec![7; 10_000]);
+        encoder.append_packet(&small).unwrap();
+        encoder.append_packet(&large).unwrap();
+
+        let encoded = encoder.take();
+        let len = encoded.len();
+        decoder.add_bytes(encoded);
+
+        for blob in [small, large] {
+            let frame = decoder.try_read_next().unwrap().unwrap();
+            assert_eq!(frame.id, Blob::ID);
+            assert_eq!(frame.body[..], blob.0[..]);
+        }
+        assert!(decoder.try_read_next().unwrap().is_none());
+
+        len
+    }
+
+    #[test]
+    fn packets_round_trip_uncompressed() {
+        let len = round_trip(&mut Encoder::new(), &mut Decoder::new());
+        assert!(len > 10_000);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn packets_round_trip_compressed() {
+        let (mut encoder, mut decoder) = (Encoder::new(), Decoder::new());
+        encoder.set_compression(Some(256));
+        decoder.set_compression(Some(256));
+
+        // the large one shrinks, the small one goes through as is
+        let len = round_trip(&mut encoder, &mut decoder);
+        assert!(len < 1_000);
+    }
+}
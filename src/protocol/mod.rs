@@ -18,14 +18,21 @@
  */
 
 pub mod datatypes {
+    mod angle;
+    pub mod fixed;
     mod impls;
+    mod metadata;
+    mod nbt;
     mod position;
     mod slot;
     mod string;
     mod text_component;
     mod variable;
 
+    pub use angle::*;
     pub use impls::*;
+    pub use metadata::*;
+    pub use nbt::*;
     pub use position::*;
     pub use slot::*;
     pub use string::*;
@@ -50,26 +57,46 @@ pub mod packets {
     }
 
     pub mod play {
+        mod advancements;
+        mod chat;
+        mod client_status;
+        mod configuration;
         mod container;
+        mod disconnect;
+        mod entity;
         mod game_event;
         mod interactions;
         mod keepalive;
         mod login;
         mod position;
+        mod resource_pack;
+        mod settings;
         mod status;
+        mod tags;
         mod teleport;
         mod tick;
+        mod title;
         mod world;
 
+        pub use advancements::*;
+        pub use chat::*;
+        pub use client_status::*;
+        pub use configuration::*;
         pub use container::*;
+        pub use disconnect::*;
+        pub use entity::*;
         pub use game_event::*;
         pub use interactions::*;
         pub use keepalive::*;
         pub use login::*;
         pub use position::*;
+        pub use resource_pack::*;
+        pub use settings::*;
         pub use status::*;
+        pub use tags::*;
         pub use teleport::*;
         pub use tick::*;
+        pub use title::*;
         pub use world::*;
     }
 }
@@ -80,8 +107,8 @@ mod encoder;
 use std::{fmt::Debug, io::Write};
 
 use bit_vec::BitVec;
-use color_eyre::eyre::{Context, Result};
-use datatypes::{Bounded, VarInt};
+use color_eyre::eyre::{ensure, Context, Result};
+use datatypes::{Bounded, OptionalPrefixed, VarInt};
 pub use decoder::*;
 pub use encoder::*;
 use thiserror::Error;
@@ -108,9 +135,34 @@ pub enum PacketState {
     Play,
     Status,
     Login,
+    /// Back in configuration after [`packets::play::StartConfigurationC`], from play.
+    Configuration,
     Transfer,
 }
 
+impl PacketState {
+    /// Whether a connection in this state can move on to `next`. There's no separate state for
+    /// the first configuration phase - it's handled as part of login.
+    pub fn can_transition_to(self, next: PacketState) -> bool {
+        use PacketState::*;
+
+        matches!(
+            (self, next),
+            (Handshaking, Status | Login | Transfer)
+                | (Login, Play)
+                | (Play, Configuration)
+                | (Configuration, Play)
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Can't go from {from:?} to {to:?}")]
+pub struct PacketStateTransitionError {
+    pub from: PacketState,
+    pub to: PacketState,
+}
+
 #[derive(Error, Debug)]
 pub enum PacketStateDecodeError {
     #[error("Unable to decode {0} into a PacketState")]
@@ -130,6 +182,12 @@ impl TryFrom<i32> for PacketState {
     }
 }
 
+impl<'a> Decode<'a> for PacketState {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(PacketState::try_from(VarInt::decode(r)?.0)?)
+    }
+}
+
 pub trait Packet {
     const ID: i32;
 }
@@ -153,18 +211,15 @@ impl<P> ClientboundPacket for P where P: Packet + Encode + Debug {}
 
 #[derive(Debug)]
 pub struct Property<'a> {
-    name: Bounded<&'a str, 32767>,
-    value: Bounded<&'a str, 32767>,
-    signature: Option<Bounded<&'a str, 32767>>,
+    pub name: Bounded<&'a str, 32767>,
+    pub value: Bounded<&'a str, 32767>,
+    pub signature: OptionalPrefixed<Bounded<&'a str, 32767>>,
 }
 
 impl Encode for Property<'_> {
     fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
-        let signed = self.signature.is_some();
-
         self.name.encode(&mut w)?;
         self.value.encode(&mut w)?;
-        signed.encode(&mut w)?;
         self.signature.encode(&mut w)?;
 
         Ok(())
@@ -188,3 +243,215 @@ impl Encode for BitVec {
         Ok(())
     }
 }
+
+/// The inverse of the [`Encode`] above. Only whole longs are sent, so the decoded length is
+/// always a multiple of 64.
+impl<'a> Decode<'a> for BitVec {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        let len = VarInt::decode(r)?.0;
+        ensure!(len >= 0, "tried to decode bit set with negative length");
+
+        let len = len as usize;
+        ensure!(
+            len.saturating_mul(8) <= r.len(),
+            "malformed packet - not enough data to continue decoding (expected {} got {})",
+            len.saturating_mul(8),
+            r.len(),
+        );
+
+        let longs = (0..len)
+            .map(|_| i64::decode(r))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(BitVec::from_fn(len * 64, |i| {
+            (longs[i / 64] >> (63 - (i % 64))) & 1 == 1
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{
+        packets::{
+            login::{registry::*, *},
+            play::*,
+        },
+        *,
+    };
+
+    /// Lists packets as `(state and direction, name, declared id, expected id)`.
+    macro_rules! packets {
+        ($($group:literal => [$($packet:ty = $id:literal),* $(,)?]),* $(,)?) => {
+            vec![$($(($group, stringify!($packet), <$packet as Packet>::ID, $id),)*)*]
+        };
+    }
+
+    /// Every packet crawlspace sends or reads, against the ids the 1.21.1 protocol gives them.
+    #[test]
+    fn packet_ids_match_the_protocol() {
+        let packets = packets![
+            "handshake serverbound" => [HandshakeS = 0x00],
+            "status clientbound" => [StatusResponseC = 0x00, Ping = 0x01],
+            "status serverbound" => [StatusRequestS = 0x00, Ping = 0x01],
+            "login clientbound" => [
+                LoginDisconnectC = 0x00,
+                EncryptionRequestC = 0x01,
+                LoginSuccessC = 0x02,
+                SetCompressionC = 0x03,
+                PluginRequestC = 0x04,
+            ],
+            "login serverbound" => [
+                LoginStartS = 0x00,
+                EncryptionResponseS = 0x01,
+                PluginResponseS = 0x02,
+                LoginAckS = 0x03,
+            ],
+            "configuration clientbound" => [
+                DisconnectConfigC = 0x02,
+                FinishConfigurationC = 0x03,
+                Registry<DimensionType> = 0x07,
+                FeatureFlagsC = 0x0C,
+                UpdateTagsConfigC = 0x0D,
+                KnownPacksC = 0x0E,
+            ],
+            "configuration serverbound" => [
+                ClientInformationConfigS = 0x00,
+                FinishConfigurationAckS = 0x03,
+                KnownPacksS = 0x07,
+            ],
+            "play clientbound" => [
+                BundleDelimiterC = 0x00,
+                SpawnEntityC = 0x01,
+                AwardStatisticsC = 0x04,
+                ClearTitlesC = 0x0F,
+                CloseContainerC = 0x12,
+                SetContainerContentC = 0x13,
+                SetContainerSlotC = 0x15,
+                DisconnectC = 0x1D,
+                UnloadChunkC = 0x21,
+                GameEventC = 0x22,
+                HurtAnimationC = 0x24,
+                InitializeWorldBorderC = 0x25,
+                KeepAliveC = 0x26,
+                ChunkDataUpdateLightC = 0x27,
+                LoginPlayC = 0x2B,
+                MerchantOffersC = 0x2D,
+                UpdateEntityPositionC = 0x2E,
+                UpdateEntityPositionAndRotationC = 0x2F,
+                UpdateEntityRotationC = 0x30,
+                OpenScreenC = 0x33,
+                PingC = 0x35,
+                PlayerInfoRemoveC = 0x3D,
+                PlayerInfoUpdateC = 0x3E,
+                SynchronisePositionC = 0x40,
+                RemoveEntitiesC = 0x42,
+                AddResourcePackC = 0x46,
+                RespawnC = 0x47,
+                SetHeadRotationC = 0x48,
+                ServerDataC = 0x4B,
+                SetBorderCenterC = 0x4D,
+                SetBorderLerpSizeC = 0x4E,
+                SetBorderSizeC = 0x4F,
+                SetBorderWarningDelayC = 0x50,
+                SetBorderWarningDistanceC = 0x51,
+                SetCameraC = 0x52,
+                SetCenterChunkC = 0x54,
+                SetEntityMetadataC = 0x58,
+                SetEquipmentC = 0x5B,
+                UpdateTimeC = 0x64,
+                StartConfigurationC = 0x69,
+                SystemChatMessageC = 0x6C,
+                SetTabListHeaderAndFooterC = 0x6D,
+                TeleportEntityC = 0x70,
+                SetTickingStateC = 0x71,
+                StepTicksC = 0x72,
+                UpdateAdvancementsC = 0x74,
+                UpdateAttributesC = 0x75,
+                UpdateTagsC = 0x78,
+            ],
+            "play serverbound" => [
+                ConfirmTeleportS = 0x00,
+                ChatCommandS = 0x04,
+                ChatMessageS = 0x06,
+                ClientStatusS = 0x09,
+                ClientInformationS = 0x0A,
+                ConfigurationAckS = 0x0C,
+                ClickContainerS = 0x0E,
+                CloseContainerS = 0x0F,
+                InteractS = 0x16,
+                KeepAliveS = 0x18,
+                SetPlayerPositionS = 0x1A,
+                SetPlayerPositionAndRotationS = 0x1B,
+                SetPlayerRotationS = 0x1C,
+                PickItemS = 0x20,
+                PongS = 0x27,
+                ResourcePackResponseS = 0x2B,
+                SetHeldItemS = 0x2F,
+                UseItemOnS = 0x38,
+            ],
+        ];
+
+        let mut seen = HashMap::new();
+        for (group, name, declared, expected) in packets {
+            assert_eq!(
+                declared, expected,
+                "{name} has id {declared:#04x}, but it's {expected:#04x} in {group}"
+            );
+
+            if let Some(other) = seen.insert((group, declared), name) {
+                panic!("{name} and {other} both have id {declared:#04x} in {group}");
+            }
+        }
+    }
+
+    #[test]
+    fn illegal_transitions_are_rejected() {
+        use PacketState::*;
+
+        assert!(Handshaking.can_transition_to(Login));
+        assert!(Login.can_transition_to(Play));
+        assert!(Play.can_transition_to(Configuration));
+
+        assert!(!Handshaking.can_transition_to(Play));
+        assert!(!Status.can_transition_to(Login));
+        assert!(!Play.can_transition_to(Handshaking));
+        assert!(!Configuration.can_transition_to(Login));
+    }
+
+    #[test]
+    fn next_states_decode() {
+        for (value, expected) in [
+            (1, PacketState::Status),
+            (2, PacketState::Login),
+            (3, PacketState::Transfer),
+        ] {
+            let state = PacketState::decode(&mut &[value][..]).unwrap();
+            assert_eq!(format!("{state:?}"), format!("{expected:?}"));
+        }
+
+        for value in [0, 4, 0x7F] {
+            assert!(PacketState::decode(&mut &[value][..]).is_err());
+        }
+        assert!(PacketState::decode(&mut &[][..]).is_err());
+    }
+
+    #[test]
+    fn bit_sets_round_trip() {
+        for len in [0, 1, 64, 130] {
+            let bits = BitVec::from_fn(len, |i| i % 3 == 0);
+
+            let mut encoded = Vec::new();
+            bits.encode(&mut encoded).unwrap();
+            let mut r = &encoded[..];
+            let decoded = BitVec::decode(&mut r).unwrap();
+            assert!(r.is_empty());
+
+            // padded out to whole longs, with the padding clear
+            assert_eq!(decoded.len(), len.div_ceil(64) * 64);
+            assert!(decoded.iter().take(len).eq(bits.iter()));
+            assert!(decoded.iter().skip(len).all(|b| !b));
+        }
+    }
+}
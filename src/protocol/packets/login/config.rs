@@ -20,7 +20,8 @@
 use color_eyre::eyre::{ensure, Result};
 
 use crate::protocol::{
-    datatypes::{Bounded, VarInt},
+    datatypes::{Bounded, TextComponent, VarInt},
+    packets::play::{ClientInformationS, UpdateTagsC},
     Decode, DecodeSized, Encode, Packet,
 };
 
@@ -80,6 +81,28 @@ impl<'a> KnownPacksC<'a> {
     }
 }
 
+/// Tells the client which feature flags (e.g. `minecraft:vanilla`, or experimental packs like
+/// `minecraft:bundle`) are enabled, so blocks and items behind them work.
+#[derive(Debug)]
+pub struct FeatureFlagsC<'a> {
+    pub flags: &'a [String],
+}
+
+impl Packet for FeatureFlagsC<'_> {
+    const ID: i32 = 0x0C;
+}
+
+impl Encode for FeatureFlagsC<'_> {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        VarInt(self.flags.len() as i32).encode(&mut w)?;
+        for flag in self.flags {
+            Bounded::<&str>(flag.as_str()).encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct KnownPacksS<'a> {
     pub _known_pack_count: VarInt,
@@ -137,3 +160,71 @@ impl<'a> Decode<'a> for FinishConfigurationAckS {
         Ok(Self)
     }
 }
+
+/// The configuration-phase copy of [`ClientInformationS`], which has a different ID.
+#[derive(Debug)]
+pub struct ClientInformationConfigS<'a>(pub ClientInformationS<'a>);
+
+impl Packet for ClientInformationConfigS<'_> {
+    const ID: i32 = 0x00;
+}
+
+impl<'a> Decode<'a> for ClientInformationConfigS<'a> {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(Self(ClientInformationS::decode(r)?))
+    }
+}
+
+/// The configuration-phase copy of [`UpdateTagsC`], which has a different ID.
+#[derive(Debug)]
+pub struct UpdateTagsConfigC<'a>(pub UpdateTagsC<'a>);
+
+impl Packet for UpdateTagsConfigC<'_> {
+    const ID: i32 = 0x0D;
+}
+
+impl Encode for UpdateTagsConfigC<'_> {
+    fn encode(&self, w: impl std::io::Write) -> Result<()> {
+        self.0.encode(w)
+    }
+}
+
+/// Disconnects a player during configuration, showing them the reason.
+#[derive(Debug)]
+pub struct DisconnectConfigC {
+    pub reason: TextComponent,
+}
+
+impl Packet for DisconnectConfigC {
+    const ID: i32 = 0x02;
+}
+
+impl Encode for DisconnectConfigC {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        fastnbt::to_bytes_with_opts(&self.reason, fastnbt::SerOpts::network_nbt())?
+            .encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::player::testing;
+
+    #[test]
+    fn default_feature_flags_are_vanilla() {
+        let state = testing::state(&[]);
+        let mut body = Vec::new();
+        FeatureFlagsC {
+            flags: &state.feature_flags,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        let mut expected = vec![1, 17];
+        expected.extend(b"minecraft:vanilla");
+        assert_eq!(body, expected);
+    }
+}
@@ -28,8 +28,10 @@ use crate::protocol::{
 #[derive(Debug)]
 pub struct HandshakeS<'a> {
     pub protocol_version: VarInt,
-    _server_address: Bounded<&'a str, 255>,
-    _server_port: u16,
+    /// The address the client typed in to connect. Proxies and mod loaders tack extra data on
+    /// after a NUL byte, so use [`HandshakeS::host`] to get just the hostname.
+    pub server_address: Bounded<&'a str, 255>,
+    pub server_port: u16,
     pub next_state: PacketState,
 }
 
@@ -41,9 +43,18 @@ impl<'a> Decode<'a> for HandshakeS<'a> {
     fn decode(buf: &mut &'a [u8]) -> Result<Self> {
         Ok(Self {
             protocol_version: VarInt::decode(buf)?,
-            _server_address: Bounded::<&'a str, 255>::decode(buf)?,
-            _server_port: buf.read_u16::<BigEndian>()?,
-            next_state: PacketState::try_from(VarInt::decode(buf)?.0)?,
+            server_address: Bounded::<&'a str, 255>::decode(buf)?,
+            server_port: buf.read_u16::<BigEndian>()?,
+            next_state: PacketState::decode(buf)?,
         })
     }
 }
+
+impl HandshakeS<'_> {
+    /// The hostname the client connected with, lowercased and without any trailing dot or data
+    /// appended by Forge or BungeeCord.
+    pub fn host(&self) -> String {
+        let host = self.server_address.0.split('\0').next().unwrap_or_default();
+        host.trim_end_matches('.').to_lowercase()
+    }
+}
@@ -17,11 +17,11 @@
  * <https://www.gnu.org/licenses/>.
  */
 
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{ensure, Result};
 use uuid::Uuid;
 
 use crate::protocol::{
-    datatypes::{Bounded, Bytes, Rest, VarInt},
+    datatypes::{Bounded, Bytes, OptionalPrefixed, Rest, TextComponent, VarInt},
     Decode, Encode, Packet, Property,
 };
 
@@ -70,6 +70,65 @@ impl<'a> Encode for LoginSuccessC<'a> {
     }
 }
 
+/// Starts the encryption handshake in online mode. The client sends back a shared secret and the
+/// verify token, both encrypted with `public_key`.
+#[derive(Debug)]
+pub struct EncryptionRequestC<'a> {
+    pub server_id: Bounded<&'a str, 20>,
+    pub public_key: &'a [u8],
+    pub verify_token: &'a [u8],
+    pub should_authenticate: bool,
+}
+
+impl Packet for EncryptionRequestC<'_> {
+    const ID: i32 = 0x01;
+}
+
+impl<'a> Encode for EncryptionRequestC<'a> {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        self.server_id.encode(&mut w)?;
+        VarInt(self.public_key.len() as i32).encode(&mut w)?;
+        Bytes(self.public_key).encode(&mut w)?;
+        VarInt(self.verify_token.len() as i32).encode(&mut w)?;
+        Bytes(self.verify_token).encode(&mut w)?;
+        self.should_authenticate.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct EncryptionResponseS<'a> {
+    pub shared_secret: &'a [u8],
+    pub verify_token: &'a [u8],
+}
+
+impl Packet for EncryptionResponseS<'_> {
+    const ID: i32 = 0x01;
+}
+
+impl<'a> Decode<'a> for EncryptionResponseS<'a> {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(Self {
+            shared_secret: decode_prefixed_bytes(r)?,
+            verify_token: decode_prefixed_bytes(r)?,
+        })
+    }
+}
+
+fn decode_prefixed_bytes<'a>(r: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = VarInt::decode(r)?.0;
+    ensure!(
+        0 <= len && len as usize <= r.len(),
+        "byte array length {len} is out of bounds (max {})",
+        r.len()
+    );
+
+    let (bytes, rest) = r.split_at(len as usize);
+    *r = rest;
+    Ok(bytes)
+}
+
 #[derive(Debug)]
 pub struct PluginRequestC<'a> {
     pub message_id: VarInt,
@@ -94,7 +153,7 @@ impl<'a> Encode for PluginRequestC<'a> {
 #[derive(Debug)]
 pub struct PluginResponseS<'a> {
     pub message_id: VarInt,
-    pub data: Option<Rest<Bytes<'a>, 1048576>>
+    pub data: OptionalPrefixed<Rest<Bytes<'a>, 1048576>>
 }
 
 impl Packet for PluginResponseS<'_> {
@@ -105,7 +164,7 @@ impl<'a> Decode<'a> for PluginResponseS<'a> {
     fn decode(r: &mut &'a [u8]) -> Result<Self> {
         Ok(Self {
             message_id: VarInt::decode(r)?,
-            data: if bool::decode(r)? { Some(Rest::<Bytes<'a>, 1048576>::decode(r)?) } else { None }
+            data: OptionalPrefixed::decode(r)?,
         })
     }
 }
@@ -122,3 +181,39 @@ impl Decode<'_> for LoginAckS {
         Ok(Self)
     }
 }
+
+/// Turns on compression for every packet after this one, in both directions. Packets at least
+/// `threshold` bytes big get compressed.
+#[derive(Debug)]
+pub struct SetCompressionC {
+    pub threshold: VarInt,
+}
+
+impl Packet for SetCompressionC {
+    const ID: i32 = 0x03;
+}
+
+impl Encode for SetCompressionC {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        self.threshold.encode(&mut w)
+    }
+}
+
+/// Disconnects a client during login, showing them the reason. Unlike in play, the reason is
+/// sent as JSON.
+#[derive(Debug)]
+pub struct LoginDisconnectC {
+    pub reason: TextComponent,
+}
+
+impl Packet for LoginDisconnectC {
+    const ID: i32 = 0x00;
+}
+
+impl Encode for LoginDisconnectC {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        Bounded::<&str, 262144>(&self.reason.to_json()?).encode(&mut w)?;
+
+        Ok(())
+    }
+}
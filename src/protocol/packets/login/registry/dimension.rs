@@ -88,6 +88,18 @@ struct WeightedProvider {
     weight: i32,
 }
 
+impl DimensionType {
+    /// The lowest block y in the dimension.
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    /// How many blocks tall the dimension is, starting from [`DimensionType::min_y`].
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+}
+
 impl RegistryItem for DimensionType {
     const ID: &str = "minecraft:dimension_type";
 }
@@ -19,6 +19,7 @@
 
 use std::{collections::HashMap, sync::LazyLock};
 
+use color_eyre::eyre::eyre;
 use fastnbt::SerOpts;
 use serde::{de, Deserialize, Serialize};
 
@@ -42,10 +43,16 @@ pub use painting::*;
 pub use trim::*;
 pub use wolf::*;
 
-pub static ALL_REGISTRIES: LazyLock<AllRegistries> = LazyLock::new(|| {
-    serde_json::from_str(include_str!("../../../../../assets/registries.json"))
-        .expect("registries.json should be parseable")
-});
+static ALL_REGISTRIES: LazyLock<Result<AllRegistries, serde_json::Error>> =
+    LazyLock::new(|| serde_json::from_str(include_str!("../../../../../assets/registries.json")));
+
+/// Parses the bundled registries.json, or returns why it couldn't be parsed. This is forced at
+/// startup so a broken file fails before anyone connects.
+pub fn all_registries() -> color_eyre::eyre::Result<&'static AllRegistries> {
+    ALL_REGISTRIES
+        .as_ref()
+        .map_err(|why| eyre!("registries.json is not parseable: {why}"))
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Registry<T: RegistryItem> {
@@ -54,13 +61,16 @@ pub struct Registry<T: RegistryItem> {
 }
 
 impl<T: RegistryItem> Registry<T> {
-    pub fn index_of(&self, id: &str) -> i32 {
+    /// Finds the index of `id` in this registry, or `None` if it isn't present.
+    pub fn try_index_of(&self, id: &str) -> Option<i32> {
         self.entries
             .iter()
             .position(|e| e.id == id)
-            .unwrap_or_else(|| panic!("Element {id} should be in registry {}!", self.registry_id))
-            .try_into()
-            .unwrap()
+            .and_then(|i| i.try_into().ok())
+    }
+
+    pub fn registry_id(&self) -> &str {
+        &self.registry_id
     }
 }
 
@@ -87,11 +97,25 @@ where
 
         for RegistryEntry { id, entry } in &self.entries {
             id.encode(&mut w)?;
-            match entry {
+
+            // an entry without data falls back to the client's copy from the known packs, which
+            // is better than failing the whole registry
+            let data = entry.as_ref().and_then(|e| match e.network_nbt() {
+                Ok(data) => Some(data),
+                Err(why) => {
+                    warn!(
+                        "Failed to serialize {id} in {}, sending it without data: {why}",
+                        T::ID
+                    );
+                    None
+                }
+            });
+
+            match data {
                 None => false.encode(&mut w)?,
-                Some(e) => {
+                Some(data) => {
                     true.encode(&mut w)?;
-                    e.encode(&mut w)?;
+                    data.encode(&mut w)?;
                 }
             }
         }
@@ -104,8 +128,8 @@ where
 pub trait RegistryItem: Serialize + Sized + Clone {
     const ID: &str;
 
-    fn encode(&self, w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
-        fastnbt::to_bytes_with_opts(self, SerOpts::network_nbt())?.encode(w)
+    fn network_nbt(&self) -> color_eyre::eyre::Result<Vec<u8>> {
+        Ok(fastnbt::to_bytes_with_opts(self, SerOpts::network_nbt())?)
     }
 }
 
@@ -133,13 +157,17 @@ pub struct AllRegistries {
 
 impl<T: RegistryItem> From<HashMap<String, T>> for Registry<T> {
     fn from(value: HashMap<String, T>) -> Self {
-        let entries = value
+        let mut entries = value
             .into_iter()
             .map(|(k, v)| RegistryEntry {
                 id: k,
                 entry: Some(v),
             })
-            .collect();
+            .collect::<Vec<_>>();
+
+        // HashMap iteration order changes between runs, so sort to keep indices (e.g. the_end)
+        // the same every time
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
 
         Self {
             registry_id: T::ID.to_owned(),
@@ -0,0 +1,229 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use color_eyre::eyre::Result;
+
+use crate::protocol::{
+    datatypes::{Bounded, Slot, TextComponent, VarInt},
+    Encode, Packet,
+};
+
+/// Adds, removes and updates progress on advancements. Crawlspace has no real advancements, so
+/// this is only used to pop up toasts.
+#[derive(Debug)]
+pub struct UpdateAdvancementsC {
+    /// Whether to clear every advancement first. Clients don't show toasts for updates that reset.
+    pub reset: bool,
+    pub added: Vec<(String, Advancement)>,
+    pub removed: Vec<String>,
+    /// Each advancement's criteria, and when they were achieved (in ms since the epoch) if they
+    /// have been.
+    pub progress: Vec<(String, Vec<(String, Option<i64>)>)>,
+}
+
+#[derive(Debug)]
+pub struct Advancement {
+    pub parent: Option<String>,
+    pub display: Option<AdvancementDisplay>,
+    /// Groups of criteria, where one criterion from every group has to be met.
+    pub requirements: Vec<Vec<String>>,
+    pub sends_telemetry: bool,
+}
+
+#[derive(Debug)]
+pub struct AdvancementDisplay {
+    pub title: TextComponent,
+    pub description: TextComponent,
+    pub icon: Slot,
+    pub frame: AdvancementFrame,
+    pub show_toast: bool,
+    /// Hidden advancements don't show up in the advancements screen until they're done.
+    pub hidden: bool,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The border around an advancement's icon, which also picks the toast's heading.
+#[derive(Debug, Clone, Copy)]
+#[repr(i32)]
+pub enum AdvancementFrame {
+    Task,
+    Challenge,
+    Goal,
+}
+
+impl Packet for UpdateAdvancementsC {
+    const ID: i32 = 0x74;
+}
+
+impl Encode for UpdateAdvancementsC {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        self.reset.encode(&mut w)?;
+
+        VarInt(self.added.len() as i32).encode(&mut w)?;
+        for (id, advancement) in &self.added {
+            Bounded::<&str>(id).encode(&mut w)?;
+            advancement.encode(&mut w)?;
+        }
+
+        VarInt(self.removed.len() as i32).encode(&mut w)?;
+        for id in &self.removed {
+            Bounded::<&str>(id).encode(&mut w)?;
+        }
+
+        VarInt(self.progress.len() as i32).encode(&mut w)?;
+        for (id, criteria) in &self.progress {
+            Bounded::<&str>(id).encode(&mut w)?;
+            VarInt(criteria.len() as i32).encode(&mut w)?;
+            for (criterion, achieved) in criteria {
+                Bounded::<&str>(criterion).encode(&mut w)?;
+                achieved.is_some().encode(&mut w)?;
+                if let Some(achieved) = achieved {
+                    achieved.encode(&mut w)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Encode for Advancement {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        self.parent.is_some().encode(&mut w)?;
+        if let Some(ref parent) = self.parent {
+            Bounded::<&str>(parent).encode(&mut w)?;
+        }
+
+        self.display.is_some().encode(&mut w)?;
+        if let Some(ref display) = self.display {
+            display.encode(&mut w)?;
+        }
+
+        VarInt(self.requirements.len() as i32).encode(&mut w)?;
+        for group in &self.requirements {
+            VarInt(group.len() as i32).encode(&mut w)?;
+            for criterion in group {
+                Bounded::<&str>(criterion).encode(&mut w)?;
+            }
+        }
+
+        self.sends_telemetry.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+impl Encode for AdvancementDisplay {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        fastnbt::to_bytes_with_opts(&self.title, fastnbt::SerOpts::network_nbt())?
+            .encode(&mut w)?;
+        fastnbt::to_bytes_with_opts(&self.description, fastnbt::SerOpts::network_nbt())?
+            .encode(&mut w)?;
+        self.icon.encode(&mut w)?;
+        VarInt(self.frame as i32).encode(&mut w)?;
+
+        // 0x01 would mean a background texture follows, which only root advancements in the
+        // advancements screen need
+        let flags = (self.show_toast as i32) << 1 | (self.hidden as i32) << 2;
+        flags.encode(&mut w)?;
+
+        self.x.encode(&mut w)?;
+        self.y.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+impl UpdateAdvancementsC {
+    /// Grants a hidden, display-only advancement with a toast, which makes the client pop the
+    /// toast up. Follow it with [`UpdateAdvancementsC::remove`] so they don't pile up.
+    pub fn toast(id: &str, icon: Slot, title: TextComponent, frame: AdvancementFrame) -> Self {
+        let criterion = "shown".to_owned();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+
+        Self {
+            reset: false,
+            added: vec![(
+                id.to_owned(),
+                Advancement {
+                    parent: None,
+                    display: Some(AdvancementDisplay {
+                        title,
+                        description: TextComponent::default(),
+                        icon,
+                        frame,
+                        show_toast: true,
+                        hidden: true,
+                        x: 0.0,
+                        y: 0.0,
+                    }),
+                    requirements: vec![vec![criterion.clone()]],
+                    sends_telemetry: false,
+                },
+            )],
+            removed: Vec::new(),
+            progress: vec![(id.to_owned(), vec![(criterion, Some(now))])],
+        }
+    }
+
+    pub fn remove(id: &str) -> Self {
+        Self {
+            reset: false,
+            added: Vec::new(),
+            removed: vec![id.to_owned()],
+            progress: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toasts_are_displayed_and_already_done() {
+        let toast = UpdateAdvancementsC::toast(
+            "crawlspace:toast/test",
+            Slot::default(),
+            "Hi".into(),
+            AdvancementFrame::Goal,
+        );
+
+        let [(id, advancement)] = &toast.added[..] else {
+            panic!("a toast should add exactly one advancement");
+        };
+        assert_eq!(id, "crawlspace:toast/test");
+        let display = advancement.display.as_ref().unwrap();
+        assert!(display.show_toast);
+
+        // every criterion it needs has been achieved
+        let [(progress_id, criteria)] = &toast.progress[..] else {
+            panic!("a toast should come with its progress");
+        };
+        assert_eq!(progress_id, id);
+        for group in &advancement.requirements {
+            assert!(group
+                .iter()
+                .any(|c| criteria.iter().any(|(done, at)| done == c && at.is_some())));
+        }
+    }
+}
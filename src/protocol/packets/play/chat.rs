@@ -0,0 +1,187 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use color_eyre::eyre::ensure;
+
+use crate::protocol::{
+    datatypes::{Bounded, Bytes, OptionalPrefixed, TextComponent, VarInt},
+    Decode, Encode, Packet,
+};
+
+#[derive(Debug)]
+pub struct ChatCommandS<'a> {
+    /// The command, without the leading slash.
+    pub command: Bounded<&'a str, 256>,
+}
+
+impl Packet for ChatCommandS<'_> {
+    const ID: i32 = 0x04;
+}
+
+impl<'a> Decode<'a> for ChatCommandS<'a> {
+    fn decode(r: &mut &'a [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        // the timestamp, salt, argument signatures and acknowledgements follow, but we don't
+        // verify signed commands so there's no need to read them
+        Ok(Self {
+            command: Bounded::decode(r)?,
+        })
+    }
+}
+
+/// A chat message. The client is meant to cap these at 256 characters, but that isn't enforced
+/// here, so callers should truncate.
+#[derive(Debug)]
+pub struct ChatMessageS<'a> {
+    pub message: Bounded<&'a str>,
+    pub timestamp: i64,
+    pub salt: i64,
+    /// We don't verify signatures, but they still need reading past.
+    pub signature: OptionalPrefixed<MessageSignature<'a>>,
+    pub message_count: VarInt,
+    /// Which of the last 20 messages the client has seen, as a fixed 20 bit set.
+    pub acknowledged: Bytes<'a>,
+}
+
+impl Packet for ChatMessageS<'_> {
+    const ID: i32 = 0x06;
+}
+
+/// Signatures are always 256 bytes.
+const SIGNATURE_LENGTH: usize = 256;
+
+/// 20 bits, rounded up to whole bytes.
+const ACKNOWLEDGED_LENGTH: usize = 3;
+
+/// A message signature, which has a fixed length rather than a length prefix.
+#[derive(Debug)]
+pub struct MessageSignature<'a>(pub Bytes<'a>);
+
+impl<'a> Decode<'a> for MessageSignature<'a> {
+    fn decode(r: &mut &'a [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(take(r, SIGNATURE_LENGTH)?))
+    }
+}
+
+/// Splits `len` bytes off the front of `r`.
+fn take<'a>(r: &mut &'a [u8], len: usize) -> color_eyre::eyre::Result<Bytes<'a>> {
+    ensure!(
+        len <= r.len(),
+        "malformed packet - not enough data to continue decoding (expected {len} got {})",
+        r.len(),
+    );
+
+    let (bytes, rest) = r.split_at(len);
+    *r = rest;
+    Ok(Bytes(bytes))
+}
+
+impl<'a> Decode<'a> for ChatMessageS<'a> {
+    fn decode(r: &mut &'a [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            message: Bounded::decode(r)?,
+            timestamp: i64::decode(r)?,
+            salt: i64::decode(r)?,
+            signature: OptionalPrefixed::decode(r)?,
+            message_count: VarInt::decode(r)?,
+            acknowledged: take(r, ACKNOWLEDGED_LENGTH)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SystemChatMessageC {
+    pub content: TextComponent,
+    /// Whether to show the message above the hotbar instead of in chat.
+    pub overlay: bool,
+}
+
+impl Packet for SystemChatMessageC {
+    const ID: i32 = 0x6C;
+}
+
+impl Encode for SystemChatMessageC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        fastnbt::to_bytes_with_opts(&self.content, fastnbt::SerOpts::network_nbt())?
+            .encode(&mut w)?;
+        self.overlay.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_a_colored_system_message() {
+        let mut body = Vec::new();
+        SystemChatMessageC {
+            content: TextComponent::from("Hi").color("red"),
+            overlay: false,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        let expected = [
+            &[0x0A, 0x08, 0, 4][..],
+            b"text",
+            &[0, 2],
+            b"Hi",
+            &[0x08, 0, 5],
+            b"color",
+            &[0, 3],
+            b"red",
+            // end of the compound, then not an overlay
+            &[0x00, 0x00],
+        ]
+        .concat();
+        assert_eq!(body, expected);
+    }
+
+    #[test]
+    fn reads_past_a_signature() {
+        let body = [
+            &[2, b'h', b'i'][..],
+            &[0; 16],
+            // signed, then the fixed length signature
+            &[1],
+            &[0xAB; SIGNATURE_LENGTH],
+            &[0, 0xFF, 0xFF, 0x0F],
+        ]
+        .concat();
+
+        let packet = ChatMessageS::decode(&mut &body[..]).unwrap();
+        assert_eq!(packet.message.0, "hi");
+        assert!(packet
+            .signature
+            .0
+            .is_some_and(|s| s.0 .0 == [0xAB; SIGNATURE_LENGTH]));
+        assert_eq!(packet.acknowledged.0, [0xFF, 0xFF, 0x0F]);
+    }
+}
@@ -0,0 +1,120 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use crate::protocol::{datatypes::VarInt, Decode, Encode, Packet};
+
+#[derive(Debug)]
+pub struct ClientStatusS {
+    pub action: ClientStatusAction,
+}
+
+#[derive(Debug)]
+pub enum ClientStatusAction {
+    /// Sent when the client is ready to respawn after death.
+    PerformRespawn,
+    /// Sent when the client opens the statistics menu.
+    RequestStats,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClientStatusActionParseError {
+    #[error("Got unexpected client status action {0}")]
+    Unexpected(i32),
+}
+
+impl TryFrom<VarInt> for ClientStatusAction {
+    type Error = ClientStatusActionParseError;
+
+    fn try_from(value: VarInt) -> Result<Self, Self::Error> {
+        match value.0 {
+            0 => Ok(ClientStatusAction::PerformRespawn),
+            1 => Ok(ClientStatusAction::RequestStats),
+            i => Err(ClientStatusActionParseError::Unexpected(i)),
+        }
+    }
+}
+
+impl Packet for ClientStatusS {
+    const ID: i32 = 0x09;
+}
+
+impl Decode<'_> for ClientStatusS {
+    fn decode(r: &mut &'_ [u8]) -> color_eyre::eyre::Result<Self> {
+        Ok(Self {
+            action: VarInt::decode(r)?.try_into()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct AwardStatisticsC {
+    pub statistics: Vec<Statistic>,
+}
+
+#[derive(Debug)]
+pub struct Statistic {
+    pub category_id: VarInt,
+    pub statistic_id: VarInt,
+    pub value: VarInt,
+}
+
+impl Packet for AwardStatisticsC {
+    const ID: i32 = 0x04;
+}
+
+impl Encode for AwardStatisticsC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.statistics.len() as i32).encode(&mut w)?;
+
+        for statistic in &self.statistics {
+            statistic.category_id.encode(&mut w)?;
+            statistic.statistic_id.encode(&mut w)?;
+            statistic.value.encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(body: &[u8]) -> color_eyre::eyre::Result<ClientStatusAction> {
+        Ok(ClientStatusS::decode(&mut &body[..])?.action)
+    }
+
+    #[test]
+    fn decodes_actions() {
+        assert!(matches!(
+            decode(&[0]).unwrap(),
+            ClientStatusAction::PerformRespawn
+        ));
+        assert!(matches!(
+            decode(&[1]).unwrap(),
+            ClientStatusAction::RequestStats
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_actions() {
+        assert!(decode(&[2]).is_err());
+        assert!(decode(&[]).is_err());
+    }
+}
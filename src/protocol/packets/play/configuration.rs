@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use color_eyre::eyre::Result;
+
+use crate::protocol::{Decode, Encode, Packet};
+
+/// Sends the client back to the configuration phase, e.g. to resend registries. The client
+/// answers with [`ConfigurationAckS`] once it's switched over.
+#[derive(Debug)]
+pub struct StartConfigurationC;
+
+impl Packet for StartConfigurationC {
+    const ID: i32 = 0x69;
+}
+
+impl Encode for StartConfigurationC {
+    fn encode(&self, _w: impl std::io::Write) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigurationAckS;
+
+impl Packet for ConfigurationAckS {
+    const ID: i32 = 0x0C;
+}
+
+impl<'a> Decode<'a> for ConfigurationAckS {
+    fn decode(_r: &mut &'a [u8]) -> Result<Self> {
+        Ok(Self)
+    }
+}
@@ -17,10 +17,13 @@
  * <https://www.gnu.org/licenses/>.
  */
 
+use byteorder::{BigEndian, ReadBytesExt};
+use color_eyre::eyre::bail;
+
 use crate::{
     protocol::{
         datatypes::{Slot, TextComponent, VarInt},
-        Encode, Packet,
+        Decode, Encode, Packet,
     },
     server::window::{Window, WindowType},
 };
@@ -84,3 +87,367 @@ impl Encode for SetContainerContentC {
         Ok(())
     }
 }
+
+#[derive(Debug)]
+pub struct SetContainerSlotC {
+    pub window_id: i8,
+    pub state_id: i32,
+    pub slot: i16,
+    pub slot_data: Slot,
+}
+
+impl Packet for SetContainerSlotC {
+    const ID: i32 = 0x15;
+}
+
+impl Encode for SetContainerSlotC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.window_id.encode(&mut w)?;
+        VarInt(self.state_id).encode(&mut w)?;
+        self.slot.encode(&mut w)?;
+        self.slot_data.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+impl SetContainerSlotC {
+    /// Sets the item the player is holding on their cursor. 1.21.2 has a separate packet for
+    /// this, but in 1.21.1 the cursor is slot -1 of window -1.
+    pub fn cursor(item: Slot) -> Self {
+        Self {
+            window_id: -1,
+            state_id: 0,
+            slot: -1,
+            slot_data: item,
+        }
+    }
+
+    /// Sets a slot in the player's own inventory, whatever window they have open. 1.21.2 has a
+    /// separate packet for this too, but in 1.21.1 it's window -2. Slots are inventory indexes,
+    /// not window slots: 0-8 is the hotbar, 9-35 the rest, 36-39 armor and 40 the offhand.
+    pub fn player_inventory(slot: i16, item: Slot) -> Self {
+        Self {
+            window_id: -2,
+            state_id: 0,
+            slot,
+            slot_data: item,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ClickContainerS {
+    pub window_id: u8,
+    pub state_id: VarInt,
+    pub slot: i16,
+    pub button: i8,
+    pub mode: VarInt,
+}
+
+impl Packet for ClickContainerS {
+    const ID: i32 = 0x0E;
+}
+
+impl Decode<'_> for ClickContainerS {
+    fn decode(r: &mut &'_ [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        // the changed slots and carried item follow, but we never trust the client's view of a
+        // container anyway
+        Ok(Self {
+            window_id: r.read_u8()?,
+            state_id: VarInt::decode(r)?,
+            slot: r.read_i16::<BigEndian>()?,
+            button: r.read_i8()?,
+            mode: VarInt::decode(r)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct CloseContainerC {
+    pub window_id: u8,
+}
+
+impl Packet for CloseContainerC {
+    const ID: i32 = 0x12;
+}
+
+impl Encode for CloseContainerC {
+    fn encode(&self, w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.window_id.encode(w)
+    }
+}
+
+#[derive(Debug)]
+pub struct CloseContainerS {
+    pub window_id: u8,
+}
+
+impl Packet for CloseContainerS {
+    const ID: i32 = 0x0F;
+}
+
+impl Decode<'_> for CloseContainerS {
+    fn decode(r: &mut &'_ [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            window_id: r.read_u8()?,
+        })
+    }
+}
+
+/// A single fixed trade shown in a merchant window.
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub input_1: Slot,
+    pub input_2: Option<Slot>,
+    pub output: Slot,
+    pub disabled: bool,
+    pub uses: i32,
+    pub max_uses: i32,
+    pub xp: i32,
+    pub special_price: i32,
+    pub price_multiplier: f32,
+    pub demand: i32,
+}
+
+impl Trade {
+    /// A trade that never runs out and doesn't change price.
+    pub fn new(input_1: Slot, input_2: Option<Slot>, output: Slot) -> Self {
+        Self {
+            input_1,
+            input_2,
+            output,
+            disabled: false,
+            uses: 0,
+            max_uses: i32::MAX,
+            xp: 0,
+            special_price: 0,
+            price_multiplier: 0.0,
+            demand: 0,
+        }
+    }
+}
+
+/// Trade inputs aren't full slots: just an id, a count, and the components the offered item
+/// has to match (which we never set).
+fn encode_trade_item(slot: &Slot, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+    let Some(item_id) = slot.item_id() else {
+        bail!("trade inputs can't be empty");
+    };
+
+    VarInt(item_id).encode(&mut w)?;
+    VarInt(slot.count() as i32).encode(&mut w)?;
+    VarInt(0).encode(&mut w)?;
+
+    Ok(())
+}
+
+impl Encode for Trade {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        encode_trade_item(&self.input_1, &mut w)?;
+        self.output.encode(&mut w)?;
+
+        match self.input_2 {
+            None => false.encode(&mut w)?,
+            Some(ref input_2) => {
+                true.encode(&mut w)?;
+                encode_trade_item(input_2, &mut w)?;
+            }
+        }
+
+        self.disabled.encode(&mut w)?;
+        self.uses.encode(&mut w)?;
+        self.max_uses.encode(&mut w)?;
+        self.xp.encode(&mut w)?;
+        self.special_price.encode(&mut w)?;
+        self.price_multiplier.encode(&mut w)?;
+        self.demand.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct MerchantOffersC {
+    pub window_id: i32,
+    pub trades: Vec<Trade>,
+    pub villager_level: i32,
+    pub experience: i32,
+    /// Whether to show the villager's level and experience bar.
+    pub is_regular_villager: bool,
+    pub can_restock: bool,
+}
+
+impl Packet for MerchantOffersC {
+    const ID: i32 = 0x2D;
+}
+
+impl Encode for MerchantOffersC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.window_id).encode(&mut w)?;
+        VarInt(self.trades.len() as i32).encode(&mut w)?;
+        self.trades.encode(&mut w)?;
+        VarInt(self.villager_level).encode(&mut w)?;
+        VarInt(self.experience).encode(&mut w)?;
+        self.is_regular_villager.encode(&mut w)?;
+        self.can_restock.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server::registries::registries, world::Item};
+
+    fn item(id: &str, count: i32) -> Slot {
+        Item {
+            slot: 0,
+            id: id.to_owned(),
+            count,
+        }
+        .into()
+    }
+
+    fn item_id(id: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        VarInt(registries().item.entries[id].protocol_id)
+            .encode(&mut buf)
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn encodes_a_trade_with_two_inputs() {
+        let trade = Trade {
+            uses: 2,
+            max_uses: 10,
+            xp: 3,
+            price_multiplier: 0.5,
+            ..Trade::new(
+                item("minecraft:emerald", 5),
+                Some(item("minecraft:book", 1)),
+                item("minecraft:enchanted_book", 1),
+            )
+        };
+
+        let mut body = Vec::new();
+        MerchantOffersC {
+            window_id: 1,
+            trades: vec![trade],
+            villager_level: 1,
+            experience: 0,
+            is_regular_villager: false,
+            can_restock: false,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        let expected = [
+            &[1, 1][..],
+            // first input, with no components to match
+            &item_id("minecraft:emerald"),
+            &[5, 0],
+            // the output is a whole slot
+            &[1],
+            &item_id("minecraft:enchanted_book"),
+            &[0, 0],
+            // second input
+            &[1],
+            &item_id("minecraft:book"),
+            &[1, 0],
+            // disabled, uses, max uses, xp, special price
+            &[0],
+            &2i32.to_be_bytes(),
+            &10i32.to_be_bytes(),
+            &3i32.to_be_bytes(),
+            &0i32.to_be_bytes(),
+            // price multiplier, demand
+            &0.5f32.to_be_bytes(),
+            &0i32.to_be_bytes(),
+            // level, experience, regular villager, can restock
+            &[1, 0, 0, 0],
+        ]
+        .concat();
+
+        assert_eq!(body, expected);
+    }
+
+    #[test]
+    fn encodes_a_styled_window_title() {
+        let window = Window {
+            id: 1,
+            kind: WindowType::Generic9x3,
+            title: TextComponent::from("Loot").color("gold").bold(true),
+            slots: Vec::new(),
+        };
+
+        let mut body = Vec::new();
+        OpenScreenC::from(&window).encode(&mut body).unwrap();
+
+        let expected = [
+            &[1, 2][..],
+            // network NBT has no name on the root compound
+            &[0x0A],
+            &[0x08, 0, 4],
+            b"text",
+            &[0, 4],
+            b"Loot",
+            &[0x08, 0, 5],
+            b"color",
+            &[0, 4],
+            b"gold",
+            &[0x01, 0, 4],
+            b"bold",
+            &[1, 0x00],
+        ]
+        .concat();
+        assert_eq!(body, expected);
+    }
+
+    #[test]
+    fn encodes_the_cursor_item() {
+        let mut empty = Vec::new();
+        SetContainerSlotC::cursor(Slot::default())
+            .encode(&mut empty)
+            .unwrap();
+        // window -1, state 0, slot -1, then an empty slot
+        assert_eq!(empty, [0xFF, 0, 0xFF, 0xFF, 0]);
+
+        let mut present = Vec::new();
+        SetContainerSlotC::cursor(item("minecraft:stone", 3))
+            .encode(&mut present)
+            .unwrap();
+        let expected = [
+            &[0xFF, 0, 0xFF, 0xFF, 3][..],
+            &item_id("minecraft:stone"),
+            &[0, 0],
+        ]
+        .concat();
+        assert_eq!(present, expected);
+    }
+
+    #[test]
+    fn encodes_a_player_inventory_slot() {
+        let mut body = Vec::new();
+        SetContainerSlotC::player_inventory(4, item("minecraft:compass", 1))
+            .encode(&mut body)
+            .unwrap();
+
+        // window -2, state 0, hotbar slot 4, then the compass
+        let expected = [
+            &[0xFE, 0, 0, 4, 1][..],
+            &item_id("minecraft:compass"),
+            &[0, 0],
+        ]
+        .concat();
+        assert_eq!(body, expected);
+    }
+}
@@ -0,0 +1,41 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use color_eyre::eyre::Result;
+
+use crate::protocol::{datatypes::TextComponent, Encode, Packet};
+
+/// Disconnects a player in play, showing them the reason.
+#[derive(Debug)]
+pub struct DisconnectC {
+    pub reason: TextComponent,
+}
+
+impl Packet for DisconnectC {
+    const ID: i32 = 0x1D;
+}
+
+impl Encode for DisconnectC {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        fastnbt::to_bytes_with_opts(&self.reason, fastnbt::SerOpts::network_nbt())?
+            .encode(&mut w)?;
+
+        Ok(())
+    }
+}
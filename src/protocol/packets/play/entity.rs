@@ -0,0 +1,504 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use color_eyre::eyre::{eyre, Result};
+use uuid::Uuid;
+
+use crate::{
+    protocol::{
+        datatypes::{Angle, Metadata, Slot, VarInt},
+        Encode, Packet,
+    },
+    server::registries::registries,
+};
+
+#[derive(Debug)]
+pub struct UpdateEntityPositionC {
+    pub entity_id: i32,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub on_ground: bool,
+}
+
+impl Packet for UpdateEntityPositionC {
+    const ID: i32 = 0x2E;
+}
+
+impl Encode for UpdateEntityPositionC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+        self.delta_x.encode(&mut w)?;
+        self.delta_y.encode(&mut w)?;
+        self.delta_z.encode(&mut w)?;
+        self.on_ground.encode(&mut w)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct UpdateEntityPositionAndRotationC {
+    pub entity_id: i32,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl Packet for UpdateEntityPositionAndRotationC {
+    const ID: i32 = 0x2F;
+}
+
+impl Encode for UpdateEntityPositionAndRotationC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+        self.delta_x.encode(&mut w)?;
+        self.delta_y.encode(&mut w)?;
+        self.delta_z.encode(&mut w)?;
+        Angle(self.yaw).encode(&mut w)?;
+        Angle(self.pitch).encode(&mut w)?;
+        self.on_ground.encode(&mut w)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct UpdateEntityRotationC {
+    pub entity_id: i32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl Packet for UpdateEntityRotationC {
+    const ID: i32 = 0x30;
+}
+
+impl Encode for UpdateEntityRotationC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+        Angle(self.yaw).encode(&mut w)?;
+        Angle(self.pitch).encode(&mut w)?;
+        self.on_ground.encode(&mut w)?;
+        Ok(())
+    }
+}
+
+/// Turns an entity's head. Entity rotation packets only turn the body, so players need this too
+/// for their head to follow where they're looking.
+#[derive(Debug)]
+pub struct SetHeadRotationC {
+    pub entity_id: i32,
+    pub head_yaw: f32,
+}
+
+impl Packet for SetHeadRotationC {
+    const ID: i32 = 0x48;
+}
+
+impl Encode for SetHeadRotationC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+        Angle(self.head_yaw).encode(&mut w)?;
+        Ok(())
+    }
+}
+
+/// Starts or ends a bundle. The client holds every packet between a pair of these and applies
+/// them together in one tick, so an entity never shows up half set up.
+#[derive(Debug)]
+pub struct BundleDelimiterC;
+
+impl Packet for BundleDelimiterC {
+    const ID: i32 = 0x00;
+}
+
+impl Encode for BundleDelimiterC {
+    fn encode(&self, _w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        Ok(())
+    }
+}
+
+/// Moves an entity to an absolute position. Used when a move is too large to send as a delta.
+#[derive(Debug)]
+pub struct TeleportEntityC {
+    pub entity_id: i32,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl Packet for TeleportEntityC {
+    const ID: i32 = 0x70;
+}
+
+impl Encode for TeleportEntityC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+        self.x.encode(&mut w)?;
+        self.y.encode(&mut w)?;
+        self.z.encode(&mut w)?;
+        Angle(self.yaw).encode(&mut w)?;
+        Angle(self.pitch).encode(&mut w)?;
+        self.on_ground.encode(&mut w)?;
+        Ok(())
+    }
+}
+
+/// Locks the client's view to another entity, as if spectating it. The entity must already have
+/// been spawned for the client, or this is ignored. Set the camera to the player's own entity id
+/// to release it.
+#[derive(Debug)]
+pub struct SetCameraC {
+    pub camera_id: VarInt,
+}
+
+impl Packet for SetCameraC {
+    const ID: i32 = 0x52;
+}
+
+impl Encode for SetCameraC {
+    fn encode(&self, w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.camera_id.encode(w)
+    }
+}
+
+#[derive(Debug)]
+pub struct SpawnEntityC {
+    pub entity_id: i32,
+    pub entity_uuid: Uuid,
+    /// The entity's protocol id in the `minecraft:entity_type` registry.
+    pub kind: i32,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub head_yaw: f32,
+    /// Depends on the entity type, e.g. the block state for falling blocks.
+    pub data: i32,
+    pub velocity_x: i16,
+    pub velocity_y: i16,
+    pub velocity_z: i16,
+}
+
+impl Packet for SpawnEntityC {
+    const ID: i32 = 0x01;
+}
+
+impl Encode for SpawnEntityC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+        self.entity_uuid.encode(&mut w)?;
+        VarInt(self.kind).encode(&mut w)?;
+        self.x.encode(&mut w)?;
+        self.y.encode(&mut w)?;
+        self.z.encode(&mut w)?;
+        Angle(self.pitch).encode(&mut w)?;
+        Angle(self.yaw).encode(&mut w)?;
+        Angle(self.head_yaw).encode(&mut w)?;
+        VarInt(self.data).encode(&mut w)?;
+        self.velocity_x.encode(&mut w)?;
+        self.velocity_y.encode(&mut w)?;
+        self.velocity_z.encode(&mut w)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct SetEntityMetadataC {
+    pub entity_id: i32,
+    /// Metadata values keyed by index. Which index means what depends on the entity type.
+    pub metadata: Vec<(u8, Metadata)>,
+}
+
+impl Packet for SetEntityMetadataC {
+    const ID: i32 = 0x58;
+}
+
+impl Encode for SetEntityMetadataC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+
+        for (index, value) in &self.metadata {
+            index.encode(&mut w)?;
+            value.encode(&mut w)?;
+        }
+
+        0xFFu8.encode(&mut w)
+    }
+}
+
+/// Shows what an entity is holding and wearing. Slots that aren't listed keep what they had.
+#[derive(Debug)]
+pub struct SetEquipmentC {
+    pub entity_id: i32,
+    /// Must have at least one entry.
+    pub equipment: Vec<(EquipmentSlot, Slot)>,
+}
+
+impl Packet for SetEquipmentC {
+    const ID: i32 = 0x5B;
+}
+
+impl Encode for SetEquipmentC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+
+        for (i, (slot, item)) in self.equipment.iter().enumerate() {
+            // the top bit says another entry follows
+            let more = if i + 1 < self.equipment.len() {
+                0x80
+            } else {
+                0
+            };
+            (u8::from(*slot) | more).encode(&mut w)?;
+            item.encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Boots,
+    Leggings,
+    Chestplate,
+    Helmet,
+    /// Horse and wolf armor.
+    Body,
+}
+
+impl From<EquipmentSlot> for u8 {
+    fn from(value: EquipmentSlot) -> Self {
+        match value {
+            EquipmentSlot::MainHand => 0,
+            EquipmentSlot::OffHand => 1,
+            EquipmentSlot::Boots => 2,
+            EquipmentSlot::Leggings => 3,
+            EquipmentSlot::Chestplate => 4,
+            EquipmentSlot::Helmet => 5,
+            EquipmentSlot::Body => 6,
+        }
+    }
+}
+
+/// Despawns entities, e.g. players that went out of view.
+#[derive(Debug)]
+pub struct RemoveEntitiesC {
+    pub entity_ids: Vec<i32>,
+}
+
+impl Packet for RemoveEntitiesC {
+    const ID: i32 = 0x42;
+}
+
+impl Encode for RemoveEntitiesC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.entity_ids.len() as i32).encode(&mut w)?;
+        for id in &self.entity_ids {
+            VarInt(*id).encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Flashes an entity red and tilts the camera (for players) as if it was hit from `yaw`.
+#[derive(Debug)]
+pub struct HurtAnimationC {
+    pub entity_id: i32,
+    /// The direction the hit came from, in degrees. 0 is straight ahead.
+    pub yaw: f32,
+}
+
+impl Packet for HurtAnimationC {
+    const ID: i32 = 0x24;
+}
+
+impl Encode for HurtAnimationC {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+        self.yaw.encode(&mut w)
+    }
+}
+
+/// Sets an entity's attributes, like scale, movement speed or max health. Attributes that aren't
+/// listed keep their current value.
+#[derive(Debug)]
+pub struct UpdateAttributesC {
+    pub entity_id: i32,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Packet for UpdateAttributesC {
+    const ID: i32 = 0x75;
+}
+
+impl Encode for UpdateAttributesC {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        VarInt(self.entity_id).encode(&mut w)?;
+        VarInt(self.attributes.len() as i32).encode(&mut w)?;
+
+        for attribute in &self.attributes {
+            VarInt(attribute.id).encode(&mut w)?;
+            attribute.base.encode(&mut w)?;
+
+            VarInt(attribute.modifiers.len() as i32).encode(&mut w)?;
+            for modifier in &attribute.modifiers {
+                modifier.id.encode(&mut w)?;
+                modifier.amount.encode(&mut w)?;
+                i8::from(modifier.operation).encode(&mut w)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Attribute {
+    /// The attribute's protocol id, from the attribute registry.
+    pub id: i32,
+    pub base: f64,
+    pub modifiers: Vec<AttributeModifier>,
+}
+
+impl Attribute {
+    /// Makes an attribute with no modifiers, e.g. `Attribute::new("minecraft:generic.scale", 2.0)`.
+    pub fn new(name: &str, base: f64) -> Result<Self> {
+        let id = registries()
+            .attribute
+            .entries
+            .get(name)
+            .ok_or_else(|| eyre!("Couldn't find registry entry for attribute {name}"))?
+            .protocol_id;
+
+        Ok(Self {
+            id,
+            base,
+            modifiers: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct AttributeModifier {
+    /// Identifies the modifier, so it can be replaced or removed later.
+    pub id: String,
+    pub amount: f64,
+    pub operation: AttributeOperation,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AttributeOperation {
+    /// Adds `amount` to the base value.
+    Add,
+    /// Adds `amount` times the base value.
+    MultiplyBase,
+    /// Multiplies the total by `1 + amount`.
+    MultiplyTotal,
+}
+
+impl From<AttributeOperation> for i8 {
+    fn from(value: AttributeOperation) -> Self {
+        match value {
+            AttributeOperation::Add => 0,
+            AttributeOperation::MultiplyBase => 1,
+            AttributeOperation::MultiplyTotal => 2,
+        }
+    }
+}
+
+/// How a display entity turns to face the player looking at it.
+#[derive(Debug, Clone, Copy)]
+pub enum Billboard {
+    Fixed,
+    Vertical,
+    Horizontal,
+    Center,
+}
+
+impl From<Billboard> for i8 {
+    fn from(value: Billboard) -> Self {
+        match value {
+            Billboard::Fixed => 0,
+            Billboard::Vertical => 1,
+            Billboard::Horizontal => 2,
+            Billboard::Center => 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hurt_yaw_is_a_float() {
+        let mut body = Vec::new();
+        HurtAnimationC {
+            entity_id: 300,
+            yaw: 90.0,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        assert_eq!(body, [0xAC, 0x02, 0x42, 0xB4, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn encodes_a_scale_of_two() {
+        let packet = UpdateAttributesC {
+            entity_id: 5,
+            attributes: vec![Attribute::new("minecraft:generic.scale", 2.0).unwrap()],
+        };
+
+        let mut body = Vec::new();
+        packet.encode(&mut body).unwrap();
+
+        // entity, one attribute (scale is 24), 2.0 as a big-endian double, no modifiers
+        assert_eq!(body, [5, 1, 24, 0x40, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn only_the_last_equipment_slot_ends_the_list() {
+        let mut body = Vec::new();
+        SetEquipmentC {
+            entity_id: 1,
+            equipment: vec![
+                (EquipmentSlot::MainHand, Slot::default()),
+                (EquipmentSlot::Helmet, Slot::default()),
+            ],
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        // entity 1, then each slot with an empty item
+        assert_eq!(body, [1, 0x80, 0, 5, 0]);
+    }
+}
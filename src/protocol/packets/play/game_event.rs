@@ -41,7 +41,6 @@ impl Encode for GameEventC {
 
 #[derive(Debug)]
 #[repr(u8)]
-#[expect(unused)]
 pub enum GameEvent {
     NoRespawnBlockAvailable,
     BeginRaining,
@@ -66,7 +65,6 @@ pub enum GameEvent {
 }
 
 #[derive(Debug)]
-#[expect(unused)]
 pub enum DemoCommand {
     ShowWelcome = 0,
     TellMovement = 101,
@@ -99,3 +97,19 @@ impl From<GameEvent> for GameEventC {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn win_game_is_event_4() {
+        for (roll_credits, value) in [(true, 1.0f32), (false, 0.0)] {
+            let mut buf = Vec::new();
+            GameEventC::from(GameEvent::WinGame(roll_credits))
+                .encode(&mut buf)
+                .unwrap();
+            assert_eq!(buf, [&[4][..], &value.to_be_bytes()].concat());
+        }
+    }
+}
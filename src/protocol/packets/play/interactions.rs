@@ -113,3 +113,129 @@ impl Decode<'_> for UseItemOnS {
         })
     }
 }
+
+#[derive(Debug)]
+pub struct InteractS {
+    pub entity_id: i32,
+    pub interaction: Interaction,
+    pub sneaking: bool,
+}
+
+#[derive(Debug)]
+pub enum Interaction {
+    Interact {
+        hand: Hand,
+    },
+    Attack,
+    /// Right clicking a specific point on the entity, relative to its position. Armor stands
+    /// care about this, most other entities get a plain [`Interaction::Interact`] alongside it.
+    InteractAt {
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+        hand: Hand,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum InteractionParseError {
+    #[error("Got unexpected interaction type {0}")]
+    Unexpected(i32),
+}
+
+impl Packet for InteractS {
+    const ID: i32 = 0x16;
+}
+
+impl Decode<'_> for InteractS {
+    fn decode(r: &mut &'_ [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        let entity_id = VarInt::decode(r)?.0;
+
+        // only interact at carries a target, and attacking has no hand
+        let interaction = match VarInt::decode(r)?.0 {
+            0 => Interaction::Interact {
+                hand: VarInt::decode(r)?.try_into()?,
+            },
+            1 => Interaction::Attack,
+            2 => Interaction::InteractAt {
+                target_x: r.read_f32::<BigEndian>()?,
+                target_y: r.read_f32::<BigEndian>()?,
+                target_z: r.read_f32::<BigEndian>()?,
+                hand: VarInt::decode(r)?.try_into()?,
+            },
+            i => return Err(InteractionParseError::Unexpected(i).into()),
+        };
+
+        Ok(Self {
+            entity_id,
+            interaction,
+            sneaking: bool::decode(r)?,
+        })
+    }
+}
+
+/// Sent when a player middle clicks a block whose item is already somewhere in their inventory.
+/// In 1.21.1 creative clients pick blocks themselves, so this only comes from survival.
+#[derive(Debug)]
+pub struct PickItemS {
+    pub slot_to_use: i32,
+}
+
+impl Packet for PickItemS {
+    const ID: i32 = 0x20;
+}
+
+impl Decode<'_> for PickItemS {
+    fn decode(r: &mut &'_ [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            slot_to_use: VarInt::decode(r)?.0,
+        })
+    }
+}
+
+/// Sent when the player scrolls to or presses a number for another hotbar slot.
+#[derive(Debug)]
+pub struct SetHeldItemS {
+    /// The hotbar slot, 0-8.
+    pub slot: i16,
+}
+
+impl Packet for SetHeldItemS {
+    const ID: i32 = 0x2F;
+}
+
+impl Decode<'_> for SetHeldItemS {
+    fn decode(r: &mut &'_ [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            slot: r.read_i16::<BigEndian>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_pick_item() {
+        let packet = PickItemS::decode(&mut &[0x14][..]).unwrap();
+        assert_eq!(packet.slot_to_use, 20);
+        assert!(PickItemS::decode(&mut &[0x80][..]).is_err());
+    }
+
+    #[test]
+    fn decodes_set_held_item() {
+        let packet = SetHeldItemS::decode(&mut &[0x00, 0x03][..]).unwrap();
+        assert_eq!(packet.slot, 3);
+        assert!(SetHeldItemS::decode(&mut &[0x00][..]).is_err());
+    }
+}
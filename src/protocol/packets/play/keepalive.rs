@@ -33,8 +33,7 @@ impl Encode for KeepAliveC {
 }
 
 #[derive(Debug)]
-#[expect(unused)]
-pub struct KeepAliveS(i64);
+pub struct KeepAliveS(pub i64);
 
 impl Packet for KeepAliveS {
     const ID: i32 = 0x18;
@@ -45,3 +44,42 @@ impl<'a> Decode<'a> for KeepAliveS {
         Ok(Self(i64::decode(r)?))
     }
 }
+
+/// Asks the client to reply with a [`PongS`] carrying the same id, for measuring latency.
+#[derive(Debug)]
+pub struct PingC(pub i32);
+
+impl Packet for PingC {
+    const ID: i32 = 0x35;
+}
+
+impl Encode for PingC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.0.encode(&mut w)
+    }
+}
+
+#[derive(Debug)]
+pub struct PongS(pub i32);
+
+impl Packet for PongS {
+    const ID: i32 = 0x27;
+}
+
+impl<'a> Decode<'a> for PongS {
+    fn decode(r: &mut &'a [u8]) -> color_eyre::eyre::Result<Self> {
+        Ok(Self(i32::decode(r)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_pong() {
+        let body = (-2i32).to_be_bytes();
+        let pong = PongS::decode(&mut &body[..]).unwrap();
+        assert_eq!(pong.0, -2);
+    }
+}
@@ -18,7 +18,7 @@
  */
 
 use crate::protocol::{
-    datatypes::{Bounded, Position, VarInt},
+    datatypes::{Bounded, Bytes, OptionalPrefixed, Position, TextComponent, VarInt},
     Encode, Packet,
 };
 
@@ -50,7 +50,7 @@ pub struct LoginPlayC<'a> {
     pub previous_gamemode: Option<Gamemode>,
     pub is_debug: bool,
     pub is_superflat: bool,
-    pub death_location: Option<DeathLocation<'a>>,
+    pub death_location: OptionalPrefixed<DeathLocation<'a>>,
     pub portal_cooldown: VarInt,
     pub enforces_secure_chat: bool,
 }
@@ -91,6 +91,15 @@ pub struct DeathLocation<'a> {
     death_location: Position,
 }
 
+impl Encode for DeathLocation<'_> {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.dimension_name.encode(&mut w)?;
+        self.death_location.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
 impl Packet for LoginPlayC<'_> {
     const ID: i32 = 0x2B;
 }
@@ -120,20 +129,121 @@ impl<'a> Encode for LoginPlayC<'a> {
         self.is_debug.encode(&mut w)?;
         self.is_superflat.encode(&mut w)?;
 
-        match &self.death_location {
-            Some(l) => {
-                true.encode(&mut w)?;
-                l.dimension_name.encode(&mut w)?;
-                l.death_location.encode(&mut w)?;
-            }
-            None => {
-                false.encode(&mut w)?;
-            }
+        self.death_location.encode(&mut w)?;
+
+        self.portal_cooldown.encode(&mut w)?;
+        self.enforces_secure_chat.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct RespawnC<'a> {
+    pub dimension_type: VarInt,
+    pub dimension_name: Bounded<&'a str>,
+    pub hashed_seed: i64,
+    pub gamemode: Gamemode,
+    pub previous_gamemode: Option<Gamemode>,
+    pub is_debug: bool,
+    pub is_superflat: bool,
+    pub death_location: OptionalPrefixed<DeathLocation<'a>>,
+    pub portal_cooldown: VarInt,
+    /// Bit 0 keeps attributes, bit 1 keeps metadata. Anything not kept is reset by the client.
+    pub data_kept: u8,
+}
+
+impl Packet for RespawnC<'_> {
+    const ID: i32 = 0x47;
+}
+
+impl<'a> Encode for RespawnC<'a> {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.dimension_type.encode(&mut w)?;
+        self.dimension_name.encode(&mut w)?;
+        self.hashed_seed.encode(&mut w)?;
+        u8::from(self.gamemode).encode(&mut w)?;
+
+        match self.previous_gamemode {
+            None => (-1i8).encode(&mut w)?,
+            Some(g) => i8::from(g).encode(&mut w)?,
         }
 
+        self.is_debug.encode(&mut w)?;
+        self.is_superflat.encode(&mut w)?;
+
+        self.death_location.encode(&mut w)?;
+
         self.portal_cooldown.encode(&mut w)?;
+        self.data_kept.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+/// Vanilla doesn't limit the icon's size, so this is just a sanity check.
+const ICON_BOUND: usize = 1048576;
+
+/// The MOTD and icon the client shows for the server once it's in, e.g. on the social
+/// interactions screen.
+#[derive(Debug)]
+pub struct ServerDataC<'a> {
+    pub motd: TextComponent,
+    /// A 64x64 PNG.
+    pub icon: OptionalPrefixed<Bounded<Bytes<'a>, ICON_BOUND>>,
+    pub enforces_secure_chat: bool,
+}
+
+impl Packet for ServerDataC<'_> {
+    const ID: i32 = 0x4B;
+}
+
+impl<'a> Encode for ServerDataC<'a> {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        fastnbt::to_bytes_with_opts(&self.motd, fastnbt::SerOpts::network_nbt())?.encode(&mut w)?;
+        self.icon.encode(&mut w)?;
         self.enforces_secure_chat.encode(&mut w)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "Hi" as network NBT.
+    const HI: [u8; 13] = [
+        0x0A, 0x08, 0x00, 0x04, b't', b'e', b'x', b't', 0x00, 0x02, b'H', b'i', 0x00,
+    ];
+
+    #[test]
+    fn encodes_server_data_without_an_icon() {
+        let mut body = Vec::new();
+        ServerDataC {
+            motd: "Hi".into(),
+            icon: OptionalPrefixed(None),
+            enforces_secure_chat: false,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        assert_eq!(body, [&HI[..], &[0, 0]].concat());
+    }
+
+    #[test]
+    fn encodes_server_data_with_an_icon() {
+        let mut body = Vec::new();
+        ServerDataC {
+            motd: "Hi".into(),
+            icon: OptionalPrefixed(Some(Bounded(Bytes(&[0x89, b'P', b'N', b'G'])))),
+            enforces_secure_chat: true,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        // present, then the length-prefixed icon
+        let expected = [&HI[..], &[1, 4, 0x89, b'P', b'N', b'G', 1]].concat();
+        assert_eq!(body, expected);
+    }
+}
@@ -76,3 +76,27 @@ impl Decode<'_> for SetPlayerPositionAndRotationS {
         })
     }
 }
+
+#[derive(Debug)]
+pub struct SetPlayerRotationS {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl Packet for SetPlayerRotationS {
+    const ID: i32 = 0x1C;
+}
+
+impl Decode<'_> for SetPlayerRotationS {
+    fn decode(r: &mut &'_ [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            yaw: r.read_f32::<BigEndian>()?,
+            pitch: r.read_f32::<BigEndian>()?,
+            on_ground: r.read_u8()? == 1,
+        })
+    }
+}
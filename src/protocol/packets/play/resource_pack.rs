@@ -0,0 +1,120 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use uuid::Uuid;
+
+use crate::protocol::{
+    datatypes::{Bounded, Nbt, OptionalPrefixed, TextComponent, VarInt},
+    Decode, Encode, Packet,
+};
+
+#[derive(Debug)]
+pub struct AddResourcePackC<'a> {
+    pub uuid: Uuid,
+    pub url: Bounded<&'a str>,
+    /// The pack's SHA-1 as hex, or empty to skip checking it.
+    pub hash: Bounded<&'a str, 40>,
+    /// Whether the player gets disconnected by the client if they decline.
+    pub forced: bool,
+    pub prompt_message: OptionalPrefixed<Nbt<TextComponent>>,
+}
+
+impl Packet for AddResourcePackC<'_> {
+    const ID: i32 = 0x46;
+}
+
+impl Encode for AddResourcePackC<'_> {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.uuid.encode(&mut w)?;
+        self.url.encode(&mut w)?;
+        self.hash.encode(&mut w)?;
+        self.forced.encode(&mut w)?;
+        self.prompt_message.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ResourcePackResponseS {
+    pub uuid: Uuid,
+    pub result: ResourcePackResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourcePackResult {
+    SuccessfullyDownloaded,
+    Declined,
+    FailedToDownload,
+    Accepted,
+    Downloaded,
+    InvalidUrl,
+    FailedToReload,
+    Discarded,
+}
+
+impl ResourcePackResult {
+    /// Whether the client is still working on the pack and will send another response.
+    pub fn is_pending(self) -> bool {
+        matches!(
+            self,
+            ResourcePackResult::Accepted | ResourcePackResult::Downloaded
+        )
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ResourcePackResultParseError {
+    #[error("Got unexpected resource pack result {0}")]
+    Unexpected(i32),
+}
+
+impl TryFrom<VarInt> for ResourcePackResult {
+    type Error = ResourcePackResultParseError;
+
+    fn try_from(value: VarInt) -> Result<Self, Self::Error> {
+        match value.0 {
+            0 => Ok(ResourcePackResult::SuccessfullyDownloaded),
+            1 => Ok(ResourcePackResult::Declined),
+            2 => Ok(ResourcePackResult::FailedToDownload),
+            3 => Ok(ResourcePackResult::Accepted),
+            4 => Ok(ResourcePackResult::Downloaded),
+            5 => Ok(ResourcePackResult::InvalidUrl),
+            6 => Ok(ResourcePackResult::FailedToReload),
+            7 => Ok(ResourcePackResult::Discarded),
+            i => Err(ResourcePackResultParseError::Unexpected(i)),
+        }
+    }
+}
+
+impl Packet for ResourcePackResponseS {
+    const ID: i32 = 0x2B;
+}
+
+impl Decode<'_> for ResourcePackResponseS {
+    fn decode(r: &mut &'_ [u8]) -> color_eyre::eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            uuid: Uuid::decode(r)?,
+            result: VarInt::decode(r)?.try_into()?,
+        })
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use byteorder::ReadBytesExt;
+
+use crate::protocol::{
+    datatypes::{Bounded, VarInt},
+    Decode, Packet,
+};
+
+/// Sent by the client when entering configuration and whenever the player changes their settings.
+#[derive(Debug)]
+pub struct ClientInformationS<'a> {
+    pub locale: Bounded<&'a str, 16>,
+    /// The client's render distance, in chunks.
+    pub view_distance: i8,
+    pub chat_mode: ChatMode,
+    pub chat_colors: bool,
+    pub displayed_skin_parts: u8,
+    pub main_hand: MainHand,
+    pub enable_text_filtering: bool,
+    pub allow_server_listings: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ChatMode {
+    Enabled,
+    CommandsOnly,
+    Hidden,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ChatModeParseError {
+    #[error("Got unexpected chat mode index {0}")]
+    Unexpected(i32),
+}
+
+impl TryFrom<VarInt> for ChatMode {
+    type Error = ChatModeParseError;
+
+    fn try_from(value: VarInt) -> Result<Self, Self::Error> {
+        match value.0 {
+            0 => Ok(ChatMode::Enabled),
+            1 => Ok(ChatMode::CommandsOnly),
+            2 => Ok(ChatMode::Hidden),
+            i => Err(ChatModeParseError::Unexpected(i)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MainHand {
+    Left,
+    Right,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MainHandParseError {
+    #[error("Got unexpected main hand index {0}")]
+    Unexpected(i32),
+}
+
+impl TryFrom<VarInt> for MainHand {
+    type Error = MainHandParseError;
+
+    fn try_from(value: VarInt) -> Result<Self, Self::Error> {
+        match value.0 {
+            0 => Ok(MainHand::Left),
+            1 => Ok(MainHand::Right),
+            i => Err(MainHandParseError::Unexpected(i)),
+        }
+    }
+}
+
+impl Packet for ClientInformationS<'_> {
+    const ID: i32 = 0x0A;
+}
+
+impl<'a> Decode<'a> for ClientInformationS<'a> {
+    fn decode(r: &mut &'a [u8]) -> color_eyre::eyre::Result<Self> {
+        Ok(Self {
+            locale: Bounded::<&'a str, 16>::decode(r)?,
+            view_distance: r.read_i8()?,
+            chat_mode: VarInt::decode(r)?.try_into()?,
+            chat_colors: bool::decode(r)?,
+            displayed_skin_parts: r.read_u8()?,
+            main_hand: VarInt::decode(r)?.try_into()?,
+            enable_text_filtering: bool::decode(r)?,
+            allow_server_listings: bool::decode(r)?,
+        })
+    }
+}
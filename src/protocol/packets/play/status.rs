@@ -20,7 +20,7 @@
 use uuid::Uuid;
 
 use crate::protocol::{
-    datatypes::{Bounded, VarInt},
+    datatypes::{Bounded, TextComponent, VarInt},
     Encode, Packet, Property,
 };
 
@@ -31,6 +31,27 @@ pub struct PlayerInfoUpdateC<'a> {
     pub players: &'a [PlayerStatus<'a>],
 }
 
+/// Forgets players added with [`PlayerInfoUpdateC`], taking them out of the tab list too.
+#[derive(Debug)]
+pub struct PlayerInfoRemoveC {
+    pub players: Vec<Uuid>,
+}
+
+impl Packet for PlayerInfoRemoveC {
+    const ID: i32 = 0x3D;
+}
+
+impl Encode for PlayerInfoRemoveC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.players.len() as i32).encode(&mut w)?;
+        for uuid in &self.players {
+            uuid.encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct PlayerStatus<'a> {
     uuid: Uuid,
@@ -109,7 +130,6 @@ impl PlayerAction<'_> {
     }
 }
 
-#[allow(unused)]
 impl<'a> PlayerStatus<'a> {
     pub fn for_player(player: Uuid) -> Self {
         Self {
@@ -145,3 +165,25 @@ impl<'a> PlayerStatus<'a> {
         self
     }
 }
+
+/// The text shown above and below the player list in the tab list. Empty components hide them.
+#[derive(Debug)]
+pub struct SetTabListHeaderAndFooterC {
+    pub header: TextComponent,
+    pub footer: TextComponent,
+}
+
+impl Packet for SetTabListHeaderAndFooterC {
+    const ID: i32 = 0x6D;
+}
+
+impl Encode for SetTabListHeaderAndFooterC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        fastnbt::to_bytes_with_opts(&self.header, fastnbt::SerOpts::network_nbt())?
+            .encode(&mut w)?;
+        fastnbt::to_bytes_with_opts(&self.footer, fastnbt::SerOpts::network_nbt())?
+            .encode(&mut w)?;
+
+        Ok(())
+    }
+}
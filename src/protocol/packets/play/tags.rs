@@ -0,0 +1,99 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use color_eyre::eyre::Result;
+
+use crate::protocol::{
+    datatypes::{Bounded, VarInt},
+    Encode, Packet,
+};
+
+/// The tags in one registry, e.g. `minecraft:block`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistryTags {
+    pub registry: String,
+    /// Tag names and the protocol ids of the entries in them.
+    pub tags: Vec<(String, Vec<i32>)>,
+}
+
+impl Encode for RegistryTags {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        Bounded::<&str>(&self.registry).encode(&mut w)?;
+
+        VarInt(self.tags.len() as i32).encode(&mut w)?;
+        for (name, entries) in &self.tags {
+            Bounded::<&str>(name).encode(&mut w)?;
+            VarInt(entries.len() as i32).encode(&mut w)?;
+            for entry in entries {
+                VarInt(*entry).encode(&mut w)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Replaces the client's tags for every registry listed. Tags in those registries that aren't
+/// listed end up empty, so always send the full set.
+#[derive(Debug)]
+pub struct UpdateTagsC<'a> {
+    pub registries: &'a [RegistryTags],
+}
+
+impl Packet for UpdateTagsC<'_> {
+    const ID: i32 = 0x78;
+}
+
+impl Encode for UpdateTagsC<'_> {
+    fn encode(&self, mut w: impl std::io::Write) -> Result<()> {
+        VarInt(self.registries.len() as i32).encode(&mut w)?;
+        for registry in self.registries {
+            registry.encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_each_registry_and_tag() {
+        let registries = [RegistryTags {
+            registry: "minecraft:fluid".to_owned(),
+            tags: vec![("minecraft:water".to_owned(), vec![2, 1])],
+        }];
+
+        let mut body = Vec::new();
+        UpdateTagsC {
+            registries: &registries,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        let mut expected = vec![1, 15];
+        expected.extend(b"minecraft:fluid");
+        expected.extend([1, 15]);
+        expected.extend(b"minecraft:water");
+        expected.extend([2, 2, 1]);
+        assert_eq!(body, expected);
+    }
+}
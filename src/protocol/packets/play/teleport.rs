@@ -43,7 +43,6 @@ mod flags {
     pub const X_ROT: i8 = 0x10;
 }
 
-#[allow(unused)]
 impl SynchronisePositionC {
     pub fn new(x: f64, y: f64, z: f64, yaw: f32, pitch: f32) -> Self {
         Self {
@@ -49,3 +49,69 @@ impl Encode for StepTicksC {
         VarInt(self.0).encode(w)
     }
 }
+
+/// Sets the world age and time of day.
+///
+/// 1.21.2 added a flag for whether the time of day advances, but 1.21.1 doesn't have it, so when
+/// `advancing` is false the time of day is sent negated instead, which older clients also read as
+/// "don't advance". The world age keeps going either way. A negative `time_of_day` freezes it even
+/// when `advancing` is true.
+#[derive(Debug)]
+pub struct UpdateTimeC {
+    pub world_age: i64,
+    pub time_of_day: i64,
+    pub advancing: bool,
+}
+
+impl Packet for UpdateTimeC {
+    const ID: i32 = 0x64;
+}
+
+impl Encode for UpdateTimeC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.world_age.encode(&mut w)?;
+
+        // 0 can't be negated, so freezing at 0 is sent as -1
+        let time_of_day = match self.advancing {
+            true => self.time_of_day,
+            false => -self.time_of_day.abs().max(1),
+        };
+        time_of_day.encode(&mut w)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The time of day as sent, after the world age.
+    fn sent_time(time_of_day: i64, advancing: bool) -> i64 {
+        let mut body = Vec::new();
+        UpdateTimeC {
+            world_age: 1000,
+            time_of_day,
+            advancing,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        assert_eq!(body[..8], 1000i64.to_be_bytes());
+        i64::from_be_bytes(body[8..].try_into().unwrap())
+    }
+
+    #[test]
+    fn encodes_time_and_freezing() {
+        assert_eq!(sent_time(6000, true), 6000);
+        assert_eq!(sent_time(6000, false), -6000);
+        assert_eq!(sent_time(-6000, true), -6000);
+        assert_eq!(sent_time(-6000, false), -6000);
+    }
+
+    #[test]
+    fn frozen_midnight_is_still_frozen() {
+        assert_eq!(sent_time(0, false), -1);
+        assert_eq!(sent_time(0, true), 0);
+    }
+}
@@ -0,0 +1,55 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use crate::protocol::{Encode, Packet};
+
+/// Hides the player's current title and subtitle.
+#[derive(Debug)]
+pub struct ClearTitlesC {
+    /// Whether to also reset the title fade in/stay/fade out times to their defaults.
+    pub reset: bool,
+}
+
+impl Packet for ClearTitlesC {
+    const ID: i32 = 0x0F;
+}
+
+impl Encode for ClearTitlesC {
+    fn encode(&self, w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.reset.encode(w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(reset: bool) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ClearTitlesC { reset }.encode(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn encodes_clear_titles() {
+        assert_eq!(ClearTitlesC::ID, 0x0F);
+        assert_eq!(encode(false), [0x00]);
+        assert_eq!(encode(true), [0x01]);
+    }
+}
@@ -53,6 +53,26 @@ impl Encode for SetCenterChunkC {
     }
 }
 
+/// Tells the client to forget about a chunk that's now outside its view distance.
+#[derive(Debug)]
+pub struct UnloadChunkC {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl Packet for UnloadChunkC {
+    const ID: i32 = 0x21;
+}
+
+impl Encode for UnloadChunkC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        // yes, z really does come first
+        self.z.encode(&mut w)?;
+        self.x.encode(&mut w)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct ChunkDataUpdateLightC<'a> {
     x: i32,
@@ -88,71 +108,139 @@ impl Encode for BlockEntity {
     }
 }
 
-impl From<world::BlockEntity> for BlockEntity {
-    fn from(value: world::BlockEntity) -> Self {
-        let data = fastnbt::to_bytes_with_opts(&value.raw_data, fastnbt::SerOpts::network_nbt())
-            .expect("Failed to parse network nbt for block entity");
-
-        let kind = VarInt(match value.id.as_str() {
-            "minecraft:furnace" => 0,
-            "minecraft:chest" => 1,
-            "minecraft:trapped_chest" => 2,
-            "minecraft:ender_chest" => 3,
-            "minecraft:jukebox" => 4,
-            "minecraft:dispenser" => 5,
-            "minecraft:dropper" => 6,
-            "minecraft:sign" => 7,
-            "minecraft:hanging_sign" => 8,
-            "minecraft:mob_spawner" => 9,
-            "minecraft:piston" => 10,
-            "minecraft:brewing_stand" => 11,
-            "minecraft:enchanting_table" => 12,
-            "minecraft:end_portal" => 13,
-            "minecraft:beacon" => 14,
-            "minecraft:skull" => 15,
-            "minecraft:daylight_detector" => 16,
-            "minecraft:hopper" => 17,
-            "minecraft:comparator" => 18,
-            "minecraft:banner" => 19,
-            "minecraft:structure_block" => 20,
-            "minecraft:end_gateway" => 21,
-            "minecraft:command_block" => 22,
-            "minecraft:shulker_box" => 23,
-            "minecraft:bed" => 24,
-            "minecraft:conduit" => 25,
-            "minecraft:barrel" => 26,
-            "minecraft:smoker" => 27,
-            "minecraft:blast_furnace" => 28,
-            "minecraft:lectern" => 29,
-            "minecraft:bell" => 30,
-            "minecraft:jigsaw" => 31,
-            "minecraft:campfire" => 32,
-            "minecraft:beehive" => 33,
-            "minecraft:sculk_sensor" => 34,
-            "minecraft:calibrated_sculk_sensor" => 35,
-            "minecraft:sculk_catalyst" => 36,
-            "minecraft:sculk_shrieker" => 37,
-            "minecraft:chiseled_bookshelf" => 38,
-            "minecraft:brushable_block" => 39,
-            "minecraft:decorated_pot" => 40,
-            "minecraft:crafter" => 41,
-            "minecraft:trial_spawner" => 42,
-            "minecraft:vault" => 43,
-            i => unimplemented!("Block Entity type {i} is unimplemented"),
-        });
+#[derive(Debug, thiserror::Error)]
+pub enum BlockEntityError {
+    #[error("Block Entity type {0} is unimplemented")]
+    UnknownKind(String),
+    #[error("Failed to serialize block entity data: {0}")]
+    Nbt(#[from] fastnbt::error::Error),
+    #[error("Block entity y {0} doesn't fit in a chunk packet")]
+    OutOfRange(i32),
+}
 
-        Self {
+/// Gets the protocol id for a block entity type, or `None` if it isn't one crawlspace knows.
+pub fn block_entity_kind(id: &str) -> Option<i32> {
+    Some(match id {
+        "minecraft:furnace" => 0,
+        "minecraft:chest" => 1,
+        "minecraft:trapped_chest" => 2,
+        "minecraft:ender_chest" => 3,
+        "minecraft:jukebox" => 4,
+        "minecraft:dispenser" => 5,
+        "minecraft:dropper" => 6,
+        "minecraft:sign" => 7,
+        "minecraft:hanging_sign" => 8,
+        "minecraft:mob_spawner" => 9,
+        "minecraft:piston" => 10,
+        "minecraft:brewing_stand" => 11,
+        "minecraft:enchanting_table" => 12,
+        "minecraft:end_portal" => 13,
+        "minecraft:beacon" => 14,
+        "minecraft:skull" => 15,
+        "minecraft:daylight_detector" => 16,
+        "minecraft:hopper" => 17,
+        "minecraft:comparator" => 18,
+        "minecraft:banner" => 19,
+        "minecraft:structure_block" => 20,
+        "minecraft:end_gateway" => 21,
+        "minecraft:command_block" => 22,
+        "minecraft:shulker_box" => 23,
+        "minecraft:bed" => 24,
+        "minecraft:conduit" => 25,
+        "minecraft:barrel" => 26,
+        "minecraft:smoker" => 27,
+        "minecraft:blast_furnace" => 28,
+        "minecraft:lectern" => 29,
+        "minecraft:bell" => 30,
+        "minecraft:jigsaw" => 31,
+        "minecraft:campfire" => 32,
+        "minecraft:beehive" => 33,
+        "minecraft:sculk_sensor" => 34,
+        "minecraft:calibrated_sculk_sensor" => 35,
+        "minecraft:sculk_catalyst" => 36,
+        "minecraft:sculk_shrieker" => 37,
+        "minecraft:chiseled_bookshelf" => 38,
+        "minecraft:brushable_block" => 39,
+        "minecraft:decorated_pot" => 40,
+        "minecraft:crafter" => 41,
+        "minecraft:trial_spawner" => 42,
+        "minecraft:vault" => 43,
+        _ => return None,
+    })
+}
+
+impl TryFrom<world::BlockEntity> for BlockEntity {
+    type Error = BlockEntityError;
+
+    fn try_from(value: world::BlockEntity) -> Result<Self, Self::Error> {
+        let kind = block_entity_kind(&value.id)
+            .map(VarInt)
+            .ok_or_else(|| BlockEntityError::UnknownKind(value.id.clone()))?;
+
+        let data = fastnbt::to_bytes_with_opts(&value.raw_data, fastnbt::SerOpts::network_nbt())?;
+
+        // the protocol only has an i16 for y, so anything outside that would silently wrap
+        let y = i16::try_from(value.y).map_err(|_| BlockEntityError::OutOfRange(value.y))?;
+
+        Ok(Self {
+            // & 15 is the position within the chunk for negative coordinates too, since it's two's
+            // complement
             packed_xz: (((value.x & 15) << 4) | (value.z & 15)) as u8,
-            y: value.y as i16,
+            y,
             kind,
             data,
-        }
+        })
     }
 }
 
 #[derive(Debug)]
 struct HeightMaps(HashMap<String, fastnbt::LongArray>);
 
+impl HeightMaps {
+    /// Works out the highest non-air block in each column, as vanilla does for `WORLD_SURFACE`.
+    /// There's no collision data to tell what blocks motion, so `MOTION_BLOCKING` gets the same
+    /// heights - close enough for a world with no weather.
+    fn compute(chunk: &world::Chunk, min_y: i32, height: i32) -> Self {
+        // columns are indexed x + z * 16, and hold the y above the top block relative to min_y,
+        // or 0 for an empty column
+        let mut heights = [0u64; 256];
+
+        // sections are sorted bottom up, so the first hit from the top is the highest
+        for (i, column) in heights.iter_mut().enumerate() {
+            let (x, z) = ((i % 16) as i32, (i / 16) as i32);
+
+            let top = chunk.sections.iter().rev().find_map(|section| {
+                (0..16).rev().find_map(|y| {
+                    section
+                        .block(x, y, z)
+                        .filter(|b| !b.is_air())
+                        .map(|_| section.y * 16 + y)
+                })
+            });
+
+            if let Some(top) = top {
+                *column = (top - min_y + 1).clamp(0, height) as u64;
+            }
+        }
+
+        let bits = u64::BITS - (height as u64).leading_zeros();
+        let per_long = (64 / bits) as usize;
+
+        let mut packed = vec![0i64; heights.len().div_ceil(per_long)];
+        for (i, column) in heights.iter().enumerate() {
+            packed[i / per_long] |= (column << ((i % per_long) as u32 * bits)) as i64;
+        }
+
+        Self(HashMap::from([
+            (
+                "MOTION_BLOCKING".to_owned(),
+                fastnbt::LongArray::new(packed.clone()),
+            ),
+            ("WORLD_SURFACE".to_owned(), fastnbt::LongArray::new(packed)),
+        ]))
+    }
+}
+
 #[derive(Debug)]
 struct ChunkSection {
     block_count: i16,
@@ -422,13 +510,26 @@ impl ChunkDataUpdateLightC<'_> {
                     },
                 )
             })
-            .map(Into::into)
+            .filter_map(|e| match self::BlockEntity::try_from(e) {
+                Ok(e) => Some(e),
+                Err(why) => {
+                    warn!(
+                        "{why}, ignoring in final chunk packet for ({}, {})",
+                        value.x_pos, value.z_pos,
+                    );
+                    None
+                }
+            })
             .collect::<Vec<self::BlockEntity>>();
 
         Self {
             x: value.x_pos,
             z: value.z_pos,
-            heightmaps: HeightMaps(HashMap::new()),
+            heightmaps: HeightMaps::compute(
+                value,
+                crawlstate.registry_cache.the_end_min_y,
+                crawlstate.registry_cache.the_end_height,
+            ),
             data,
             entities: block_entities,
             sky_light_mask: BitVec::from_elem(18, false),
@@ -489,6 +590,55 @@ impl Encode for SetBorderCenterC {
     }
 }
 
+#[derive(Debug)]
+pub struct SetBorderLerpSizeC {
+    pub old_diameter: f64,
+    pub new_diameter: f64,
+    /// How long the border takes to get to `new_diameter`, in milliseconds.
+    pub speed: i64,
+}
+
+impl Packet for SetBorderLerpSizeC {
+    const ID: i32 = 0x4E;
+}
+
+impl Encode for SetBorderLerpSizeC {
+    fn encode(&self, mut w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        self.old_diameter.encode(&mut w)?;
+        self.new_diameter.encode(&mut w)?;
+        VarLong(self.speed).encode(&mut w)?;
+        Ok(())
+    }
+}
+
+/// How many seconds before a moving border reaches the player the warning shows.
+#[derive(Debug)]
+pub struct SetBorderWarningDelayC(pub i32);
+
+impl Packet for SetBorderWarningDelayC {
+    const ID: i32 = 0x50;
+}
+
+impl Encode for SetBorderWarningDelayC {
+    fn encode(&self, w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.0).encode(w)
+    }
+}
+
+/// How close to the border, in blocks, the player has to be for the red warning to show.
+#[derive(Debug)]
+pub struct SetBorderWarningDistanceC(pub i32);
+
+impl Packet for SetBorderWarningDistanceC {
+    const ID: i32 = 0x51;
+}
+
+impl Encode for SetBorderWarningDistanceC {
+    fn encode(&self, w: impl std::io::Write) -> color_eyre::eyre::Result<()> {
+        VarInt(self.0).encode(w)
+    }
+}
+
 #[derive(Debug)]
 pub struct SetBorderSizeC(pub f64);
 
@@ -501,3 +651,82 @@ impl Encode for SetBorderSizeC {
         self.0.encode(w)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn border_warnings_are_varints() {
+        let mut distance = Vec::new();
+        SetBorderWarningDistanceC(300)
+            .encode(&mut distance)
+            .unwrap();
+        assert_eq!(distance, [0xAC, 0x02]);
+
+        let mut delay = Vec::new();
+        SetBorderWarningDelayC(15).encode(&mut delay).unwrap();
+        assert_eq!(delay, [15]);
+    }
+
+    #[test]
+    fn unserializable_block_entities_are_skipped() {
+        use fastnbt::Value;
+
+        // NBT lists can only hold one type, so this can't be written out
+        let bad = Value::Compound(HashMap::from([
+            ("id".to_owned(), Value::String("minecraft:chest".to_owned())),
+            ("keepPacked".to_owned(), Value::Byte(0)),
+            ("x".to_owned(), Value::Int(1)),
+            ("y".to_owned(), Value::Int(64)),
+            ("z".to_owned(), Value::Int(0)),
+            (
+                "Mixed".to_owned(),
+                Value::List(vec![Value::Int(1), Value::String("two".to_owned())]),
+            ),
+        ]));
+
+        let parsed = world::BlockEntity::try_parse(bad.clone()).unwrap();
+        assert!(matches!(
+            BlockEntity::try_from(parsed),
+            Err(BlockEntityError::Nbt(_))
+        ));
+
+        let mut chunk = world::fixtures::chunk_with_chest(&[]);
+        chunk.block_entities.push(bad);
+
+        let state = crate::net::player::testing::state(&[]);
+        let packet = ChunkDataUpdateLightC::new(state, &chunk, &Blocks::new());
+        assert_eq!(packet.entities.len(), 1);
+        assert_eq!(packet.entities[0].packed_xz, 0);
+    }
+
+    fn chest_at(x: i32, y: i32, z: i32) -> Result<BlockEntity, BlockEntityError> {
+        use fastnbt::Value;
+
+        let chest = Value::Compound(HashMap::from([
+            ("id".to_owned(), Value::String("minecraft:chest".to_owned())),
+            ("keepPacked".to_owned(), Value::Byte(0)),
+            ("x".to_owned(), Value::Int(x)),
+            ("y".to_owned(), Value::Int(y)),
+            ("z".to_owned(), Value::Int(z)),
+        ]));
+        BlockEntity::try_from(world::BlockEntity::try_parse(chest).unwrap())
+    }
+
+    #[test]
+    fn block_entity_positions_pack() {
+        let tall = chest_at(3, 2000, 5).unwrap();
+        assert_eq!(tall.y, 2000);
+        assert_eq!(tall.packed_xz, 0x35);
+
+        // negative coordinates still count up from the chunk's corner
+        assert_eq!(chest_at(-1, 64, -16).unwrap().packed_xz, 0xF0);
+        assert_eq!(chest_at(-17, -64, -3).unwrap().packed_xz, 0xFD);
+
+        assert!(matches!(
+            chest_at(0, 40000, 0),
+            Err(BlockEntityError::OutOfRange(40000))
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::protocol::packets::play::SetBorderLerpSizeC;
+
+/// A border shrinking through a list of phases, battle royale style. Each phase moves the border
+/// from wherever it is to a new diameter over a duration.
+#[derive(Debug)]
+pub struct BorderShrink {
+    phases: VecDeque<(f64, Duration)>,
+    current: Option<Phase>,
+    /// The diameter as of the end of the last finished phase.
+    diameter: f64,
+}
+
+#[derive(Debug)]
+struct Phase {
+    from: f64,
+    to: f64,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Phase {
+    fn ends(&self) -> Instant {
+        self.started + self.duration
+    }
+
+    fn diameter_at(&self, now: Instant) -> f64 {
+        if self.duration.is_zero() || now >= self.ends() {
+            return self.to;
+        }
+
+        let progress = (now - self.started).as_secs_f64() / self.duration.as_secs_f64();
+        self.from + (self.to - self.from) * progress
+    }
+}
+
+impl BorderShrink {
+    pub fn new(diameter: f64, phases: Vec<(f64, Duration)>) -> Self {
+        Self {
+            phases: phases.into(),
+            current: None,
+            diameter,
+        }
+    }
+
+    /// Starts the next phase if the current one is over (or none has started yet), returning the
+    /// packet everyone needs to see it.
+    pub fn advance(&mut self, now: Instant) -> Option<SetBorderLerpSizeC> {
+        if let Some(phase) = &self.current {
+            if now < phase.ends() {
+                return None;
+            }
+
+            self.diameter = phase.to;
+            self.current = None;
+        }
+
+        let (to, duration) = self.phases.pop_front()?;
+        let phase = Phase {
+            from: self.diameter,
+            to,
+            started: now,
+            duration,
+        };
+
+        debug!(
+            "Border shrinking from {} to {to} over {}s",
+            phase.from,
+            duration.as_secs_f64()
+        );
+
+        let packet = lerp(&phase, now);
+        self.current = Some(phase);
+        Some(packet)
+    }
+
+    /// The lerp a player joining mid-phase needs to see the border where everyone else does.
+    pub fn current_lerp(&self, now: Instant) -> Option<SetBorderLerpSizeC> {
+        self.current.as_ref().map(|phase| lerp(phase, now))
+    }
+
+    pub fn diameter(&self, now: Instant) -> f64 {
+        match &self.current {
+            Some(phase) => phase.diameter_at(now),
+            None => self.diameter,
+        }
+    }
+}
+
+fn lerp(phase: &Phase, now: Instant) -> SetBorderLerpSizeC {
+    let remaining = phase.ends().saturating_duration_since(now);
+
+    SetBorderLerpSizeC {
+        old_diameter: phase.diameter_at(now),
+        new_diameter: phase.to,
+        speed: remaining.as_millis() as i64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn phases_advance_on_schedule() {
+        let start = Instant::now();
+        let mut border = BorderShrink::new(200.0, vec![(100.0, secs(10)), (20.0, secs(5))]);
+
+        let first = border.advance(start).unwrap();
+        assert_eq!(first.old_diameter, 200.0);
+        assert_eq!(first.new_diameter, 100.0);
+        assert_eq!(first.speed, 10_000);
+
+        assert!(border.advance(start + secs(9)).is_none());
+        assert_eq!(border.diameter(start + secs(5)), 150.0);
+
+        let second = border.advance(start + secs(10)).unwrap();
+        assert_eq!(second.old_diameter, 100.0);
+        assert_eq!(second.new_diameter, 20.0);
+        assert_eq!(second.speed, 5_000);
+
+        let joined = border.current_lerp(start + secs(12)).unwrap();
+        assert_eq!(joined.old_diameter, 68.0);
+        assert_eq!(joined.speed, 3_000);
+
+        assert!(border.advance(start + secs(15)).is_none());
+        assert!(border.current_lerp(start + secs(15)).is_none());
+        assert_eq!(border.diameter(start + secs(60)), 20.0);
+    }
+}
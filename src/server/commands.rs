@@ -0,0 +1,214 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::HashMap, str::SplitWhitespace};
+
+use color_eyre::eyre::{bail, eyre, Result};
+
+use crate::net::player::SharedPlayer;
+
+use super::Server;
+
+/// Runs a command a player sent, without the leading slash. Errors are meant to be shown to the
+/// player that sent it.
+pub async fn run(
+    server: &Server,
+    players: &HashMap<u16, SharedPlayer>,
+    player: &SharedPlayer,
+    command: &str,
+) -> Result<()> {
+    debug!("Player {} ran /{command}", player.id());
+
+    let mut args = command.split_whitespace();
+    match args.next() {
+        Some("world") => world(server, player, args).await,
+        Some("kill") => kill(players, player, args).await,
+        Some("reload-world") => reload_world(server, player, args).await,
+        Some(other) => bail!("Unknown command /{other}"),
+        None => Ok(()),
+    }
+}
+
+/// `/world` lists the loaded worlds, and `/world <name>` moves the player into one.
+async fn world(
+    server: &Server,
+    player: &SharedPlayer,
+    mut args: SplitWhitespace<'_>,
+) -> Result<()> {
+    let Some(name) = args.next() else {
+        let worlds = server.world_names().join(", ");
+        return player
+            .send_system_message(format!("Worlds: {worlds}"), false)
+            .await;
+    };
+
+    server.switch_world(player, name).await?;
+    player
+        .send_system_message(format!("Moved to {name}"), false)
+        .await
+}
+
+/// `/kill` resets the player back to spawn, and `/kill <name>` does the same to someone else.
+/// Operator only.
+async fn kill(
+    players: &HashMap<u16, SharedPlayer>,
+    player: &SharedPlayer,
+    mut args: SplitWhitespace<'_>,
+) -> Result<()> {
+    if !player.is_op().await {
+        bail!("You don't have permission to use /kill");
+    }
+
+    let target = match args.next() {
+        None => player.clone(),
+        Some(name) => find_player(players, name)
+            .await
+            .ok_or_else(|| eyre!("There's no player named {name}"))?,
+    };
+
+    target.reset().await?;
+
+    let name = target.username().await;
+    player
+        .send_system_message(format!("Killed {name}"), false)
+        .await
+}
+
+/// `/reload-world` reloads the world the player is in from disk, and `/reload-world <name>` reloads
+/// another one. Operator only.
+async fn reload_world(
+    server: &Server,
+    player: &SharedPlayer,
+    mut args: SplitWhitespace<'_>,
+) -> Result<()> {
+    if !player.is_op().await {
+        bail!("You don't have permission to use /reload-world");
+    }
+
+    let name = match args.next() {
+        Some(name) => name.to_owned(),
+        None => player.world().await,
+    };
+
+    player
+        .send_system_message(format!("Reloading {name}..."), false)
+        .await?;
+
+    // reading a world takes a while, so do it off the tick. it also needs the player list
+    let server = server.crawlstate.get_server().await;
+    let player = player.clone();
+    tokio::spawn(async move {
+        let message = match server.reload_world(&name).await {
+            Ok(()) => format!("Reloaded {name}"),
+            Err(why) => format!("Failed to reload {name}: {why}"),
+        };
+
+        if let Err(why) = player.send_system_message(message, false).await {
+            warn!(
+                "Failed to send reload result to player {}: {why}",
+                player.id()
+            );
+        }
+    });
+
+    Ok(())
+}
+
+async fn find_player(players: &HashMap<u16, SharedPlayer>, name: &str) -> Option<SharedPlayer> {
+    for player in players.values() {
+        if player.username().await.eq_ignore_ascii_case(name) {
+            return Some(player.clone());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::player::testing,
+        protocol::{
+            datatypes::{Bounded, VarInt},
+            packets::play::{
+                ChunkDataUpdateLightC, CloseContainerC, RespawnC, SynchronisePositionC,
+            },
+            Decode, Packet,
+        },
+        world::fixtures,
+    };
+
+    #[tokio::test]
+    async fn world_respawns_into_the_new_dimension() {
+        let state = testing::state(&["nether=elsewhere"]);
+        let chunk = fixtures::chunk(0, 0, vec![fixtures::air_section(0)]);
+        let nether = testing::world(&state, vec![chunk]);
+        let server = testing::server(&state, vec![("nether".to_owned(), nether)]).await;
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+
+        run(&server, &HashMap::new(), &player, "world nether")
+            .await
+            .unwrap();
+
+        let respawn = client.next_packet(RespawnC::ID).await;
+        let mut body = &respawn.body[..];
+        VarInt::decode(&mut body).unwrap();
+        let dimension = Bounded::<&str>::decode(&mut body).unwrap();
+        assert_eq!(dimension.0, "crawlspace:nether");
+        assert_eq!(player.world().await, "nether");
+
+        assert!(player.needs_chunk_update().await);
+        let worlds = server.worlds.read().await.clone();
+        Server::send_world_to(player.clone(), worlds).await.unwrap();
+        client.next_packet(ChunkDataUpdateLightC::ID).await;
+    }
+
+    #[tokio::test]
+    async fn kill_sends_the_player_to_spawn_with_nothing_open() {
+        let state = testing::state(&["--ops", "alice", "-x", "8", "-y", "70", "-z", "24"]);
+        let server = testing::server(&state, Vec::new()).await;
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        player.open_merchant(Vec::new()).await.unwrap();
+        client.drain(&player).await;
+
+        run(&server, &HashMap::new(), &player, "kill")
+            .await
+            .unwrap();
+
+        let ids = client.drain(&player).await;
+        assert!(ids.contains(&CloseContainerC::ID));
+        assert!(ids.contains(&SynchronisePositionC::ID));
+        assert_eq!(player.position().await, (8.0, 70.0, 24.0));
+
+        player.close_window().await.unwrap();
+        assert!(!client.drain(&player).await.contains(&CloseContainerC::ID));
+    }
+
+    #[tokio::test]
+    async fn kill_is_operator_only() {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+        let (player, _client) = testing::player(&state, 0, "alice").await;
+
+        assert!(run(&server, &HashMap::new(), &player, "kill")
+            .await
+            .is_err());
+    }
+}
@@ -17,51 +17,150 @@
  * <https://www.gnu.org/licenses/>.
  */
 
+pub mod border;
+pub mod commands;
+pub mod npc;
 pub mod registries;
+pub mod tags;
 pub mod ticker;
+#[cfg(feature = "timings")]
+pub mod timings;
 pub mod window;
 
 use std::{
     collections::{HashMap, HashSet},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
-use color_eyre::eyre::Result;
-
-use tokio::sync::Mutex;
+use bytes::BytesMut;
+use color_eyre::eyre::{bail, eyre, Result};
+use thiserror::Error;
+use uuid::Uuid;
 
-#[cfg(feature = "timings")]
-use tokio::time::Instant;
+use tokio::{
+    sync::{Mutex, RwLock},
+    time::Instant,
+};
 
 use crate::{
     net::{
         cache::WorldCache,
+        entity::in_view_range,
         player::{SharedPlayer, TeleportError},
     },
-    world::{Container, World},
+    protocol::{
+        datatypes::{Metadata, Slot, TextComponent},
+        packets::play::{
+            AdvancementFrame, Attribute, Billboard, HurtAnimationC, PlayerInfoUpdateC,
+            PlayerStatus, SetBorderCenterC, SetBorderSizeC, SetBorderWarningDelayC,
+            SetBorderWarningDistanceC, SetEntityMetadataC, SpawnEntityC, UpdateAdvancementsC,
+            UpdateAttributesC,
+        },
+        ClientboundPacket, Encoder,
+    },
+    state::Border,
+    world::{read_world, Container, World},
     CrawlState,
 };
 
-use self::ticker::Ticker;
+use self::{border::BorderShrink, npc::NpcHandler, registries::registries, ticker::Ticker};
+
+/// How often players' tab list ping is updated.
+const LATENCY_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Entity ids for anything that isn't a player start here, so they never clash with player ids.
+const FIRST_ENTITY_ID: i32 = u16::MAX as i32 + 1;
+
+/// A non-player entity the server spawned, kept so players who show up later see it too.
+#[derive(Debug)]
+struct SpawnedEntity {
+    world: String,
+    /// The spawn and metadata packets (plus any attribute updates since), already encoded.
+    packets: BytesMut,
+}
+
+/// Why the tick dropped a player. The display form goes in logs, [`Removal::kick_message`] is
+/// what the player sees.
+#[derive(Debug, Error)]
+enum Removal {
+    #[error("error handling packets: {0}")]
+    PacketError(color_eyre::Report),
+    #[error("connection closed")]
+    ConnectionClosed,
+    #[error("idle for too long")]
+    Idle,
+    #[error("keepalive timed out")]
+    KeepaliveTimedOut,
+    #[error("teleport failed: {0}")]
+    TeleportFailed(TeleportError),
+}
+
+impl Removal {
+    fn kick_message(&self) -> TextComponent {
+        match self {
+            Self::PacketError(_) => "Your client sent something the server couldn't handle",
+            // they won't see this, but it's sent on the off chance the socket is still writable
+            Self::ConnectionClosed => "Connection closed",
+            Self::Idle => "You were idle for too long",
+            Self::KeepaliveTimedOut => "Timed out",
+            Self::TeleportFailed(_) => "Your client didn't confirm a teleport in time",
+        }
+        .into()
+    }
+}
 
 #[derive(Debug)]
 pub struct Server {
     pub ticker: Ticker,
 
-    world_cache: Arc<WorldCache>,
+    /// Every loaded world, keyed by name. Swapped out wholesale when a world is reloaded.
+    worlds: RwLock<Arc<HashMap<String, Arc<WorldCache>>>>,
     players: Mutex<HashMap<u16, SharedPlayer>>,
+    border: Mutex<Option<BorderShrink>>,
+    /// The border warning distance and delay, if they've been changed from the defaults.
+    border_warning: Mutex<Option<(i32, i32)>>,
+    npc_handlers: Mutex<HashMap<i32, NpcHandler>>,
+    entities: Mutex<HashMap<i32, SpawnedEntity>>,
+    /// Commands players sent this tick, run once packet handling is done.
+    commands: Mutex<Vec<(SharedPlayer, String)>>,
+    /// Chat messages players sent this tick, broadcast once packet handling is done.
+    chat: Mutex<Vec<TextComponent>>,
+    next_entity_id: AtomicI32,
+    /// When players' tab list latency was last sent.
+    last_latency_update: Mutex<Instant>,
+    /// Per packet id handle times, logged every minute.
+    #[cfg(feature = "timings")]
+    packet_timings: std::sync::Mutex<timings::PacketTimings>,
 
     crawlstate: CrawlState,
 }
 
 impl Server {
     #[must_use]
-    pub fn new(state: CrawlState, world_cache: WorldCache, tick_rate: u8) -> Arc<Self> {
+    pub fn new(state: CrawlState, worlds: Vec<(String, WorldCache)>, tick_rate: u8) -> Arc<Self> {
+        let worlds = worlds
+            .into_iter()
+            .map(|(name, cache)| (name, Arc::new(cache)))
+            .collect();
+
         let server = Arc::new(Server {
             ticker: Ticker::new(tick_rate),
-            world_cache: Arc::new(world_cache),
+            worlds: RwLock::new(Arc::new(worlds)),
             players: Mutex::new(HashMap::new()),
+            border: Mutex::new(None),
+            border_warning: Mutex::new(None),
+            npc_handlers: Mutex::new(HashMap::new()),
+            entities: Mutex::new(HashMap::new()),
+            commands: Mutex::new(Vec::new()),
+            chat: Mutex::new(Vec::new()),
+            next_entity_id: AtomicI32::new(FIRST_ENTITY_ID),
+            last_latency_update: Mutex::new(Instant::now()),
+            #[cfg(feature = "timings")]
+            packet_timings: std::sync::Mutex::new(timings::PacketTimings::new()),
             crawlstate: state.clone(),
         });
 
@@ -80,13 +179,23 @@ impl Server {
         let state = self.crawlstate.clone();
         let mut player_recv = state.player_recv.lock().await;
 
+        let worlds = self.worlds.read().await.clone();
+
         let mut players = self.players.lock().await;
         while let Ok(p) = player_recv.try_recv() {
             players.insert(p.0.id, p.clone());
-            tokio::spawn(Self::send_world_to(p.clone(), self.world_cache.clone()));
+            tokio::spawn(Self::send_world_to(p.clone(), worlds.clone()));
+
+            if let Err(why) = self.send_border_to(&p).await {
+                warn!("Failed to send border to player {}: {why}", p.id());
+            }
+
+            if let Err(why) = self.send_entities_to(&p).await {
+                warn!("Failed to send entities to player {}: {why}", p.id());
+            }
         }
 
-        let mut invalid_players: HashSet<u16> = HashSet::new();
+        let mut invalid_players: HashMap<u16, Removal> = HashMap::new();
 
         for (id, player) in &*players {
             let _ = player.keepalive().await;
@@ -95,47 +204,994 @@ impl Server {
                 Ok(()) => (),
                 Err(why) => {
                     error!("error handling packets for player {}: {why}", player.id());
-                    invalid_players.insert(*id);
+                    invalid_players.insert(*id, Removal::PacketError(why));
                     continue;
                 }
             }
 
             {
                 if !player.0.io.connected().await {
-                    invalid_players.insert(*id);
+                    invalid_players.insert(*id, Removal::ConnectionClosed);
+                }
+            }
+
+            if player.keepalive_timed_out().await {
+                invalid_players
+                    .entry(*id)
+                    .or_insert(Removal::KeepaliveTimedOut);
+            }
+
+            if let Some(limit) = state.idle_kick {
+                if player.idle_for().await > limit && player.idle_kickable().await {
+                    invalid_players.entry(*id).or_insert(Removal::Idle);
                 }
             }
 
+            // nothing but packet handling until a reconfiguring player is back in play
+            if !player.in_play().await {
+                continue;
+            }
+
+            if player.take_rejoined() {
+                if let Err(why) = self.send_border_to(player).await {
+                    warn!("Failed to send border to player {id}: {why}");
+                }
+
+                if let Err(why) = self.send_entities_to(player).await {
+                    warn!("Failed to send entities to player {id}: {why}");
+                }
+            }
+
+            if player.needs_chunk_update().await {
+                tokio::spawn(Self::send_world_to(player.clone(), worlds.clone()));
+            }
+
             match player.check_teleports(None).await {
-                Err(TeleportError::TimedOut) | Err(TeleportError::WrongId(..)) => {
+                Err(why @ TeleportError::TimedOut) | Err(why @ TeleportError::WrongId(..)) => {
                     warn!("Player {} teleport failed, removing", player.0.id);
-                    invalid_players.insert(*id);
+                    invalid_players
+                        .entry(*id)
+                        .or_insert(Removal::TeleportFailed(why));
                 }
                 _ => (),
             }
         }
 
-        for id in invalid_players {
-            // TODO: kick player properly
-            players.remove(&id);
+        let queued = std::mem::take(&mut *self.commands.lock().await);
+        for (player, command) in queued {
+            if let Err(why) = commands::run(self, &players, &player, &command).await {
+                if let Err(why) = player.send_system_message(why.to_string(), false).await {
+                    warn!(
+                        "Failed to send command error to player {}: {why}",
+                        player.id()
+                    );
+                }
+            }
+        }
+
+        let chat = std::mem::take(&mut *self.chat.lock().await);
+        if !chat.is_empty() {
+            for (id, player) in &*players {
+                if !player.in_play().await {
+                    continue;
+                }
+
+                if let Err(why) = player.send_messages(&chat).await {
+                    warn!("Failed to send chat to player {id}: {why}");
+                }
+            }
+        }
+
+        let mut departed = HashMap::new();
+        for (id, removal) in invalid_players {
+            if let Some(player) = players.remove(&id) {
+                departed.insert(id, player.uuid().await);
+                player.release_slot();
+                player.log_disconnect(&removal.to_string()).await;
+                // a client that isn't reading could hold up the tick until the kick times out
+                let message = removal.kick_message();
+                tokio::spawn(async move { player.kick(message).await });
+            }
+        }
+
+        let range = state.entity_view_range;
+        let mut positions = HashMap::new();
+        let mut movements = HashMap::new();
+        for (id, player) in &*players {
+            if !player.in_play().await {
+                continue;
+            }
+
+            positions.insert(*id, (player.world().await, player.position().await));
+
+            match player.movement_packets().await {
+                Ok(Some(packets)) => {
+                    movements.insert(*id, packets);
+                }
+                Ok(None) => (),
+                Err(why) => warn!("Failed to encode movement for player {id}: {why}"),
+            }
+        }
+
+        // sending can wait on a slow client's full write queue, so everything from here on is
+        // collected and sent in the background once the player list is unlocked
+        let mut updates = Vec::new();
+        for (id, player) in &*players {
+            let Some((world, here)) = positions.get(id) else {
+                continue;
+            };
+            let visible = positions
+                .iter()
+                .filter(|(other, (other_world, pos))| {
+                    *other != id && other_world == world && in_view_range(*here, *pos, range)
+                })
+                .map(|(other, _)| *other)
+                .collect::<HashSet<u16>>();
+
+            let (entered, left) = player.update_tracked(visible.clone()).await;
+            let mut packets = BytesMut::new();
+
+            if !left.is_empty() {
+                // players who just left the server aren't in the list any more
+                let mut uuids = Vec::new();
+                for gone in &left {
+                    match players.get(gone) {
+                        Some(gone) => uuids.push(gone.uuid().await),
+                        None => uuids.extend(departed.get(gone)),
+                    }
+                }
+
+                let entity_ids = left.iter().map(|gone| *gone as i32).collect();
+                match SharedPlayer::despawn_packets(entity_ids, uuids) {
+                    Ok(despawn) => packets.unsplit(despawn),
+                    Err(why) => warn!("Failed to encode despawns for player {id}: {why}"),
+                }
+            }
+
+            for mover in visible {
+                if entered.contains(&mover) {
+                    match players[&mover].spawn_packets().await {
+                        Ok(spawn) => packets.unsplit(spawn),
+                        Err(why) => warn!("Failed to spawn player {mover} for player {id}: {why}"),
+                    }
+                    continue;
+                }
+
+                if let Some(movement) = movements.get(&mover) {
+                    packets.extend_from_slice(movement);
+                }
+            }
+
+            if !packets.is_empty() {
+                updates.push((player.clone(), packets));
+            }
+        }
+
+        let lerp = self
+            .border
+            .lock()
+            .await
+            .as_mut()
+            .and_then(|border| border.advance(Instant::now()));
+
+        if let Some(lerp) = lerp {
+            let mut encoder = Encoder::new();
+            match encoder.append_packet(&lerp) {
+                Ok(()) => {
+                    let packet = encoder.take();
+                    for player in players.values() {
+                        if player.in_play().await {
+                            updates.push((player.clone(), packet.clone()));
+                        }
+                    }
+                }
+                Err(why) => warn!("Failed to encode border lerp: {why}"),
+            }
+        }
+
+        if self.latency_update_due().await {
+            Self::broadcast_latency(&players).await;
+        }
+
+        drop(players);
+        for (player, packets) in updates {
+            player.send_in_background(packets);
         }
 
         #[cfg(feature = "timings")]
         {
             let run_end = Instant::now();
             debug!("Tick took {}ms", (run_end - run_start).as_millis());
+
+            self.packet_timings
+                .lock()
+                .expect("Failed to lock packet timings mutex")
+                .report_every(Duration::from_secs(60));
+        }
+    }
+
+    /// Sends everyone's tab list ping to everyone in play. The writes happen in the background,
+    /// so slow clients don't hold up the tick while it has the player list locked.
+    async fn broadcast_latency(players: &HashMap<u16, SharedPlayer>) {
+        let mut recipients = Vec::new();
+        let mut statuses = Vec::new();
+        for player in players.values() {
+            // players still logging in don't have a uuid yet
+            if !player.in_play().await {
+                continue;
+            }
+
+            recipients.push(player.clone());
+            if let Some(latency) = player.latency().await {
+                let latency = latency.as_millis().try_into().unwrap_or(i32::MAX);
+                statuses
+                    .push(PlayerStatus::for_player(player.uuid().await).update_latency(latency));
+            }
+        }
+
+        if statuses.is_empty() {
+            return;
+        }
+
+        let mut encoder = Encoder::new();
+        if let Err(why) = encoder.append_packet(&PlayerInfoUpdateC { players: &statuses }) {
+            warn!("Failed to encode latency update: {why}");
+            return;
+        }
+        let packet = encoder.take();
+
+        tokio::spawn(async move {
+            for player in recipients {
+                if let Err(why) = player.0.io.tx_raw(&packet).await {
+                    warn!("Failed to send latency to player {}: {why}", player.id());
+                }
+            }
+        });
+    }
+
+    /// Shows `target` to `observer`, all at once so they never see a half spawned player.
+    /// Doesn't lock the player list, so it's safe to call from the tick.
+    pub async fn spawn_player_for(
+        &self,
+        observer: &SharedPlayer,
+        target: &SharedPlayer,
+    ) -> Result<()> {
+        let packets = target.spawn_packets().await?;
+        observer.0.io.tx_raw(&packets).await
+    }
+
+    /// Whether it's been [`LATENCY_UPDATE_INTERVAL`] since tab list latency was last sent, and
+    /// if so starts the next interval.
+    async fn latency_update_due(&self) -> bool {
+        let mut last_latency_update = self.last_latency_update.lock().await;
+        if last_latency_update.elapsed() < LATENCY_UPDATE_INTERVAL {
+            return false;
+        }
+
+        *last_latency_update = Instant::now();
+        true
+    }
+
+    /// Records how long a serverbound packet took to handle.
+    #[cfg(feature = "timings")]
+    pub fn record_packet_timing(&self, id: i32, elapsed: Duration) {
+        self.packet_timings
+            .lock()
+            .expect("Failed to lock packet timings mutex")
+            .record(id, elapsed);
+    }
+
+    async fn send_world_to(
+        player: SharedPlayer,
+        worlds: Arc<HashMap<String, Arc<WorldCache>>>,
+    ) -> Result<()> {
+        // look the world up here rather than when spawning, in case the player changed worlds
+        let world = player.world().await;
+        let Some(world_cache) = worlds.get(&world) else {
+            warn!("Player {} is in unknown world {world}", player.id());
+            return Ok(());
+        };
+
+        if let Err(why) = player.stream_chunks(world_cache).await {
+            warn!("Failed to stream chunks to player {}: {why}", player.id());
+            return Err(why);
+        }
+
+        Ok(())
+    }
+
+    /// Shrinks (or grows) the world border through each `(diameter, duration)` phase in order,
+    /// starting from wherever the border is now. Replaces any shrink already running.
+    pub async fn start_border_shrink(&self, phases: Vec<(f64, Duration)>) {
+        let mut border = self.border.lock().await;
+        let diameter = match &*border {
+            Some(border) => border.diameter(Instant::now()),
+            None => self.crawlstate.border.read().await.diameter,
+        };
+
+        *border = Some(BorderShrink::new(diameter, phases));
+    }
+
+    /// Moves the world border and resizes it right away, for everyone now and anyone who joins
+    /// later. Stops any shrink in progress. Don't call this from a packet handler - it locks the
+    /// player list.
+    pub async fn set_border(&self, center_x: f64, center_z: f64, diameter: f64) -> Result<()> {
+        {
+            let mut border = self.crawlstate.border.write().await;
+            *border = Border {
+                center_x,
+                center_z,
+                diameter,
+            };
+        }
+        *self.border.lock().await = None;
+
+        let mut encoder = Encoder::new();
+        encoder.append_packet(&SetBorderCenterC {
+            x: center_x,
+            z: center_z,
+        })?;
+        encoder.append_packet(&SetBorderSizeC(diameter))?;
+        self.broadcast_raw(&encoder.take(), None).await;
+
+        Ok(())
+    }
+
+    /// Makes the red border warning show when players are within `distance` blocks of the
+    /// border, or `delay` seconds of a moving one reaching them. This works whether or not the
+    /// border is moving. Don't call this from a packet handler - it locks the player list.
+    pub async fn set_border_warning(&self, distance: i32, delay: i32) -> Result<()> {
+        {
+            let mut border_warning = self.border_warning.lock().await;
+            *border_warning = Some((distance, delay));
+        }
+
+        let mut encoder = Encoder::new();
+        encoder.append_packet(&SetBorderWarningDistanceC(distance))?;
+        encoder.append_packet(&SetBorderWarningDelayC(delay))?;
+        self.broadcast_raw(&encoder.take(), None).await;
+
+        Ok(())
+    }
+
+    /// Catches a newly joined player up on a border shrink and warning, if they've been set.
+    async fn send_border_to(&self, player: &SharedPlayer) -> Result<()> {
+        let border_warning = *self.border_warning.lock().await;
+        if let Some((distance, delay)) = border_warning {
+            player.0.io.tx(&SetBorderWarningDistanceC(distance)).await?;
+            player.0.io.tx(&SetBorderWarningDelayC(delay)).await?;
+        }
+
+        let border = self.border.lock().await;
+        let Some(border) = &*border else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        match border.current_lerp(now) {
+            Some(lerp) => player.0.io.tx(&lerp).await,
+            None => player.0.io.tx(&SetBorderSizeC(border.diameter(now))).await,
+        }
+    }
+
+    /// Where players spawn in a world. Falls back to the configured spawnpoint for unknown worlds.
+    pub async fn spawnpoint(&self, world: &str) -> (f64, f64, f64) {
+        let worlds = self.worlds.read().await;
+        worlds
+            .get(world)
+            .map_or(self.crawlstate.spawnpoint, |w| w.spawnpoint)
+    }
+
+    pub async fn get_container(&self, world: &str, x: i32, y: i32, z: i32) -> Option<Container> {
+        let worlds = self.worlds.read().await;
+        worlds.get(world)?.containers.get(&(x, y, z)).cloned()
+    }
+
+    /// Reads a world from disk again and swaps it in, then resends chunks to everyone in it. This
+    /// skips the disk cache, since the point is to pick up changes. Don't call this from a packet
+    /// handler - it locks the player list.
+    pub async fn reload_world(&self, name: &str) -> Result<()> {
+        let Some(spec) = self.crawlstate.worlds.iter().find(|w| w.name == name) else {
+            bail!("There's no world named {name}");
+        };
+
+        info!("Reloading world {} from {}", spec.name, spec.dir);
+        let dir = spec.dir.clone();
+        let crawlstate = self.crawlstate.clone();
+        let world_cache = tokio::task::spawn_blocking(move || -> Result<WorldCache> {
+            let (world, report) =
+                read_world(&dir, crawlstate.chunk_bounds, crawlstate.origin_offset)?;
+            if report.skipped_chunks() > 0 {
+                warn!("Skipped {} chunks while reloading", report.skipped_chunks());
+            }
+
+            Ok(WorldCache::from_anvil(crawlstate, &world))
+        })
+        .await??;
+
+        {
+            let mut worlds = self.worlds.write().await;
+            let mut reloaded = (**worlds).clone();
+            reloaded.insert(spec.name.clone(), Arc::new(world_cache));
+            *worlds = Arc::new(reloaded);
+        }
+
+        info!("Reloaded world {}", spec.name);
+
+        let players = self.players.lock().await;
+        for player in players.values() {
+            if player.world().await == spec.name {
+                // the next tick sees they need chunks and streams the new ones
+                if let Err(why) = player.forget_chunks().await {
+                    warn!("Failed to unload chunks for player {}: {why}", player.id());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The names of every loaded world, in the order they were given.
+    pub fn world_names(&self) -> Vec<&str> {
+        self.crawlstate
+            .worlds
+            .iter()
+            .map(|w| w.name.as_str())
+            .collect()
+    }
+
+    /// Sets what happens when a player right clicks the NPC with entity id `npc_id`, replacing
+    /// any handler it already had.
+    pub async fn set_npc_on_interact(&self, npc_id: i32, handler: NpcHandler) {
+        let mut handlers = self.npc_handlers.lock().await;
+        handlers.insert(npc_id, handler);
+    }
+
+    pub async fn npc_on_interact(&self, npc_id: i32) -> Option<NpcHandler> {
+        let handlers = self.npc_handlers.lock().await;
+        handlers.get(&npc_id).cloned()
+    }
+
+    /// Queues a command to run at the end of this tick's packet handling, when the player list is
+    /// available to it.
+    pub async fn queue_command(&self, player: SharedPlayer, command: &str) {
+        let mut commands = self.commands.lock().await;
+        commands.push((player, command.to_owned()));
+    }
+
+    /// Queues a chat message to be sent to everyone at the end of this tick's packet handling.
+    pub async fn queue_chat(&self, message: TextComponent) {
+        let mut chat = self.chat.lock().await;
+        chat.push(message);
+    }
+
+    /// Moves a player into another loaded world. Their chunks are streamed on the next tick.
+    pub async fn switch_world(&self, player: &SharedPlayer, name: &str) -> Result<()> {
+        let Some(spec) = self.crawlstate.worlds.iter().find(|w| w.name == name) else {
+            bail!("There's no world named {name}");
+        };
+
+        if player.world().await == spec.name {
+            bail!("You're already in {name}");
+        }
+
+        player.change_world(spec).await?;
+        self.send_border_to(player).await?;
+        self.send_entities_to(player).await
+    }
+
+    /// Spawns a text display, which shows floating text, and returns its entity id. Don't call
+    /// this from a packet handler - it locks the player list, which the tick already holds.
+    pub async fn spawn_text_display(
+        &self,
+        world: &str,
+        (x, y, z): (f64, f64, f64),
+        text: TextComponent,
+        billboard: Billboard,
+    ) -> Result<i32> {
+        let metadata = vec![
+            (15, Metadata::Byte(billboard.into())),
+            (23, Metadata::TextComponent(text)),
+        ];
+
+        self.spawn_entity(world, "minecraft:text_display", (x, y, z), metadata)
+            .await
+    }
+
+    /// Spawns an item display, which shows a floating item at `scale` times its normal size, and
+    /// returns its entity id. Don't call this from a packet handler either.
+    pub async fn spawn_item_display(
+        &self,
+        world: &str,
+        (x, y, z): (f64, f64, f64),
+        item: Slot,
+        scale: f32,
+    ) -> Result<i32> {
+        let metadata = vec![
+            (12, Metadata::Vector3(scale, scale, scale)),
+            (23, Metadata::Slot(item)),
+        ];
+
+        self.spawn_entity(world, "minecraft:item_display", (x, y, z), metadata)
+            .await
+    }
+
+    async fn spawn_entity(
+        &self,
+        world: &str,
+        kind: &str,
+        (x, y, z): (f64, f64, f64),
+        metadata: Vec<(u8, Metadata)>,
+    ) -> Result<i32> {
+        let kind = registries()
+            .entity_type
+            .entries
+            .get(kind)
+            .ok_or_else(|| eyre!("Couldn't find registry entry for entity type {kind}"))?
+            .protocol_id;
+
+        let entity_id = self.next_entity_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut encoder = Encoder::new();
+        encoder.append_packet(&SpawnEntityC {
+            entity_id,
+            entity_uuid: Uuid::from_u128(rand::random()),
+            kind,
+            x,
+            y,
+            z,
+            pitch: 0.0,
+            yaw: 0.0,
+            head_yaw: 0.0,
+            data: 0,
+            velocity_x: 0,
+            velocity_y: 0,
+            velocity_z: 0,
+        })?;
+        encoder.append_packet(&SetEntityMetadataC {
+            entity_id,
+            metadata,
+        })?;
+        let packets = encoder.take();
+
+        {
+            let players = self.players.lock().await;
+            for player in players.values() {
+                if player.world().await != world {
+                    continue;
+                }
+
+                if let Err(why) = player.0.io.tx_raw(&packets).await {
+                    warn!(
+                        "Failed to send entity {entity_id} to player {}: {why}",
+                        player.id()
+                    );
+                }
+            }
+        }
+
+        let mut entities = self.entities.lock().await;
+        entities.insert(
+            entity_id,
+            SpawnedEntity {
+                world: world.to_owned(),
+                packets,
+            },
+        );
+
+        Ok(entity_id)
+    }
+
+    /// Sends a packet to every player, encoding it once. Players that can't be sent to are
+    /// skipped. This locks the player list, so don't call it from the tick or a packet handler.
+    pub async fn broadcast<P: ClientboundPacket>(&self, packet: &P) -> Result<()> {
+        let mut encoder = Encoder::new();
+        encoder.append_packet(packet)?;
+        self.broadcast_raw(&encoder.take(), None).await;
+        Ok(())
+    }
+
+    /// Like [`Self::broadcast`], but skips the player with id `exclude`, e.g. whoever caused it.
+    pub async fn broadcast_except<P: ClientboundPacket>(
+        &self,
+        exclude: u16,
+        packet: &P,
+    ) -> Result<()> {
+        let mut encoder = Encoder::new();
+        encoder.append_packet(packet)?;
+        self.broadcast_raw(&encoder.take(), Some(exclude)).await;
+        Ok(())
+    }
+
+    /// Sends everyone in play the tags again. See [`SharedPlayer::send_tags`].
+    pub async fn broadcast_tags(&self) {
+        let state = self.crawlstate.clone();
+        self.broadcast_raw(&state.tag_cache.encoded_play, None)
+            .await;
+    }
+
+    async fn broadcast_raw(&self, packets: &[u8], exclude: Option<u16>) {
+        let players = self.players.lock().await;
+        for (id, player) in &*players {
+            if Some(*id) == exclude || !player.in_play().await {
+                continue;
+            }
+
+            if let Err(why) = player.0.io.tx_raw(packets).await {
+                warn!("Failed to broadcast to player {id}: {why}");
+            }
         }
     }
 
-    async fn send_world_to(player: SharedPlayer, world_cache: Arc<WorldCache>) -> Result<()> {
-        for packet in world_cache.encoded.iter() {
-            player.0.io.tx_raw(packet).await?;
+    /// Plays the red hurt flash on an entity (or player) for everyone, as if it was hit from
+    /// `yaw`. Nothing is actually damaged.
+    pub async fn play_hurt_animation(&self, entity_id: i32, yaw: f32) -> Result<()> {
+        self.broadcast(&HurtAnimationC { entity_id, yaw }).await
+    }
+
+    /// Pops up a toast in the top right for one player, the way an advancement does when it's
+    /// granted. `frame` picks the heading, e.g. "Challenge Complete!".
+    pub async fn show_toast(
+        &self,
+        player: &SharedPlayer,
+        icon: Slot,
+        title: impl Into<TextComponent>,
+        frame: AdvancementFrame,
+    ) -> Result<()> {
+        let id = format!("crawlspace:toast/{}", rand::random::<u32>());
+
+        let mut encoder = Encoder::new();
+        encoder.append_packet(&UpdateAdvancementsC::toast(&id, icon, title.into(), frame))?;
+        // the toast stays up once it's shown, so the advancement can go straight away
+        encoder.append_packet(&UpdateAdvancementsC::remove(&id))?;
+
+        player.0.io.tx_raw(&encoder.take()).await
+    }
+
+    /// Resizes an entity (or player) with the `generic.scale` attribute, where 1.0 is normal
+    /// size. Spawned entities keep their scale for players who join later, but players don't.
+    pub async fn set_scale(&self, entity_id: i32, scale: f64) -> Result<()> {
+        let mut encoder = Encoder::new();
+        encoder.append_packet(&UpdateAttributesC {
+            entity_id,
+            attributes: vec![Attribute::new("minecraft:generic.scale", scale)?],
+        })?;
+        let packets = encoder.take();
+        self.broadcast_raw(&packets, None).await;
+
+        let mut entities = self.entities.lock().await;
+        if let Some(entity) = entities.get_mut(&entity_id) {
+            entity.packets.extend_from_slice(&packets);
         }
 
         Ok(())
     }
 
-    pub fn get_container(&self, x: i32, y: i32, z: i32) -> Option<Container> {
-        self.world_cache.containers.get(&(x, y, z)).cloned()
+    /// Sends a player every spawned entity in the world they're in.
+    async fn send_entities_to(&self, player: &SharedPlayer) -> Result<()> {
+        let world = player.world().await;
+        let entities = self.entities.lock().await;
+
+        for entity in entities.values().filter(|e| e.world == world) {
+            player.0.io.tx_raw(&entity.packets).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::player::testing,
+        protocol::{
+            datatypes::VarInt,
+            packets::play::{
+                BundleDelimiterC, ChunkDataUpdateLightC, KeepAliveC, PlayerInfoUpdateC,
+                SetEquipmentC, SetHeadRotationC, SpawnEntityC, TeleportEntityC,
+                UpdateEntityPositionAndRotationC, UpdateEntityPositionC,
+            },
+            Decode, Encode, Packet,
+        },
+        world::{fixtures, Item},
+    };
+
+    /// A server with one player in its player list, as if they'd joined on a tick.
+    async fn server_with_player() -> (Arc<Server>, SharedPlayer, testing::Client) {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+        let (player, client) = testing::player(&state, 0, "alice").await;
+        server.players.lock().await.insert(0, player.clone());
+
+        (server, player, client)
+    }
+
+    fn metadata_body(entity_id: i32, fields: &[&[u8]]) -> Vec<u8> {
+        let mut body = Vec::new();
+        VarInt(entity_id).encode(&mut body).unwrap();
+        body.extend(fields.concat());
+        body.push(0xFF);
+        body
+    }
+
+    #[tokio::test]
+    async fn text_display_sends_its_text() {
+        let (server, _player, mut client) = server_with_player().await;
+        let id = server
+            .spawn_text_display("test", (0.0, 64.0, 0.0), "Hi".into(), Billboard::Center)
+            .await
+            .unwrap();
+
+        let metadata = client.next_packet(SetEntityMetadataC::ID).await;
+        let text = [
+            0x0A, 0x08, 0x00, 0x04, b't', b'e', b'x', b't', 0x00, 0x02, b'H', b'i', 0x00,
+        ];
+        let expected = metadata_body(id, &[&[15, 0, 3], &[23, 5], &text]);
+        assert_eq!(&metadata.body[..], &expected[..]);
+    }
+
+    #[tokio::test]
+    async fn players_spawn_fully_formed_in_one_bundle() {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+        let writer = testing::RecordingWriter::default();
+        let observer = testing::player_writing_to(&state, 0, "alice", writer.clone()).await;
+        let (target, _client) = testing::player(&state, 1, "bob").await;
+        let item = Item {
+            slot: 0,
+            id: "minecraft:diamond".to_owned(),
+            count: 1,
+        };
+        target
+            .set_player_inventory_slot(0, item.into())
+            .await
+            .unwrap();
+
+        server.spawn_player_for(&observer, &target).await.unwrap();
+        observer.0.io.flush().await.unwrap();
+
+        let frames = writer.frames();
+        let ids = frames.iter().map(|frame| frame.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                PlayerInfoUpdateC::ID,
+                BundleDelimiterC::ID,
+                SpawnEntityC::ID,
+                SetHeadRotationC::ID,
+                SetEntityMetadataC::ID,
+                SetEquipmentC::ID,
+                BundleDelimiterC::ID,
+            ]
+        );
+
+        // bob hasn't sent settings, so every skin layer shows and the right hand is the main one
+        let expected = metadata_body(1, &[&[17, 0, 0x7F], &[18, 0, 1]]);
+        assert_eq!(&frames[4].body[..], &expected[..]);
+
+        // entity 1, then the diamond in the main hand with more slots to come
+        assert_eq!(frames[5].body[..3], [1, 0x80, 1]);
+    }
+
+    #[tokio::test]
+    async fn toasts_are_granted_then_removed() {
+        let (server, player, mut client) = server_with_player().await;
+        server
+            .show_toast(&player, Slot::default(), "Hi", AdvancementFrame::Task)
+            .await
+            .unwrap();
+
+        // no reset, then one advancement added
+        let grant = client.next_packet(UpdateAdvancementsC::ID).await;
+        assert_eq!(grant.body[..2], [0, 1]);
+        // no reset, nothing added, then it's removed
+        let removal = client.next_packet(UpdateAdvancementsC::ID).await;
+        assert_eq!(removal.body[..3], [0, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn item_display_sends_its_item() {
+        let (server, _player, mut client) = server_with_player().await;
+        let item = Item {
+            slot: 0,
+            id: "minecraft:diamond".to_owned(),
+            count: 1,
+        };
+        let id = server
+            .spawn_item_display("test", (0.0, 64.0, 0.0), item.into(), 2.0)
+            .await
+            .unwrap();
+
+        let metadata = client.next_packet(SetEntityMetadataC::ID).await;
+        let mut scale = vec![12, 29];
+        for _ in 0..3 {
+            2.0f32.encode(&mut scale).unwrap();
+        }
+        let mut slot = vec![23, 7, 1];
+        VarInt(registries().item.entries["minecraft:diamond"].protocol_id)
+            .encode(&mut slot)
+            .unwrap();
+        slot.extend([0, 0]);
+
+        let expected = metadata_body(id, &[&scale, &slot]);
+        assert_eq!(&metadata.body[..], &expected[..]);
+    }
+
+    #[tokio::test]
+    async fn reloading_swaps_the_world_and_resends_it() {
+        let dir = fixtures::MapDir::new("reload");
+        let state = testing::state_in(&format!("test={}", dir.path()), &[]);
+        let empty = testing::world(&state, Vec::new());
+        let server = testing::server(&state, vec![("test".to_owned(), empty)]).await;
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+        server.players.lock().await.insert(0, player.clone());
+
+        let worlds = server.worlds.read().await.clone();
+        Server::send_world_to(player.clone(), worlds).await.unwrap();
+        client.drain(&player).await;
+        assert!(!player.needs_chunk_update().await);
+
+        let chunk = fixtures::chunk(0, 0, vec![fixtures::air_section(0)]);
+        dir.write_region(
+            "r.0.0.mca",
+            &fixtures::region(&[((0, 0), fixtures::compress(&chunk))]),
+        );
+        server.reload_world("test").await.unwrap();
+
+        let worlds = server.worlds.read().await.clone();
+        assert_eq!(worlds["test"].encoded.len(), 1);
+        assert!(player.needs_chunk_update().await);
+
+        Server::send_world_to(player.clone(), worlds).await.unwrap();
+        client.next_packet(ChunkDataUpdateLightC::ID).await;
+    }
+
+    #[tokio::test]
+    async fn movement_only_goes_to_players_in_range() {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+
+        let mut clients = Vec::new();
+        for (id, name, x) in [(0, "alice", 0.0), (1, "bob", 30.0), (2, "carol", 200.0)] {
+            let (player, client) = testing::player(&state, id, name).await;
+            testing::move_to(&player, x, 100.0, 0.0).await;
+            server.players.lock().await.insert(id, player.clone());
+            clients.push((player, client));
+        }
+
+        // the first tick spawns whoever's in range, and the second sends their movement
+        server.tick().await;
+        let (alice, alice_client) = &mut clients[0];
+        alice_client.drain(alice).await;
+
+        for (player, _) in &clients[1..] {
+            let (x, y, z) = player.position().await;
+            testing::move_to(player, x + 1.0, y, z).await;
+        }
+        server.tick().await;
+
+        let (alice, alice_client) = &mut clients[0];
+        alice.flush().await.unwrap();
+        let mut movers = Vec::new();
+        while let Some(frame) = alice_client
+            .try_next_frame(Duration::from_millis(100))
+            .await
+        {
+            if frame.id == UpdateEntityPositionC::ID {
+                movers.push(VarInt::decode(&mut &frame.body[..]).unwrap().0);
+            }
+        }
+        assert_eq!(movers, [1]);
+    }
+
+    /// Never finishes a write, like a client that's stopped reading.
+    struct StalledWriter;
+
+    impl tokio::io::AsyncWrite for StalledWriter {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            _buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Pending
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Pending
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Pending
+        }
+    }
+
+    #[tokio::test]
+    async fn a_stalled_client_doesnt_hold_up_the_tick() {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+
+        let alice = testing::player_writing_to(&state, 0, "alice", StalledWriter).await;
+        // fill alice's write queue, as chunks would
+        let keepalive = {
+            let mut encoder = Encoder::new();
+            encoder.append_packet(&KeepAliveC(0)).unwrap();
+            encoder.take()
+        };
+        while tokio::time::timeout(Duration::from_millis(100), alice.0.io.tx_raw(&keepalive))
+            .await
+            .is_ok()
+        {}
+
+        let (bob, mut bob_client) = testing::player(&state, 1, "bob").await;
+        testing::move_to(&bob, 4.0, 100.0, 0.0).await;
+        for player in [&alice, &bob] {
+            server
+                .players
+                .lock()
+                .await
+                .insert(player.id(), player.clone());
+        }
+
+        // alice has to be sent bob's spawn, which can't fit in her queue
+        tokio::time::timeout(Duration::from_secs(5), server.tick())
+            .await
+            .expect("the tick shouldn't wait on alice");
+        bob_client.next_packet(SpawnEntityC::ID).await;
+    }
+
+    #[tokio::test]
+    async fn idle_players_are_kicked_unless_op() {
+        let state = testing::state(&["--idle-kick-minutes", "1", "--ops", "bob"]);
+        let server = testing::server(&state, Vec::new()).await;
+
+        let mut clients = Vec::new();
+        for (id, name) in [(0, "alice"), (1, "bob")] {
+            let (player, client) = testing::player(&state, id, name).await;
+            testing::idle_for(&player, Duration::from_secs(120)).await;
+            server.players.lock().await.insert(id, player);
+            clients.push(client);
+        }
+
+        server.tick().await;
+
+        let players = server.players.lock().await;
+        assert!(!players.contains_key(&0));
+        assert!(players.contains_key(&1));
+    }
+
+    #[tokio::test]
+    async fn server_teleports_are_broadcast_as_teleports() {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+
+        let (alice, mut alice_client) = testing::player(&state, 0, "alice").await;
+        let (bob, _bob_client) = testing::player(&state, 1, "bob").await;
+        testing::move_to(&bob, 4.0, 100.0, 0.0).await;
+        for player in [&alice, &bob] {
+            server
+                .players
+                .lock()
+                .await
+                .insert(player.id(), player.clone());
+        }
+
+        server.tick().await;
+        alice_client.drain(&alice).await;
+
+        // close enough that walking there would be a relative move
+        bob.teleport(4.5, 100.0, 0.0, 0.0, 0.0).await.unwrap();
+        server.tick().await;
+
+        let ids = alice_client.drain(&alice).await;
+        assert!(ids.contains(&TeleportEntityC::ID));
+        assert!(!ids.contains(&UpdateEntityPositionC::ID));
+        assert!(!ids.contains(&UpdateEntityPositionAndRotationC::ID));
     }
 }
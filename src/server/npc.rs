@@ -0,0 +1,88 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use std::{fmt::Debug, future::Future, pin::Pin, sync::Arc};
+
+use color_eyre::eyre::Result;
+
+use crate::net::player::SharedPlayer;
+
+type HandlerFn =
+    dyn Fn(SharedPlayer) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync;
+
+/// What happens when a player right clicks an NPC. The handler gets the player that clicked, so
+/// it can look at their name, position, etc. and respond however it likes.
+#[derive(Clone)]
+pub struct NpcHandler(Arc<HandlerFn>);
+
+impl NpcHandler {
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(SharedPlayer) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Self(Arc::new(move |player| Box::pin(handler(player))))
+    }
+
+    /// Says hello to whoever clicked, by name.
+    pub fn greeting() -> Self {
+        Self::new(|player: SharedPlayer| async move {
+            let name = player.username().await;
+            player
+                .send_system_message(format!("Hello, {name}!"), false)
+                .await
+        })
+    }
+
+    pub async fn run(&self, player: SharedPlayer) -> Result<()> {
+        (self.0)(player).await
+    }
+}
+
+impl Debug for NpcHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NpcHandler")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::player::testing,
+        protocol::{packets::play::SystemChatMessageC, Packet},
+    };
+
+    #[tokio::test]
+    async fn greeting_says_hello_by_name() {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+        let (player, mut client) = testing::player(&state, 0, "alice").await;
+
+        server.set_npc_on_interact(7, NpcHandler::greeting()).await;
+        assert!(server.npc_on_interact(8).await.is_none());
+
+        let handler = server.npc_on_interact(7).await.unwrap();
+        handler.run(player).await.unwrap();
+
+        let message = client.next_packet(SystemChatMessageC::ID).await;
+        let greeting = b"Hello, alice!";
+        assert!(message.body.windows(greeting.len()).any(|w| w == greeting));
+    }
+}
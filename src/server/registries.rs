@@ -19,17 +19,56 @@
 
 use std::{collections::HashMap, sync::LazyLock};
 
+use color_eyre::eyre::{eyre, Result};
 use serde::Deserialize;
 
-pub static REGISTRIES: LazyLock<Registries> = LazyLock::new(|| {
-    serde_json::from_str(include_str!("../../assets/more_registries.json"))
-        .expect("more_registries.json should be parseable")
-});
+static REGISTRIES: LazyLock<Result<Registries, serde_json::Error>> =
+    LazyLock::new(|| serde_json::from_str(include_str!("../../assets/more_registries.json")));
+
+/// Parses the bundled more_registries.json, or returns why it couldn't be parsed. This is forced
+/// at startup so a broken file fails before anyone connects.
+pub fn try_registries() -> Result<&'static Registries> {
+    REGISTRIES
+        .as_ref()
+        .map_err(|why| eyre!("more_registries.json is not parseable: {why}"))
+}
+
+/// Gets the parsed registries. These are validated on startup, so this only panics if that was
+/// skipped.
+pub fn registries() -> &'static Registries {
+    try_registries().expect("more_registries.json should be validated at startup")
+}
 
 #[derive(Deserialize)]
 pub struct Registries {
     #[serde(rename = "minecraft:item")]
     pub item: ItemRegistry,
+    #[serde(rename = "minecraft:block")]
+    pub block: ItemRegistry,
+    #[serde(rename = "minecraft:fluid")]
+    pub fluid: ItemRegistry,
+    /// Same shape as the item registry, so it's parsed the same way.
+    #[serde(rename = "minecraft:entity_type")]
+    pub entity_type: ItemRegistry,
+    #[serde(rename = "minecraft:data_component_type")]
+    pub data_component_type: ComponentRegistry,
+    #[serde(rename = "minecraft:attribute")]
+    pub attribute: ComponentRegistry,
+}
+
+impl Registries {
+    /// The entries of the registry called `id`, e.g. `minecraft:block`, if it's one of these.
+    pub fn entries(&self, id: &str) -> Option<&HashMap<String, ItemRegistryEntry>> {
+        match id {
+            "minecraft:item" => Some(&self.item.entries),
+            "minecraft:block" => Some(&self.block.entries),
+            "minecraft:fluid" => Some(&self.fluid.entries),
+            "minecraft:entity_type" => Some(&self.entity_type.entries),
+            "minecraft:data_component_type" => Some(&self.data_component_type.entries),
+            "minecraft:attribute" => Some(&self.attribute.entries),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -39,6 +78,12 @@ pub struct ItemRegistry {
     pub entries: HashMap<String, ItemRegistryEntry>,
 }
 
+/// Like [`ItemRegistry`], but without a default entry.
+#[derive(Deserialize)]
+pub struct ComponentRegistry {
+    pub entries: HashMap<String, ItemRegistryEntry>,
+}
+
 #[derive(Deserialize)]
 pub struct ItemRegistryEntry {
     pub protocol_id: i32,
@@ -0,0 +1,36 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::BTreeMap, sync::LazyLock};
+
+use color_eyre::eyre::{eyre, Result};
+
+static TAGS: LazyLock<Result<Tags, serde_json::Error>> =
+    LazyLock::new(|| serde_json::from_str(include_str!("../../assets/tags.json")));
+
+/// Entry names in each tag, by registry and then tag name. These are sorted so tags go out in
+/// the same order every time.
+pub type Tags = BTreeMap<String, BTreeMap<String, Vec<String>>>;
+
+/// Parses the bundled tags.json, or returns why it couldn't be parsed. This is forced at startup
+/// so a broken file fails before anyone connects.
+pub fn try_tags() -> Result<&'static Tags> {
+    TAGS.as_ref()
+        .map_err(|why| eyre!("tags.json is not parseable: {why}"))
+}
@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::HashMap, time::Duration};
+
+use tokio::time::Instant;
+
+/// Number of histogram buckets. Bucket `n` holds handle times under 2^n microseconds, and the
+/// last one holds everything slower.
+const BUCKETS: usize = 20;
+
+/// How long handling each kind of serverbound packet has taken since the last report, for
+/// finding slow handlers.
+#[derive(Debug)]
+pub struct PacketTimings {
+    since: Instant,
+    by_id: HashMap<i32, Histogram>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    count: u64,
+    total: Duration,
+    max: Duration,
+    buckets: [u64; BUCKETS],
+}
+
+impl Histogram {
+    fn record(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+
+        let micros = elapsed.as_micros() as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+    }
+
+    /// An upper bound on the `p`th percentile, going by bucket edges.
+    fn percentile(&self, p: f64) -> Duration {
+        let target = (self.count as f64 * p).ceil() as u64;
+        let mut seen = 0;
+
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target && bucket < BUCKETS - 1 {
+                return Duration::from_micros(1 << bucket);
+            }
+        }
+
+        self.max
+    }
+}
+
+impl PacketTimings {
+    pub fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            by_id: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, id: i32, elapsed: Duration) {
+        self.by_id.entry(id).or_default().record(elapsed);
+    }
+
+    /// Logs every packet id handled since the last report, slowest in total first, then starts
+    /// over. Does nothing until `interval` has passed.
+    pub fn report_every(&mut self, interval: Duration) {
+        if self.since.elapsed() < interval {
+            return;
+        }
+
+        let mut by_id = std::mem::take(&mut self.by_id)
+            .into_iter()
+            .collect::<Vec<_>>();
+        by_id.sort_by(|(_, a), (_, b)| b.total.cmp(&a.total));
+
+        debug!(
+            "Packet handle times over the last {}s:",
+            self.since.elapsed().as_secs()
+        );
+        for (id, histogram) in by_id {
+            debug!(
+                "  0x{id:02X}: {} handled, {:?} total, {:?} mean, p99 < {:?}, max {:?}",
+                histogram.count,
+                histogram.total,
+                histogram.total / histogram.count as u32,
+                histogram.percentile(0.99),
+                histogram.max,
+            );
+        }
+
+        self.since = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::player::testing,
+        protocol::{packets::play::SetPlayerRotationS, Encode, Packet},
+    };
+
+    #[tokio::test]
+    async fn handled_packets_are_timed() {
+        let state = testing::state(&[]);
+        let server = testing::server(&state, Vec::new()).await;
+        let (player, _client) = testing::player(&state, 0, "alice").await;
+
+        let mut body = Vec::new();
+        90.0f32.encode(&mut body).unwrap();
+        0.0f32.encode(&mut body).unwrap();
+        true.encode(&mut body).unwrap();
+        testing::queue(&player, testing::frame(SetPlayerRotationS::ID, &body)).await;
+        player.handle_all_packets().await.unwrap();
+
+        let timings = server.packet_timings.lock().unwrap();
+        let histogram = &timings.by_id[&SetPlayerRotationS::ID];
+        assert_eq!(histogram.count, 1);
+        assert!(histogram.total > Duration::ZERO);
+    }
+
+    #[test]
+    fn percentiles_round_up_to_a_bucket_edge() {
+        let mut histogram = Histogram::default();
+        for micros in [1, 3, 3, 100] {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        assert_eq!(histogram.percentile(0.5), Duration::from_micros(4));
+        assert_eq!(histogram.percentile(0.99), Duration::from_micros(128));
+    }
+}
@@ -17,19 +17,19 @@
  * <https://www.gnu.org/licenses/>.
  */
 
-use crate::protocol::datatypes::TextComponent;
+use crate::protocol::datatypes::{Slot, TextComponent};
 
 #[derive(Debug)]
 pub struct Window {
     pub id: u8,
     pub kind: WindowType,
     pub title: TextComponent,
-    // slots: Vec<Slot>,
+    /// What the window showed when it was opened, for putting things back after a click.
+    pub slots: Vec<Slot>,
 }
 
 #[derive(Debug, Clone, Copy)]
 #[repr(i32)]
-#[allow(unused)]
 pub enum WindowType {
     Generic9x1,
     Generic9x2,
@@ -17,16 +17,28 @@
  * <https://www.gnu.org/licenses/>.
  */
 
-use std::sync::{atomic::AtomicUsize, Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{atomic::AtomicUsize, Arc},
+    time::Duration,
+};
 
+use color_eyre::eyre::{bail, ensure, eyre, Context, Result};
 use tokio::sync::{mpsc, Mutex, RwLock, Semaphore};
 use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
 
 use crate::{
     args::Args,
-    net::{cache::RegistryCache, player::SharedPlayer},
-    protocol::packets::login::registry::ALL_REGISTRIES,
-    server::Server,
+    net::{
+        cache::{RegistryCache, TagCache},
+        connection_log::ConnectionLog,
+        player::{ChatEvent, SharedPlayer},
+    },
+    protocol::packets::{login::registry::all_registries, play::Gamemode},
+    server::{tags::try_tags, Server},
+    world::{ChunkBounds, WorldSpec},
 };
 
 #[derive(Debug)]
@@ -34,63 +46,263 @@ pub struct State {
     pub max_players: usize,
     pub current_players: AtomicUsize,
     pub description: String,
+    /// The tab list header and footer. Empty ones aren't shown.
+    pub tab_header: String,
+    pub tab_footer: String,
+    /// MOTDs to use instead of `description` for specific hostnames.
+    pub virtual_hosts: HashMap<String, String>,
     pub version_name: String,
     pub version_number: i32,
-    pub addr: String,
+    /// The address to listen on, brackets already stripped.
+    pub addr: IpAddr,
     pub port: u16,
     pub velocity_forwarding: bool,
+    pub feature_flags: Vec<String>,
+    pub resource_pack: Option<ResourcePack>,
 
     pub registry_cache: RegistryCache,
+    pub tag_cache: TagCache,
 
     pub player_send: mpsc::Sender<SharedPlayer>,
     pub player_recv: Mutex<mpsc::Receiver<SharedPlayer>>,
+    /// Every chat message players send. Messages are dropped while this is full, so take them
+    /// off regularly if you're listening.
+    pub chat_send: mpsc::Sender<ChatEvent>,
+    pub chat_recv: Mutex<mpsc::Receiver<ChatEvent>>,
 
     pub shutdown_token: CancellationToken,
 
     pub net_sema: Arc<Semaphore>,
 
+    /// Every world to load. The first is the one players spawn in.
+    pub worlds: Vec<WorldSpec>,
     pub spawnpoint: (f64, f64, f64),
-    pub border_radius: i32,
+    /// Whether each world's spawn should be moved onto solid ground near `spawnpoint`.
+    pub safe_spawn: bool,
+    /// Which chunks of each world get loaded.
+    pub chunk_bounds: ChunkBounds,
+    /// How many chunks each world is moved by on x and z after loading.
+    pub origin_offset: (i32, i32),
+    /// The world border as it was last set. Starts centered on `spawnpoint`.
+    pub border: RwLock<Border>,
+    /// How far away, in blocks, other players' movement is sent to a player.
+    pub entity_view_range: f64,
+
+    pub connection_log: Option<ConnectionLog>,
+    pub cache_dir: Option<String>,
+    /// Whether world caches are written gzipped.
+    pub cache_compression: bool,
+    /// The smallest packet that gets compressed, if compression is on.
+    #[cfg(feature = "compression")]
+    pub compression_threshold: Option<usize>,
+    /// The server keypair and session server client, if online mode is on.
+    #[cfg(feature = "encryption")]
+    pub authenticator: Option<crate::net::auth::Authenticator>,
+    /// Whether refreshing an open container closes and reopens it instead of just resending it.
+    pub reopen_containers: bool,
+    /// Usernames allowed to run operator-only commands.
+    pub ops: HashSet<String>,
+    /// How long players can be idle before they're kicked, if at all.
+    pub idle_kick: Option<Duration>,
+    pub idle_kick_exempt: HashSet<String>,
+
+    /// The gamemode players are put in.
+    pub gamemode: Gamemode,
+    /// Whether the client stops ticking the world, freezing time and anything animated.
+    pub freeze_time: bool,
+    /// Whether clicks in containers are allowed to move items around client side. When false,
+    /// the container is put back after every click.
+    pub container_editing: bool,
 
     server: RwLock<Option<Arc<Server>>>,
 }
 
+/// A resource pack players are sent on join.
+#[derive(Debug)]
+pub struct ResourcePack {
+    pub uuid: Uuid,
+    pub url: String,
+    pub hash: String,
+    pub forced: bool,
+}
+
+/// How many chat messages can wait in [`State::chat_recv`] before new ones are dropped.
+const CHAT_QUEUE_SIZE: usize = 256;
+
+/// Where the world border is and how big it is, not counting any shrink in progress.
+#[derive(Debug, Clone, Copy)]
+pub struct Border {
+    pub center_x: f64,
+    pub center_z: f64,
+    pub diameter: f64,
+}
+
+/// Parses a listen address, with or without the brackets around IPv6 addresses.
+fn parse_addr(addr: &str) -> Result<IpAddr> {
+    let unbracketed = addr
+        .strip_prefix('[')
+        .and_then(|a| a.strip_suffix(']'))
+        .unwrap_or(addr);
+
+    unbracketed
+        .parse()
+        .wrap_err_with(|| format!("{addr:?} isn't an IP address to listen on"))
+}
+
 impl State {
-    #[must_use]
-    pub fn new(version_name: &str, version_number: i32, args: Args) -> Self {
+    pub fn new(version_name: &str, version_number: i32, args: Args) -> Result<Self> {
         let max = args.max_players.min(Semaphore::MAX_PERMITS);
 
         if max < args.max_players {
             warn!("Requested max player count {} is less than max semaphore permits {max} - limited to {max}.", args.max_players);
         }
 
+        ensure!(
+            args.chunk_radius > 0,
+            "chunk radius has to be at least 1, not {}",
+            args.chunk_radius
+        );
+
+        let origin_offset = match args.origin_offset[..] {
+            [] => (0, 0),
+            [x, z] => (x, z),
+            _ => bail!(
+                "origin offset needs an x and a z, not {:?}",
+                args.origin_offset
+            ),
+        };
+
         let (player_send, player_recv) = mpsc::channel(16);
+        let (chat_send, chat_recv) = mpsc::channel(CHAT_QUEUE_SIZE);
         let shutdown_token = CancellationToken::new();
 
-        Self {
+        let registry_cache =
+            RegistryCache::try_from(all_registries()?).context("Failed to build registry cache")?;
+        let tag_cache = TagCache::try_from(try_tags()?).context("Failed to build tag cache")?;
+
+        let worlds = args
+            .map_dirs
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| WorldSpec::parse(arg, i == 0))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut names = HashSet::new();
+        for world in &worlds {
+            ensure!(
+                names.insert(&world.name),
+                "world name {} is used more than once",
+                world.name
+            );
+        }
+
+        let virtual_hosts = args
+            .virtual_host
+            .iter()
+            .map(|arg| {
+                let (host, motd) = arg
+                    .split_once('=')
+                    .ok_or_else(|| eyre!("virtual host {arg:?} should look like host=motd"))?;
+                Ok((host.trim_end_matches('.').to_lowercase(), motd.to_owned()))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let resource_pack = match args.resource_pack {
+            None => None,
+            Some(url) => {
+                let hash = args.resource_pack_hash.unwrap_or_default().to_lowercase();
+                ensure!(
+                    hash.is_empty()
+                        || (hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit())),
+                    "resource pack hash should be 40 hex characters, got {hash:?}"
+                );
+
+                Some(ResourcePack {
+                    uuid: Uuid::from_u128(rand::random()),
+                    url,
+                    hash,
+                    forced: args.resource_pack_forced,
+                })
+            }
+        };
+
+        let connection_log = args
+            .connection_log
+            .as_deref()
+            .map(ConnectionLog::open)
+            .transpose()?;
+
+        Ok(Self {
             max_players: max,
             current_players: AtomicUsize::new(0),
             description: args.motd,
+            tab_header: args.tab_header,
+            tab_footer: args.tab_footer,
+            virtual_hosts,
             version_name: version_name.to_owned(),
             version_number: version_number.to_owned(),
-            addr: args.addr,
+            addr: parse_addr(&args.addr)?,
             port: args.port,
             velocity_forwarding: args.velocity_forwarding,
+            feature_flags: args.feature_flags,
+            resource_pack,
 
-            registry_cache: RegistryCache::from(&*ALL_REGISTRIES),
+            registry_cache,
+            tag_cache,
 
             player_send,
             player_recv: Mutex::new(player_recv),
+            chat_send,
+            chat_recv: Mutex::new(chat_recv),
 
             shutdown_token,
 
             net_sema: Arc::new(Semaphore::new(max)),
 
+            worlds,
             spawnpoint: (args.spawn_x, args.spawn_y, args.spawn_z),
-            border_radius: args.border_radius,
+            safe_spawn: args.safe_spawn,
+            chunk_bounds: ChunkBounds::from_radius(args.chunk_radius),
+            origin_offset,
+            border: RwLock::new(Border {
+                center_x: args.spawn_x,
+                center_z: args.spawn_z,
+                diameter: args.border_radius as f64 * 2.0,
+            }),
+            entity_view_range: args.entity_view_range,
+
+            connection_log,
+            cache_dir: args.cache_dir,
+            cache_compression: args.cache_compression,
+            #[cfg(feature = "compression")]
+            compression_threshold: usize::try_from(args.compression_threshold).ok(),
+            #[cfg(feature = "encryption")]
+            authenticator: args
+                .online_mode
+                .then(crate::net::auth::Authenticator::new)
+                .transpose()?,
+            reopen_containers: args.reopen_containers,
+            ops: args.ops.into_iter().collect(),
+            idle_kick: args
+                .idle_kick_minutes
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+            idle_kick_exempt: args.idle_kick_exempt.into_iter().collect(),
+
+            gamemode: match args.showcase {
+                true => Gamemode::Spectator,
+                false => Gamemode::Creative,
+            },
+            freeze_time: args.showcase,
+            container_editing: !args.showcase,
 
             server: RwLock::new(None),
-        }
+        })
+    }
+
+    /// The MOTD for a client that connected with `host`, falling back to the default one.
+    pub fn motd_for(&self, host: Option<&str>) -> &str {
+        host.and_then(|h| self.virtual_hosts.get(h))
+            .unwrap_or(&self.description)
     }
 
     pub async fn set_server(&self, server: Arc<Server>) {
@@ -105,3 +317,54 @@ impl State {
             .expect("state.get_server called before server initialized")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::player::testing,
+        protocol::{
+            datatypes::{Bounded, VarInt},
+            packets::login::HandshakeS,
+            Decode, Encode,
+        },
+    };
+
+    #[test]
+    fn showcase_sets_read_only_defaults() {
+        let state = testing::state(&["--showcase"]);
+        assert!(matches!(state.gamemode, Gamemode::Spectator));
+        assert!(state.freeze_time);
+        assert!(!state.container_editing);
+
+        let state = testing::state(&[]);
+        assert!(matches!(state.gamemode, Gamemode::Creative));
+        assert!(!state.freeze_time);
+        assert!(state.container_editing);
+    }
+
+    #[test]
+    fn virtual_host_picks_its_motd() {
+        let state = testing::state(&[
+            "--motd",
+            "Default",
+            "--virtual-host",
+            "play.example.com=Example;Other.Example.com.=Other",
+        ]);
+
+        let mut body = Vec::new();
+        VarInt(767).encode(&mut body).unwrap();
+        Bounded::<&str, 255>("Play.Example.com.\0FML3\0")
+            .encode(&mut body)
+            .unwrap();
+        body.extend(25565u16.to_be_bytes());
+        VarInt(1).encode(&mut body).unwrap();
+        let handshake = HandshakeS::decode(&mut &body[..]).unwrap();
+
+        assert_eq!(handshake.host(), "play.example.com");
+        assert_eq!(state.motd_for(Some(&handshake.host())), "Example");
+        assert_eq!(state.motd_for(Some("other.example.com")), "Other");
+        assert_eq!(state.motd_for(Some("unknown.example.com")), "Default");
+        assert_eq!(state.motd_for(None), "Default");
+    }
+}
@@ -21,6 +21,8 @@ use color_eyre::eyre::{bail, Result};
 use fastnbt::Value;
 use serde::Deserialize;
 
+use crate::protocol::datatypes::TextComponent;
+
 macro_rules! get_tag {
     ($data:expr, $tag_kind:path, $tag_name:literal) => {{
         {
@@ -75,6 +77,22 @@ impl BlockEntity {
         })
     }
 
+    /// The name the block was given in an anvil, if any. Names that aren't valid JSON are used
+    /// as plain text.
+    pub fn custom_name(&self) -> Option<TextComponent> {
+        let Value::Compound(ref data) = self.raw_data else {
+            return None;
+        };
+
+        match data.get("CustomName")? {
+            Value::String(name) => Some(TextComponent::from_json(name).unwrap_or_else(|why| {
+                debug!("CustomName {name:?} isn't a JSON text component ({why}), using it as is");
+                name.as_str().into()
+            })),
+            _ => None,
+        }
+    }
+
     pub fn try_get_items(&self) -> Result<Vec<Item>> {
         match self.id.as_str() {
             "minecraft:chest" | "minecraft:trapped_chest" | "minecraft:barrel" => {
@@ -88,8 +106,8 @@ impl BlockEntity {
                 let items = get_tag!(data, Value::List, "Items");
                 Ok(items
                     .iter()
-                    .map(|i| fastnbt::from_value::<Item>(i).expect("Failed to parse item"))
-                    .collect())
+                    .map(fastnbt::from_value::<Item>)
+                    .collect::<Result<Vec<_>, _>>()?)
             }
             id => bail!("try_get_items called on not a container ({id})"),
         }
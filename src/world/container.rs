@@ -17,12 +17,16 @@
  * <https://www.gnu.org/licenses/>.
  */
 
-use crate::protocol::datatypes::Slot;
+use crate::protocol::datatypes::{Slot, TextComponent};
 
 use super::BlockEntity;
 
 #[derive(Debug, Clone)]
-pub struct Container(pub Vec<Slot>);
+pub struct Container {
+    pub slots: Vec<Slot>,
+    /// The window title, from the block entity's `CustomName` if it has one.
+    pub title: TextComponent,
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum ContainerCreationError {
@@ -42,6 +46,13 @@ impl TryFrom<BlockEntity> for Container {
                     .try_get_items()
                     .map_err(|e| ContainerCreationError::ParseError(e))?;
 
+                let title = value
+                    .custom_name()
+                    .unwrap_or_else(|| match value.id.as_str() {
+                        "minecraft:barrel" => "Barrel".into(),
+                        _ => "Chest".into(),
+                    });
+
                 let mut slots = vec![Slot::default(); 27];
 
                 for item in items {
@@ -49,9 +60,44 @@ impl TryFrom<BlockEntity> for Container {
                     slots[slot_index] = Slot::from(item);
                 }
 
-                Ok(Self(slots))
+                Ok(Self { slots, title })
             }
             _ => Err(ContainerCreationError::NotAContainer),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fastnbt::Value;
+
+    use super::*;
+    use crate::world::fixtures;
+
+    #[test]
+    fn named_chests_are_titled() {
+        let mut chunk = fixtures::chunk_with_chest(&[]);
+        let Value::Compound(chest) = &mut chunk.block_entities[0] else {
+            unreachable!("fixture chests are compounds");
+        };
+        chest.insert(
+            "CustomName".to_owned(),
+            Value::String(r#"{"text":"Loot","color":"gold"}"#.to_owned()),
+        );
+
+        let chest = BlockEntity::try_parse(chunk.block_entities[0].clone()).unwrap();
+        let container = Container::try_from(chest).unwrap();
+        assert_eq!(
+            container.title.to_json().unwrap(),
+            r#"{"text":"Loot","color":"gold"}"#
+        );
+    }
+
+    #[test]
+    fn unnamed_chests_are_just_chests() {
+        let chunk = fixtures::chunk_with_chest(&[]);
+        let chest = BlockEntity::try_parse(chunk.block_entities[0].clone()).unwrap();
+        let container = Container::try_from(chest).unwrap();
+        assert_eq!(container.title.to_json().unwrap(), r#"{"text":"Chest"}"#);
+    }
+}
@@ -0,0 +1,233 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+//! Worlds built in memory, so tests don't need real maps checked in.
+
+use std::{collections::HashMap, io::Write, path::PathBuf};
+
+use fastnbt::{LongArray, Value};
+use flate2::{write::ZlibEncoder, Compression};
+
+use super::{Biomes, Block, BlockStates, Chunk, ChunkStatus, Item, Section};
+
+/// The data version 1.21.1 writes chunks with.
+const DATA_VERSION: i32 = 3955;
+
+/// A map directory with an empty `region` folder, deleted again when dropped.
+pub struct MapDir(PathBuf);
+
+impl MapDir {
+    /// Creates a map directory, `name` keeping it apart from other tests' maps.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("crawlspace-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(path.join("region")).expect("failed to create map dir");
+
+        Self(path)
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.to_str().expect("temp dir should be utf-8")
+    }
+
+    /// Writes `bytes` to a file in the region folder.
+    pub fn write_region(&self, file_name: &str, bytes: &[u8]) {
+        let path = self.0.join("region").join(file_name);
+        std::fs::write(path, bytes).expect("failed to write region");
+    }
+}
+
+impl Drop for MapDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Builds a region file holding `chunks`, each given as its chunk position and the payload that
+/// goes in its sectors. Payloads are marked as zlib compressed, see [`compress`].
+pub fn region(chunks: &[((i32, i32), Vec<u8>)]) -> Vec<u8> {
+    let mut locations = vec![0u8; 4096];
+    let timestamps = vec![0u8; 4096];
+    let mut sectors = Vec::new();
+
+    for ((x, z), payload) in chunks {
+        let offset = 2 + sectors.len() / 4096;
+
+        let mut stored = Vec::new();
+        stored.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+        stored.push(2);
+        stored.extend_from_slice(payload);
+        stored.resize(stored.len().div_ceil(4096) * 4096, 0);
+
+        let index = 4 * ((x & 31) + (z & 31) * 32) as usize;
+        locations[index..index + 3].copy_from_slice(&(offset as u32).to_be_bytes()[1..]);
+        locations[index + 3] = (stored.len() / 4096) as u8;
+
+        sectors.extend_from_slice(&stored);
+    }
+
+    [locations, timestamps, sectors].concat()
+}
+
+/// Serializes and compresses a chunk the way it's stored in a region file.
+pub fn compress(chunk: &Chunk) -> Vec<u8> {
+    let nbt = fastnbt::to_bytes(chunk).expect("fixture chunk should serialize");
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&nbt).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// A chunk at chunk position `(x, z)` with the given sections and nothing else.
+pub fn chunk(x: i32, z: i32, sections: Vec<Section>) -> Chunk {
+    Chunk {
+        _data_version: DATA_VERSION,
+        x_pos: x,
+        z_pos: z,
+        _y_pos: -4,
+        _status: ChunkStatus::Full,
+        _last_update: 0.0,
+        sections,
+        block_entities: Vec::new(),
+    }
+}
+
+/// A section at section height `y` that's entirely air.
+pub fn air_section(y: i32) -> Section {
+    Section {
+        y,
+        block_states: BlockStates {
+            palette: vec![block("minecraft:air")],
+            data: None,
+        },
+        _biomes: Biomes {
+            _palette: vec!["minecraft:plains".to_owned()],
+            _data: None,
+        },
+        _block_light: None,
+        _sky_light: None,
+    }
+}
+
+/// A section at section height `y` that's air apart from `block` in its bottom corner.
+pub fn single_block_section(y: i32, block: Block) -> Section {
+    // two palette entries still take 4 bits each, so 16 blocks to a long
+    let mut data = vec![0; 256];
+    data[0] = 1;
+
+    let mut section = air_section(y);
+    section.block_states.palette.push(block);
+    section.block_states.data = Some(LongArray::new(data));
+    section
+}
+
+/// Chunk (0, 0) with a single `block` at (0, 64, 0), and air everywhere else.
+pub fn single_block_chunk(block: &str) -> Chunk {
+    chunk(0, 0, vec![single_block_section(4, self::block(block))])
+}
+
+/// Chunk (0, 0) with a chest at (0, 64, 0) holding `items`.
+pub fn chunk_with_chest(items: &[Item]) -> Chunk {
+    let chest = Block {
+        name: "minecraft:chest".to_owned(),
+        properties: HashMap::from(
+            [
+                ("facing", "north"),
+                ("type", "single"),
+                ("waterlogged", "false"),
+            ]
+            .map(|(k, v)| (k.to_owned(), v.to_owned())),
+        ),
+    };
+
+    let items = items
+        .iter()
+        .map(|item| {
+            Value::Compound(HashMap::from([
+                ("Slot".to_owned(), Value::Byte(item.slot)),
+                ("id".to_owned(), Value::String(item.id.clone())),
+                ("count".to_owned(), Value::Int(item.count)),
+            ]))
+        })
+        .collect();
+
+    let mut chunk = chunk(0, 0, vec![single_block_section(4, chest)]);
+    chunk.block_entities.push(Value::Compound(HashMap::from([
+        ("id".to_owned(), Value::String("minecraft:chest".to_owned())),
+        ("keepPacked".to_owned(), Value::Byte(0)),
+        ("x".to_owned(), Value::Int(0)),
+        ("y".to_owned(), Value::Int(64)),
+        ("z".to_owned(), Value::Int(0)),
+        ("Items".to_owned(), Value::List(items)),
+    ])));
+    chunk
+}
+
+/// A block state with no properties.
+pub fn block(name: &str) -> Block {
+    Block {
+        name: name.to_owned(),
+        properties: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::{cache::WorldCache, player::testing},
+        protocol::{packets::play::ChunkDataUpdateLightC, Encode},
+        world::{blocks::Blocks, World},
+    };
+
+    fn stone(slot: i8, count: i32) -> Item {
+        Item {
+            slot,
+            id: "minecraft:stone".to_owned(),
+            count,
+        }
+    }
+
+    #[test]
+    fn fixture_chunks_encode() {
+        let state = testing::state(&[]);
+        let blocks = Blocks::new();
+
+        for chunk in [
+            single_block_chunk("minecraft:stone"),
+            chunk_with_chest(&[stone(0, 1), stone(5, 64)]),
+        ] {
+            let mut buf = Vec::new();
+            ChunkDataUpdateLightC::new(state.clone(), &chunk, &blocks)
+                .encode(&mut buf)
+                .unwrap();
+            assert!(!buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn chest_fixture_is_a_container() {
+        let state = testing::state(&[]);
+        let chunk = chunk_with_chest(&[stone(0, 1), stone(5, 64)]);
+        let world = World(HashMap::from([((0, 0), chunk)]));
+
+        let cache = WorldCache::from_anvil(state, &world);
+        assert!(cache.containers.contains_key(&(0, 64, 0)));
+    }
+}
@@ -19,14 +19,16 @@
 
 use std::{collections::HashMap, fs::File, path::Path};
 
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{ensure, eyre, Context, Result};
 use fastanvil::Region;
 use rayon::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub mod block_entity;
 pub mod blocks;
 pub mod container;
+#[cfg(test)]
+pub mod fixtures;
 
 pub use block_entity::*;
 pub use container::*;
@@ -34,7 +36,59 @@ pub use container::*;
 #[derive(Clone, Debug)]
 pub struct World(pub HashMap<(i32, i32), Chunk>);
 
-#[derive(Clone, Debug, Deserialize)]
+/// Blocks a player can stand in. Anything else counts as solid for finding a spawn.
+const PASSABLE: &[&str] = &[
+    "minecraft:air",
+    "minecraft:cave_air",
+    "minecraft:void_air",
+    "minecraft:water",
+    "minecraft:lava",
+];
+
+/// Blocks that don't count towards heightmaps.
+const AIR: &[&str] = &["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+impl Block {
+    pub fn is_air(&self) -> bool {
+        AIR.contains(&self.name.as_str())
+    }
+}
+
+impl World {
+    /// Looks up the block at a position, or `None` if that chunk or section wasn't loaded.
+    pub fn block_at(&self, x: i32, y: i32, z: i32) -> Option<&Block> {
+        let chunk = self.0.get(&(x.div_euclid(16), z.div_euclid(16)))?;
+        let section = chunk.sections.iter().find(|s| s.y == y.div_euclid(16))?;
+        section.block(x.rem_euclid(16), y.rem_euclid(16), z.rem_euclid(16))
+    }
+
+    fn is_passable(&self, x: i32, y: i32, z: i32) -> bool {
+        self.block_at(x, y, z)
+            .is_none_or(|b| PASSABLE.contains(&b.name.as_str()))
+    }
+
+    /// Searches up and down from `spawn`, nearest first, for a spot with two passable blocks over
+    /// a solid one, and returns the top of that solid block. Gives up after `range` blocks either
+    /// way.
+    pub fn find_safe_spawn(
+        &self,
+        (x, y, z): (f64, f64, f64),
+        range: i32,
+    ) -> Option<(f64, f64, f64)> {
+        let (bx, by, bz) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+
+        (0..=range)
+            .flat_map(|offset| [by - offset, by + offset])
+            .find(|&y| {
+                !self.is_passable(bx, y - 1, bz)
+                    && self.is_passable(bx, y, bz)
+                    && self.is_passable(bx, y + 1, bz)
+            })
+            .map(|y| (x, y as f64, z))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Chunk {
     #[serde(rename = "DataVersion")]
     pub _data_version: i32,
@@ -52,7 +106,27 @@ pub struct Chunk {
     pub block_entities: Vec<fastnbt::Value>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl Chunk {
+    /// Moves the chunk, and its block entities with it, by `(x, z)` chunks.
+    fn shift(&mut self, (x, z): (i32, i32)) {
+        self.x_pos += x;
+        self.z_pos += z;
+
+        for block_entity in &mut self.block_entities {
+            let fastnbt::Value::Compound(block_entity) = block_entity else {
+                continue;
+            };
+
+            for (key, blocks) in [("x", x * 16), ("z", z * 16)] {
+                if let Some(fastnbt::Value::Int(coord)) = block_entity.get_mut(key) {
+                    *coord += blocks;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum ChunkStatus {
     #[serde(rename = "minecraft:empty")]
     Empty,
@@ -80,26 +154,49 @@ pub enum ChunkStatus {
     Full,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Section {
     #[serde(rename = "Y")]
     pub y: i32,
     pub block_states: BlockStates,
     #[serde(rename = "biomes")]
     pub _biomes: Biomes,
-    #[serde(rename = "BlockLight")]
+    #[serde(rename = "BlockLight", skip_serializing_if = "Option::is_none")]
     pub _block_light: Option<fastnbt::ByteArray>,
-    #[serde(rename = "SkyLight")]
+    #[serde(rename = "SkyLight", skip_serializing_if = "Option::is_none")]
     pub _sky_light: Option<fastnbt::ByteArray>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl Section {
+    /// Looks up a block by its position within the section, each coordinate 0-15.
+    pub fn block(&self, x: i32, y: i32, z: i32) -> Option<&Block> {
+        let states = &self.block_states;
+
+        let index = match states.data {
+            None => 0,
+            Some(ref data) => {
+                let bits =
+                    (usize::BITS - states.palette.len().saturating_sub(1).leading_zeros()).max(4);
+                let per_long = 64 / bits as usize;
+                let i = (y * 256 + z * 16 + x) as usize;
+
+                let long = *data.get(i / per_long)? as u64;
+                ((long >> ((i % per_long) as u32 * bits)) & ((1 << bits) - 1)) as usize
+            }
+        };
+
+        states.palette.get(index)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BlockStates {
     pub palette: Vec<Block>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<fastnbt::LongArray>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Block {
     #[serde(rename = "Name")]
     pub name: String,
@@ -107,39 +204,178 @@ pub struct Block {
     pub properties: HashMap<String, String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Biomes {
     #[serde(rename = "palette")]
     pub _palette: Vec<String>,
-    #[serde(rename = "data")]
+    #[serde(rename = "data", skip_serializing_if = "Option::is_none")]
     pub _data: Option<fastnbt::LongArray>,
 }
 
-pub fn read_world(path: &str) -> Result<World> {
+/// A world crawlspace was asked to load, given as `name=dir` or just `dir`.
+#[derive(Clone, Debug)]
+pub struct WorldSpec {
+    pub name: String,
+    pub dir: String,
+    /// The dimension identifier clients are told they're in while in this world.
+    pub dimension_name: String,
+}
+
+impl WorldSpec {
+    /// Parses a world argument. The `default` world keeps the `minecraft:the_end` dimension name
+    /// so single-world setups look the same as they always have.
+    pub fn parse(arg: &str, default: bool) -> Result<Self> {
+        let (name, dir) = match arg.split_once('=') {
+            Some((name, dir)) => (name.to_owned(), dir.to_owned()),
+            None => {
+                let name = Path::new(arg)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_lowercase())
+                    .ok_or_else(|| eyre!("can't work out a world name from {arg}, use name=dir"))?;
+                (name, arg.to_owned())
+            }
+        };
+
+        ensure!(
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-' | '.')),
+            "world name {name:?} can only contain a-z, 0-9, _, - and ."
+        );
+
+        let dimension_name = match default {
+            true => "minecraft:the_end".to_owned(),
+            false => format!("crawlspace:{name}"),
+        };
+
+        Ok(Self {
+            name,
+            dir,
+            dimension_name,
+        })
+    }
+}
+
+/// What went wrong (or didn't) while loading a world.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub chunks_loaded: usize,
+    /// A description of every chunk that couldn't be parsed and was skipped.
+    pub failed_chunks: Vec<String>,
+    /// A description of every region file that couldn't be opened and was skipped.
+    pub failed_regions: Vec<String>,
+}
+
+impl LoadReport {
+    /// How many chunks were skipped. Chunks in skipped regions aren't counted, since there's no
+    /// way to know how many a broken region held.
+    pub fn skipped_chunks(&self) -> usize {
+        self.failed_chunks.len()
+    }
+}
+
+/// Which chunks to load from a world, in chunk coordinates. Mins are inclusive, maxes exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkBounds {
+    pub min_x: i32,
+    pub max_x: i32,
+    pub min_z: i32,
+    pub max_z: i32,
+}
+
+impl ChunkBounds {
+    /// A square of `radius` chunks each way around chunk (0, 0).
+    pub fn from_radius(radius: i32) -> Self {
+        Self {
+            min_x: -radius,
+            max_x: radius,
+            min_z: -radius,
+            max_z: radius,
+        }
+    }
+
+    pub fn contains(&self, x: i32, z: i32) -> bool {
+        (self.min_x..self.max_x).contains(&x) && (self.min_z..self.max_z).contains(&z)
+    }
+}
+
+impl Default for ChunkBounds {
+    fn default() -> Self {
+        Self::from_radius(10)
+    }
+}
+
+/// Loads the chunks of the world at `path` within `bounds`, then moves them all by `offset`
+/// chunks. Bounds are in the map's own coordinates, and everything after loading (spawnpoint,
+/// border, containers) is in the moved ones.
+pub fn read_world(
+    path: &str,
+    bounds: ChunkBounds,
+    offset: (i32, i32),
+) -> Result<(World, LoadReport)> {
     let folder = Path::new(path).join("region");
-    let folder = std::fs::read_dir(folder).unwrap();
+    let folder = std::fs::read_dir(&folder)
+        .wrap_err_with(|| format!("Failed to read region folder {}", folder.display()))?;
     let chunks = std::sync::Mutex::new(HashMap::new());
+    let failed_chunks = std::sync::Mutex::new(Vec::new());
+    let failed_regions = std::sync::Mutex::new(Vec::new());
+
+    let fail_region = |failure: String| {
+        warn!("Failed to open {failure}, skipping");
+        failed_regions
+            .lock()
+            .expect("Failed to lock failed region mutex")
+            .push(failure);
+    };
+
+    let fail_chunk = |failure: String| {
+        warn!("Failed to parse {failure}, skipping");
+        failed_chunks
+            .lock()
+            .expect("Failed to lock failed chunk mutex")
+            .push(failure);
+    };
 
-    folder.into_iter().par_bridge().for_each(|path| {
-        let file = File::open(path.unwrap().path()).expect("Failed to open file");
-        let mut region = Region::from_stream(file).expect("Failed to create region from stream");
+    folder.into_iter().par_bridge().for_each(|entry| {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(why) => return fail_region(format!("region folder entry: {why}")),
+        };
+
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(why) => return fail_region(format!("region {}: {why}", path.display())),
+        };
+
+        let mut region = match Region::from_stream(file) {
+            Ok(region) => region,
+            Err(why) => return fail_region(format!("region {}: {why}", path.display())),
+        };
 
         region.iter().par_bridge().for_each(|chunk| {
-            let chunk = chunk.unwrap();
-            let mut parsed: Chunk = fastnbt::from_bytes(&chunk.data).unwrap_or_else(|e| {
-                panic!(
-                    "Failed to parse chunk {e}: {}",
-                    &chunk
-                        .data
-                        .iter()
-                        .map(|b| b.to_string())
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                );
-            });
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(why) => {
+                    return fail_chunk(format!("a chunk in {}: {why}", path.display()));
+                }
+            };
+
+            let mut parsed: Chunk = match fastnbt::from_bytes(&chunk.data) {
+                Ok(parsed) => parsed,
+                Err(why) => {
+                    return fail_chunk(format!(
+                        "chunk ({}, {}) in {}: {why}",
+                        chunk.x,
+                        chunk.z,
+                        path.display()
+                    ));
+                }
+            };
 
-            if (-10..10).contains(&parsed.x_pos) && (-10..10).contains(&parsed.z_pos) {
+            if bounds.contains(parsed.x_pos, parsed.z_pos) {
                 parsed.sections.sort_by_key(|c| c.y);
+                parsed.shift(offset);
 
                 debug!(
                     "Successfully parsed chunk at {}, {}",
@@ -153,6 +389,108 @@ pub fn read_world(path: &str) -> Result<World> {
         });
     });
 
-    let chunks = chunks.lock().expect("Failed to lock chunk mutex");
-    Ok(World(chunks.clone()))
+    let chunks = chunks.into_inner().expect("Failed to lock chunk mutex");
+
+    let report = LoadReport {
+        chunks_loaded: chunks.len(),
+        failed_chunks: failed_chunks
+            .into_inner()
+            .expect("Failed to lock failed chunk mutex"),
+        failed_regions: failed_regions
+            .into_inner()
+            .expect("Failed to lock failed region mutex"),
+    };
+
+    if report.skipped_chunks() > 0 || !report.failed_regions.is_empty() {
+        warn!(
+            "Skipped {} chunks and {} region files while loading {path}",
+            report.skipped_chunks(),
+            report.failed_regions.len()
+        );
+    }
+
+    Ok((World(chunks), report))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::*;
+    use crate::{
+        net::{cache::WorldCache, player::testing},
+        protocol::{packets::play::ChunkDataUpdateLightC, Decoder, Packet},
+    };
+
+    #[test]
+    fn truncated_region_is_skipped() {
+        let map = fixtures::MapDir::new("truncated-region");
+        let good = fixtures::chunk(0, 0, vec![fixtures::air_section(0)]);
+        map.write_region(
+            "r.0.0.mca",
+            &fixtures::region(&[((0, 0), fixtures::compress(&good))]),
+        );
+
+        let cut = fixtures::chunk(0, -1, vec![fixtures::air_section(0)]);
+        let truncated = fixtures::region(&[((0, -1), fixtures::compress(&cut))]);
+        map.write_region("r.0.-1.mca", &truncated[..8192 + 10]);
+
+        let (world, report) = read_world(map.path(), ChunkBounds::default(), (0, 0)).unwrap();
+        assert_eq!(world.0.keys().collect::<Vec<_>>(), [&(0, 0)]);
+        assert_eq!(report.chunks_loaded, 1);
+        assert!(report.skipped_chunks() + report.failed_regions.len() > 0);
+    }
+
+    #[test]
+    fn safe_spawn_lands_on_top_of_the_ground() {
+        let world = World(HashMap::from([(
+            (0, 0),
+            fixtures::single_block_chunk("minecraft:stone"),
+        )]));
+
+        // from inside the block and from above it
+        assert_eq!(
+            world.find_safe_spawn((0.5, 64.0, 0.5), 64),
+            Some((0.5, 65.0, 0.5))
+        );
+        assert_eq!(
+            world.find_safe_spawn((0.5, 100.0, 0.5), 64),
+            Some((0.5, 65.0, 0.5))
+        );
+        // nothing but air next door
+        assert_eq!(world.find_safe_spawn((20.5, 64.0, 0.5), 64), None);
+    }
+
+    #[test]
+    fn origin_offset_moves_chunks_and_containers_together() {
+        let map = fixtures::MapDir::new("origin-offset");
+        let chest = fixtures::chunk_with_chest(&[Item {
+            slot: 0,
+            id: "minecraft:stone".to_owned(),
+            count: 1,
+        }]);
+        map.write_region(
+            "r.0.0.mca",
+            &fixtures::region(&[((0, 0), fixtures::compress(&chest))]),
+        );
+
+        let (world, _) = read_world(map.path(), ChunkBounds::default(), (3, -2)).unwrap();
+        let cache = WorldCache::from_anvil(testing::state(&[]), &world);
+
+        let [(pos, packet)] = &cache.encoded[..] else {
+            panic!("expected one chunk, got {}", cache.encoded.len());
+        };
+        assert_eq!(*pos, (3, -2));
+        let mut decoder = Decoder::new();
+        decoder.add_bytes(BytesMut::from(&packet[..]));
+        let frame = decoder.try_read_next().unwrap().unwrap();
+        assert_eq!(frame.id, ChunkDataUpdateLightC::ID);
+        assert_eq!(frame.body[..8], [0, 0, 0, 3, 0xFF, 0xFF, 0xFF, 0xFE]);
+
+        // the chest was at 0, 64, 0, and moves 3 chunks east and 2 north with its chunk
+        assert_eq!(
+            cache.containers.keys().collect::<Vec<_>>(),
+            [&(48, 64, -32)]
+        );
+    }
 }
@@ -0,0 +1,115 @@
+/*
+ * Copyright (c) 2024 Andrew Brower.
+ * This file is part of Crawlspace.
+ *
+ * Crawlspace is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Affero General Public
+ * License as published by the Free Software Foundation, either
+ * version 3 of the License, or (at your option) any later version.
+ *
+ * Crawlspace is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public
+ * License along with Crawlspace. If not, see
+ * <https://www.gnu.org/licenses/>.
+ */
+
+#![cfg(feature = "library")]
+
+//! Starts a server in-process and pings it like the multiplayer screen would.
+
+use std::time::Duration;
+
+use bytes::BytesMut;
+use crawlspace::{
+    protocol::{
+        datatypes::{Bounded, VarInt},
+        Decode, Decoder, Encode, Frame,
+    },
+    Crawlspace,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+/// Frames an uncompressed packet.
+fn packet(id: i32, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    VarInt(id).encode(&mut packet).unwrap();
+    packet.extend_from_slice(body);
+
+    let mut framed = Vec::new();
+    VarInt(packet.len() as i32).encode(&mut framed).unwrap();
+    framed.extend(packet);
+    framed
+}
+
+async fn next_frame(stream: &mut TcpStream, decoder: &mut Decoder) -> Frame {
+    loop {
+        if let Some(frame) = decoder.try_read_next().unwrap() {
+            return frame;
+        }
+
+        let mut buf = BytesMut::with_capacity(4096);
+        let read = timeout(Duration::from_secs(5), stream.read_buf(&mut buf))
+            .await
+            .expect("timed out waiting for the server")
+            .unwrap();
+        assert_ne!(read, 0, "server closed the connection");
+        decoder.add_bytes(buf);
+    }
+}
+
+#[tokio::test]
+async fn answers_a_status_ping() {
+    let map_dir = std::env::temp_dir().join(format!("crawlspace-library-{}", std::process::id()));
+    std::fs::create_dir_all(map_dir.join("region")).unwrap();
+
+    let crawlspace = Crawlspace::builder(map_dir.to_str().unwrap())
+        .unwrap()
+        .addr("127.0.0.1")
+        .port(0)
+        .motd("In-process")
+        .velocity_forwarding(false)
+        .start()
+        .await
+        .unwrap();
+    let addr = crawlspace.local_addr();
+    assert_ne!(addr.port(), 0);
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut decoder = Decoder::new();
+
+    let mut handshake = Vec::new();
+    VarInt(crawlspace::VERSION_NUM)
+        .encode(&mut handshake)
+        .unwrap();
+    Bounded::<&str, 255>("localhost")
+        .encode(&mut handshake)
+        .unwrap();
+    handshake.extend(addr.port().to_be_bytes());
+    VarInt(1).encode(&mut handshake).unwrap();
+    stream.write_all(&packet(0x00, &handshake)).await.unwrap();
+    stream.write_all(&packet(0x00, &[])).await.unwrap();
+
+    let response = next_frame(&mut stream, &mut decoder).await;
+    assert_eq!(response.id, 0x00);
+    let json = Bounded::<&str>::decode(&mut &response.body[..]).unwrap();
+    let status: serde_json::Value = serde_json::from_str(json.0).unwrap();
+    assert_eq!(status["version"]["protocol"], crawlspace::VERSION_NUM);
+    assert_eq!(status["description"]["text"], "In-process");
+
+    let payload = 0x0123_4567_89AB_CDEFi64.to_be_bytes();
+    stream.write_all(&packet(0x01, &payload)).await.unwrap();
+    let pong = next_frame(&mut stream, &mut decoder).await;
+    assert_eq!(pong.id, 0x01);
+    assert_eq!(&pong.body[..], &payload);
+
+    crawlspace.shutdown();
+    let _ = std::fs::remove_dir_all(map_dir);
+}